@@ -0,0 +1,129 @@
+//! Baselines for the hot paths: the container tag scanner, type detection, the ZIP EOCD
+//! search and a deeply nested extraction. Run with `cargo bench`; pass a substring to run
+//! only matching cases (`cargo bench -- eocd`).
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use common::{container_entry, zip_archive};
+use firmparse::extract::extract_to_memory;
+use firmparse::file_types::detect_file_type;
+use firmparse::parser::parse_file_entries;
+use firmparse::zip_utils::{slice_to_eocd, slice_to_eocd_with};
+
+/// Deterministic noise with no ZIP signatures or container tags in it
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            match (state >> 32) as u8 {
+                b'P' | b'"' => 0,
+                b => b,
+            }
+        })
+        .collect()
+}
+
+/// 2,000 entries of 4 KiB each, about 8 MiB
+fn large_container() -> Vec<u8> {
+    let content = noise(4096);
+    (0..2000)
+        .flat_map(|i| container_entry(&format!("part_{:04}.bin", i), &content))
+        .collect()
+}
+
+/// A ZIP holding a ZIP holding a ZIP..., `depth` levels deep, inside a container entry
+fn nested_archive(depth: usize) -> Vec<u8> {
+    let mut payload = noise(64 * 1024);
+    for level in 0..depth {
+        payload = zip_archive(&[(&format!("level_{}.zip", level), &payload)]);
+    }
+    container_entry("nested.zip", &payload)
+}
+
+fn bench_parse_file_entries(c: &mut Criterion) {
+    let container = large_container();
+    let binary = noise(16 * 1024 * 1024);
+    let nested = nested_archive(8);
+
+    let mut group = c.benchmark_group("parse_file_entries");
+    for (name, data) in [
+        ("large_container", &container),
+        ("big_binary", &binary),
+        ("nested_archive", &nested),
+    ] {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| parse_file_entries(black_box(data)).len())
+        });
+    }
+    group.finish();
+}
+
+fn bench_detect_file_type(c: &mut Criterion) {
+    let container = large_container();
+    let binary = noise(16 * 1024 * 1024);
+    let inner_zip = parse_file_entries(&nested_archive(8))[0].content.to_vec();
+
+    let mut group = c.benchmark_group("detect_file_type");
+    for (name, data, filename) in [
+        ("large_container", &container, Some("fw.bin")),
+        ("big_binary", &binary, None),
+        ("nested_zip", &inner_zip, Some("nested.zip")),
+    ] {
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| detect_file_type(black_box(data), filename))
+        });
+    }
+    group.finish();
+}
+
+fn bench_slice_to_eocd(c: &mut Criterion) {
+    let binary = noise(16 * 1024 * 1024);
+    let inner_zip = parse_file_entries(&nested_archive(8))[0].content.to_vec();
+
+    let mut group = c.benchmark_group("slice_to_eocd");
+    group.throughput(Throughput::Bytes(binary.len() as u64));
+    group.bench_function("big_binary", |b| {
+        b.iter(|| slice_to_eocd(black_box(&binary)).is_some())
+    });
+    group.bench_function("big_binary_full_scan", |b| {
+        b.iter(|| slice_to_eocd_with(black_box(&binary), true).is_some())
+    });
+    group.throughput(Throughput::Bytes(inner_zip.len() as u64));
+    group.bench_function("nested_zip", |b| {
+        b.iter(|| slice_to_eocd(black_box(&inner_zip)).is_some())
+    });
+    group.finish();
+}
+
+/// The full recursive walk, to catch costs that compound with nesting depth
+fn bench_extract_to_memory(c: &mut Criterion) {
+    let nested = nested_archive(8);
+
+    let mut group = c.benchmark_group("extract_to_memory");
+    group.throughput(Throughput::Bytes(nested.len() as u64));
+    group.bench_function("nested_archive", |b| {
+        b.iter(|| {
+            extract_to_memory(black_box(&nested), Some("nested.bin"))
+                .children
+                .len()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_parse_file_entries,
+    bench_detect_file_type,
+    bench_slice_to_eocd,
+    bench_extract_to_memory
+);
+criterion_main!(benches);
@@ -0,0 +1,674 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::file_entry::{FileEntry, TagStyle};
+use crate::file_types::FileFamily;
+use crate::integrity::meta_matches_crc32;
+
+/// A guess at what the 4-byte `content_meta` field encodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaHypothesis {
+    LengthBe,
+    LengthLe,
+    Crc32Be,
+    Crc32Le,
+    /// The same value in every entry (a magic or version marker)
+    Constant,
+}
+
+impl MetaHypothesis {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetaHypothesis::LengthBe => "len_be",
+            MetaHypothesis::LengthLe => "len_le",
+            MetaHypothesis::Crc32Be => "crc32_be",
+            MetaHypothesis::Crc32Le => "crc32_le",
+            MetaHypothesis::Constant => "constant",
+        }
+    }
+}
+
+/// How well each hypothesis about `content_meta` fits a set of entries
+#[derive(Debug)]
+pub struct MetaCorrelation {
+    /// Entries that carried a `content_meta` field
+    pub total: usize,
+    /// Matching entry count per hypothesis, in a fixed order
+    pub matches: Vec<(MetaHypothesis, usize)>,
+    /// The most common meta value, which the `Constant` count refers to
+    pub most_common: Option<[u8; 4]>,
+}
+
+impl MetaCorrelation {
+    /// The hypothesis matching the most entries, if any matched at all
+    /// Ties go to the earlier hypothesis (length, then CRC32, then constant)
+    pub fn best(&self) -> Option<(MetaHypothesis, usize)> {
+        self.matches
+            .iter()
+            .copied()
+            .filter(|&(_, count)| count > 0)
+            .fold(None, |best, candidate| match best {
+                Some((_, count)) if count >= candidate.1 => best,
+                _ => Some(candidate),
+            })
+    }
+}
+
+impl fmt::Display for MetaCorrelation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.best() {
+            Some((MetaHypothesis::Constant, count)) => write!(
+                f,
+                "meta==constant {:02x?} in {}/{} entries",
+                self.most_common.unwrap_or_default(),
+                count,
+                self.total
+            ),
+            Some((hypothesis, count)) => write!(
+                f,
+                "meta=={} in {}/{} entries",
+                hypothesis.name(),
+                count,
+                self.total
+            ),
+            None => write!(f, "no hypothesis fits ({} entries)", self.total),
+        }
+    }
+}
+
+/// How many entries carry each distinct 4-byte `header_data` value
+/// Entries without a header are left out
+pub fn header_histogram(entries: &[FileEntry]) -> BTreeMap<[u8; 4], usize> {
+    let mut counts = BTreeMap::new();
+    for header in entries.iter().filter_map(|e| e.header_data) {
+        *counts.entry(*header).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Entry count per filename extension, for each distinct (header, meta) pair
+/// Missing fields are None; names without a dot count under their whole (lowercased) name
+pub type MetadataSummary = BTreeMap<(Option<[u8; 4]>, Option<[u8; 4]>), BTreeMap<String, usize>>;
+
+/// Tally entries by header and meta bytes, then by extension
+pub fn metadata_summary(entries: &[FileEntry]) -> MetadataSummary {
+    let mut summary = MetadataSummary::new();
+    for entry in entries {
+        let ext = entry
+            .filename
+            .as_ref()
+            .and_then(|f| f.rsplit('.').next())
+            .unwrap_or("<none>")
+            .to_lowercase();
+        let key = (entry.header_data.copied(), entry.content_meta.copied());
+        *summary.entry(key).or_default().entry(ext).or_insert(0) += 1;
+    }
+    summary
+}
+
+/// Add the counts of `other` to `summary`, e.g. to build one table across many images
+pub fn merge_metadata_summary(summary: &mut MetadataSummary, other: MetadataSummary) {
+    for (key, exts) in other {
+        let counts = summary.entry(key).or_default();
+        for (ext, count) in exts {
+            *counts.entry(ext).or_insert(0) += count;
+        }
+    }
+}
+
+/// Test each `content_meta` hypothesis against every entry
+pub fn correlate_meta(entries: &[FileEntry]) -> MetaCorrelation {
+    let mut counts = [0usize; 4];
+    let mut values: HashMap<[u8; 4], usize> = HashMap::new();
+    let mut total = 0;
+
+    for entry in entries {
+        let meta = match entry.content_meta {
+            Some(meta) => *meta,
+            None => continue,
+        };
+        total += 1;
+        *values.entry(meta).or_insert(0) += 1;
+
+        let be = u32::from_be_bytes(meta);
+        let le = u32::from_le_bytes(meta);
+        let len = entry.content_length as u32;
+        let crc = crc32fast::hash(entry.content);
+
+        for (count, hit) in counts
+            .iter_mut()
+            .zip([be == len, le == len, be == crc, le == crc])
+        {
+            if hit {
+                *count += 1;
+            }
+        }
+    }
+
+    // Pick the most common value deterministically when counts tie
+    let most_common = values
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .filter(|&(_, count)| count > 1 || total == 1);
+
+    MetaCorrelation {
+        total,
+        matches: vec![
+            (MetaHypothesis::LengthBe, counts[0]),
+            (MetaHypothesis::LengthLe, counts[1]),
+            (MetaHypothesis::Crc32Be, counts[2]),
+            (MetaHypothesis::Crc32Le, counts[3]),
+            (MetaHypothesis::Constant, most_common.map_or(0, |(_, c)| c)),
+        ],
+        most_common: most_common.map(|(value, _)| value),
+    }
+}
+
+/// A checksum algorithm `bruteforce_checksum` knows how to compute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChecksumAlgorithm {
+    Crc32,
+    /// CRC-32 with the Castagnoli polynomial
+    Crc32c,
+    /// CRC-16/CCITT-FALSE, zero-extended to 32 bits
+    Crc16Ccitt,
+    Adler32,
+    /// Wrapping sum of all bytes
+    Sum32,
+    /// XOR of the content as little-endian 32-bit words (zero padded)
+    Xor32,
+}
+
+impl ChecksumAlgorithm {
+    /// Every algorithm, in the order they are tried
+    pub const ALL: [ChecksumAlgorithm; 6] = [
+        ChecksumAlgorithm::Crc32,
+        ChecksumAlgorithm::Crc32c,
+        ChecksumAlgorithm::Crc16Ccitt,
+        ChecksumAlgorithm::Adler32,
+        ChecksumAlgorithm::Sum32,
+        ChecksumAlgorithm::Xor32,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Crc32 => "crc32",
+            ChecksumAlgorithm::Crc32c => "crc32c",
+            ChecksumAlgorithm::Crc16Ccitt => "crc16-ccitt",
+            ChecksumAlgorithm::Adler32 => "adler32",
+            ChecksumAlgorithm::Sum32 => "sum32",
+            ChecksumAlgorithm::Xor32 => "xor32",
+        }
+    }
+
+    pub fn compute(&self, data: &[u8]) -> u32 {
+        match self {
+            ChecksumAlgorithm::Crc32 => crc32fast::hash(data),
+            ChecksumAlgorithm::Crc32c => crc32c(data),
+            ChecksumAlgorithm::Crc16Ccitt => crc16_ccitt(data) as u32,
+            ChecksumAlgorithm::Adler32 => adler32(data),
+            ChecksumAlgorithm::Sum32 => {
+                data.iter().fold(0u32, |acc, &b| acc.wrapping_add(b as u32))
+            }
+            ChecksumAlgorithm::Xor32 => data.chunks(4).fold(0u32, |acc, chunk| {
+                let mut word = [0u8; 4];
+                word[..chunk.len()].copy_from_slice(chunk);
+                acc ^ u32::from_le_bytes(word)
+            }),
+        }
+    }
+}
+
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn crc16_ccitt(data: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % MOD;
+        b = (b + a) % MOD;
+    }
+    (b << 16) | a
+}
+
+/// An algorithm and the byte order its result is stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChecksumSpec {
+    pub algorithm: ChecksumAlgorithm,
+    pub big_endian: bool,
+}
+
+impl fmt::Display for ChecksumSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let order = if self.big_endian { "be" } else { "le" };
+        write!(f, "{}_{}", self.algorithm.name(), order)
+    }
+}
+
+/// The first known checksum of `content` that, stored big- or little-endian, equals `target`
+pub fn bruteforce_checksum(content: &[u8], target: &[u8; 4]) -> Option<ChecksumSpec> {
+    ChecksumAlgorithm::ALL.into_iter().find_map(|algorithm| {
+        let value = algorithm.compute(content);
+        [true, false]
+            .into_iter()
+            .find(|&big_endian| {
+                let bytes = if big_endian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                };
+                bytes == *target
+            })
+            .map(|big_endian| ChecksumSpec {
+                algorithm,
+                big_endian,
+            })
+    })
+}
+
+/// The checksum that explains a 4-byte field in the most entries
+#[derive(Debug)]
+pub struct ChecksumConsensus {
+    /// Non-empty entries that carried the field
+    pub total: usize,
+    pub best: Option<(ChecksumSpec, usize)>,
+}
+
+impl fmt::Display for ChecksumConsensus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.best {
+            Some((spec, count)) => write!(f, "{} in {}/{} entries", spec, count, self.total),
+            None => write!(f, "no known checksum ({} entries)", self.total),
+        }
+    }
+}
+
+/// Bruteforce the field `pick` selects in every entry and tally the winners
+/// Empty entries are left out: most checksums of nothing are 0 and would match padding
+pub fn checksum_consensus<'e>(
+    entries: &'e [FileEntry],
+    pick: impl Fn(&'e FileEntry) -> Option<&'e [u8; 4]>,
+) -> ChecksumConsensus {
+    let mut counts: HashMap<ChecksumSpec, usize> = HashMap::new();
+    let mut total = 0;
+    for entry in entries.iter().filter(|e| !e.content.is_empty()) {
+        let target = match pick(entry) {
+            Some(t) => t,
+            None => continue,
+        };
+        total += 1;
+        if let Some(spec) = bruteforce_checksum(entry.content, target) {
+            *counts.entry(spec).or_insert(0) += 1;
+        }
+    }
+
+    // Ties go to the earlier algorithm, big-endian first
+    let best = counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)));
+    ChecksumConsensus { total, best }
+}
+
+/// Which generation of the container format an image looks like, judged from its tags,
+/// header/meta fields and any bytes ahead of the first entry
+/// A heuristic: it sorts images into buckets for a quick compatibility read, and is where
+/// schema-specific parsing would hang
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerSchema {
+    /// Quoted tags, per-entry header values, meta bytes that aren't a CRC32 of the content
+    EvoGen1,
+    /// As gen1, but most entries' meta is a CRC32 of their content
+    EvoGen2,
+    /// Vendor bytes ahead of the first entry, as in signed or wrapped images
+    Wrapped,
+    /// Tags without their quotes
+    BareTags,
+    /// Header and meta fields all zero, as `pack` writes without a manifest
+    Repacked,
+    Unknown,
+}
+
+impl ContainerSchema {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContainerSchema::EvoGen1 => "EVO-gen1",
+            ContainerSchema::EvoGen2 => "EVO-gen2",
+            ContainerSchema::Wrapped => "wrapped",
+            ContainerSchema::BareTags => "bare-tag",
+            ContainerSchema::Repacked => "repacked",
+            ContainerSchema::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for ContainerSchema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (heuristic)", self.name())
+    }
+}
+
+/// Guess a container's schema from its parsed entries, how their tags were written, and
+/// `header`, the bytes before its first `<filetransfer>` tag
+pub fn infer_container_version(
+    entries: &[FileEntry],
+    header: &[u8],
+    tag_style: Option<TagStyle>,
+) -> ContainerSchema {
+    if entries.is_empty() {
+        return ContainerSchema::Unknown;
+    }
+    if matches!(tag_style, Some(TagStyle::Bare | TagStyle::Mixed)) {
+        return ContainerSchema::BareTags;
+    }
+    let zeroed = |field: Option<&[u8; 4]>| field.is_none_or(|bytes| *bytes == [0; 4]);
+    if entries
+        .iter()
+        .all(|e| zeroed(e.header_data) && zeroed(e.content_meta))
+    {
+        return ContainerSchema::Repacked;
+    }
+    if header.iter().any(|&b| b != 0) {
+        return ContainerSchema::Wrapped;
+    }
+
+    let meta = correlate_meta(entries);
+    match meta.best() {
+        Some((MetaHypothesis::Crc32Be | MetaHypothesis::Crc32Le, count))
+            if count * 2 > meta.total =>
+        {
+            ContainerSchema::EvoGen2
+        }
+        _ if !header_histogram(entries).is_empty() => ContainerSchema::EvoGen1,
+        _ => ContainerSchema::Unknown,
+    }
+}
+
+/// Entropy (bits/byte) above which content is most likely compressed or encrypted
+pub const HIGH_ENTROPY: f64 = 7.5;
+
+/// Shannon entropy of the data in bits per byte (0.0 for empty data, at most 8.0)
+pub fn shannon_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let mut counts = [0usize; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Below this size entropy is too noisy to call content encrypted
+const MIN_ENCRYPTED_LEN: usize = 256;
+
+/// Guess whether an entry's content is encrypted (or otherwise obfuscated) rather than just
+/// an unrecognized format: high entropy in something that isn't a self-describing
+/// compressed format, with a `content_meta` that doesn't checksum the plaintext we see
+/// No bit of `content_meta` is known to flag encryption, so the meta only counts through
+/// the checksum; entries without meta are judged on entropy alone
+pub fn is_likely_encrypted(entry: &FileEntry) -> bool {
+    if entry.content.len() < MIN_ENCRYPTED_LEN {
+        return false;
+    }
+    let compressed = matches!(
+        entry.detected_type().family(),
+        FileFamily::Container | FileFamily::Archive | FileFamily::Compressed | FileFamily::Image
+    );
+    let checksum_matches = entry.content_meta.is_some() && meta_matches_crc32(entry);
+    !compressed && !checksum_matches && shannon_entropy(entry.content) > HIGH_ENTROPY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shannon_entropy() {
+        assert_eq!(shannon_entropy(b""), 0.0);
+        assert_eq!(shannon_entropy(&[0x41; 100]), 0.0);
+        assert_eq!(shannon_entropy(b"abab"), 1.0);
+
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(shannon_entropy(&all_bytes), 8.0);
+        assert!(shannon_entropy(&all_bytes) > HIGH_ENTROPY);
+    }
+
+    fn entry<'a>(content: &'a [u8], meta: &'a [u8; 4]) -> FileEntry<'a> {
+        FileEntry {
+            index: 0,
+            filename: None,
+            raw_name_bytes: None,
+            header_data: None,
+            content_meta: Some(meta),
+            content,
+            content_length: content.len(),
+            raw_content_data: content,
+            content_data_offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_header_histogram() {
+        let common = [0xfd, 0xce, 0x69, 0x48];
+        let rare = [0x01, 0x02, 0x03, 0x04];
+        let mut entries: Vec<_> = (0..4).map(|_| entry(b"a", &[0; 4])).collect();
+        entries[0].header_data = Some(&common);
+        entries[1].header_data = Some(&rare);
+        entries[2].header_data = Some(&common);
+
+        let histogram = header_histogram(&entries);
+        assert_eq!(histogram.len(), 2);
+        assert_eq!(histogram[&common], 2);
+        assert_eq!(histogram[&rare], 1);
+    }
+
+    /// Deterministic high-entropy bytes (xorshift32)
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x1234_5678u32;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_is_likely_encrypted() {
+        let blob = noise(4096);
+        assert!(is_likely_encrypted(&entry(&blob, &[0; 4])));
+
+        // A matching checksum means the content is what the vendor checksummed
+        let crc = crc32fast::hash(&blob).to_be_bytes();
+        assert!(!is_likely_encrypted(&entry(&blob, &crc)));
+
+        // Compressed formats are high entropy by nature
+        let mut gzip = vec![0x1f, 0x8b, 0x08, 0x00];
+        gzip.extend_from_slice(&blob);
+        assert!(!is_likely_encrypted(&entry(&gzip, &[0; 4])));
+
+        assert!(!is_likely_encrypted(&entry(&blob[..64], &[0; 4])));
+        assert!(!is_likely_encrypted(&entry(&[0x41; 4096], &[0; 4])));
+    }
+
+    #[test]
+    fn test_checksum_algorithms() {
+        // Standard check values over "123456789"
+        let check = b"123456789";
+        assert_eq!(ChecksumAlgorithm::Crc32.compute(check), 0xcbf4_3926);
+        assert_eq!(ChecksumAlgorithm::Crc32c.compute(check), 0xe306_9283);
+        assert_eq!(ChecksumAlgorithm::Crc16Ccitt.compute(check), 0x29b1);
+        assert_eq!(ChecksumAlgorithm::Adler32.compute(check), 0x091e_01de);
+        assert_eq!(ChecksumAlgorithm::Sum32.compute(check), 477);
+        assert_eq!(ChecksumAlgorithm::Xor32.compute(b"\x01\0\0\0\x03"), 2);
+    }
+
+    #[test]
+    fn test_bruteforce_checksum() {
+        let content = b"firmware";
+        let adler = ChecksumAlgorithm::Adler32.compute(content);
+        assert_eq!(
+            bruteforce_checksum(content, &adler.to_le_bytes()),
+            Some(ChecksumSpec {
+                algorithm: ChecksumAlgorithm::Adler32,
+                big_endian: false
+            })
+        );
+        assert_eq!(bruteforce_checksum(content, &[1, 2, 3, 4]), None);
+    }
+
+    #[test]
+    fn test_checksum_consensus() {
+        let metas = [
+            ChecksumAlgorithm::Crc32c.compute(b"one").to_be_bytes(),
+            ChecksumAlgorithm::Crc32c.compute(b"two").to_be_bytes(),
+            [9, 9, 9, 9],
+            [0, 0, 0, 0],
+        ];
+        let entries = [
+            entry(b"one", &metas[0]),
+            entry(b"two", &metas[1]),
+            entry(b"three", &metas[2]),
+            entry(b"", &metas[3]),
+        ];
+        let consensus = checksum_consensus(&entries, |e| e.content_meta);
+        assert_eq!(consensus.to_string(), "crc32c_be in 2/3 entries");
+    }
+
+    #[test]
+    fn test_meta_is_length_le() {
+        let metas = [
+            3u32.to_le_bytes(),
+            5u32.to_le_bytes(),
+            [0xde, 0xad, 0xbe, 0xef],
+        ];
+        let entries = [
+            entry(b"abc", &metas[0]),
+            entry(b"hello", &metas[1]),
+            entry(b"xy", &metas[2]),
+        ];
+        let correlation = correlate_meta(&entries);
+        assert_eq!(correlation.best(), Some((MetaHypothesis::LengthLe, 2)));
+        assert_eq!(correlation.to_string(), "meta==len_le in 2/3 entries");
+    }
+
+    #[test]
+    fn test_meta_is_crc32_be() {
+        let metas = [
+            crc32fast::hash(b"one").to_be_bytes(),
+            crc32fast::hash(b"two").to_be_bytes(),
+        ];
+        let entries = [entry(b"one", &metas[0]), entry(b"two", &metas[1])];
+        assert_eq!(
+            correlate_meta(&entries).best(),
+            Some((MetaHypothesis::Crc32Be, 2))
+        );
+    }
+
+    #[test]
+    fn test_meta_is_constant() {
+        let meta = [0x33, 0xa8, 0x3b, 0x1f];
+        let entries = [entry(b"one", &meta), entry(b"two", &meta)];
+        let correlation = correlate_meta(&entries);
+        assert_eq!(correlation.best(), Some((MetaHypothesis::Constant, 2)));
+        assert_eq!(
+            correlation.to_string(),
+            "meta==constant [33, a8, 3b, 1f] in 2/2 entries"
+        );
+    }
+
+    #[test]
+    fn test_infer_container_version() {
+        let header = [0xfd, 0xce, 0x69, 0x48];
+        let constant = [0x33, 0xa8, 0x3b, 0x1f];
+        let crcs = [
+            crc32fast::hash(b"one").to_be_bytes(),
+            crc32fast::hash(b"two").to_be_bytes(),
+        ];
+        let mut gen1 = [entry(b"one", &constant), entry(b"two", &constant)];
+        let mut gen2 = [entry(b"one", &crcs[0]), entry(b"two", &crcs[1])];
+        for e in gen1.iter_mut().chain(gen2.iter_mut()) {
+            e.header_data = Some(&header);
+        }
+        let quoted = Some(TagStyle::Quoted);
+
+        let gen1_schema = infer_container_version(&gen1, &[], quoted);
+        assert_eq!(gen1_schema, ContainerSchema::EvoGen1);
+        assert_eq!(gen1_schema.to_string(), "EVO-gen1 (heuristic)");
+        assert_eq!(
+            infer_container_version(&gen2, &[], quoted),
+            ContainerSchema::EvoGen2
+        );
+        assert_eq!(
+            infer_container_version(&gen2, &[0; 16], quoted),
+            ContainerSchema::EvoGen2
+        );
+        assert_eq!(
+            infer_container_version(&gen2, b"SIGNED", quoted),
+            ContainerSchema::Wrapped
+        );
+        assert_eq!(
+            infer_container_version(&gen1, &[], Some(TagStyle::Bare)),
+            ContainerSchema::BareTags
+        );
+
+        let zeroed = [entry(b"one", &[0; 4]), entry(b"two", &[0; 4])];
+        assert_eq!(
+            infer_container_version(&zeroed, &[], quoted),
+            ContainerSchema::Repacked
+        );
+        assert_eq!(
+            infer_container_version(&[], &[], None),
+            ContainerSchema::Unknown
+        );
+    }
+
+    #[test]
+    fn test_no_hypothesis_fits() {
+        let metas = [[1, 2, 3, 4], [5, 6, 7, 8]];
+        let entries = [entry(b"one", &metas[0]), entry(b"two", &metas[1])];
+        let correlation = correlate_meta(&entries);
+        assert_eq!(correlation.best(), None);
+        assert_eq!(correlation.to_string(), "no hypothesis fits (2 entries)");
+    }
+}
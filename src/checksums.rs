@@ -0,0 +1,250 @@
+use std::path::Path;
+
+use crate::extract::ExtractedNode;
+use crate::hash::{sha256, to_hex};
+
+/// A digest a vendor checksum list expects a component to have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpectedDigest {
+    Sha256([u8; 32]),
+    Crc32(u32),
+}
+
+impl ExpectedDigest {
+    /// Decode a hex digest, picking the algorithm by its length (64 or 8 digits)
+    pub fn from_hex(text: &str) -> Option<ExpectedDigest> {
+        let text = text.strip_prefix("0x").unwrap_or(text);
+        match text.len() {
+            64 => {
+                let mut digest = [0u8; 32];
+                for (i, byte) in digest.iter_mut().enumerate() {
+                    *byte = u8::from_str_radix(text.get(i * 2..i * 2 + 2)?, 16).ok()?;
+                }
+                Some(ExpectedDigest::Sha256(digest))
+            }
+            8 => u32::from_str_radix(text, 16)
+                .ok()
+                .map(ExpectedDigest::Crc32),
+            _ => None,
+        }
+    }
+
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            ExpectedDigest::Sha256(_) => "sha256",
+            ExpectedDigest::Crc32(_) => "crc32",
+        }
+    }
+
+    /// The same kind of digest computed over `data`, as hex
+    pub fn compute_hex(&self, data: &[u8]) -> String {
+        match self {
+            ExpectedDigest::Sha256(_) => to_hex(&sha256(data)),
+            ExpectedDigest::Crc32(_) => format!("{:08x}", crc32fast::hash(data)),
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        match self {
+            ExpectedDigest::Sha256(digest) => to_hex(digest),
+            ExpectedDigest::Crc32(crc) => format!("{:08x}", crc),
+        }
+    }
+
+    pub fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            ExpectedDigest::Sha256(digest) => sha256(data) == *digest,
+            ExpectedDigest::Crc32(crc) => crc32fast::hash(data) == *crc,
+        }
+    }
+}
+
+/// One line of a checksum list
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChecksumEntry {
+    pub name: String,
+    pub digest: ExpectedDigest,
+}
+
+/// Parse a checksum list in `sha256sum` style (`<hex>  <name>`, or `<hex> *<name>`) or as
+/// `<name>=<hex>` lines; both may be mixed. Blank lines and `#` comments are skipped
+/// A 64-digit digest is SHA-256, an 8-digit one CRC32
+pub fn parse_checksum_file(text: &str) -> Result<Vec<ChecksumEntry>, String> {
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // `sha256sum` style first, so a name containing '=' is still read correctly
+        let sum_style = line
+            .split_once(char::is_whitespace)
+            .filter(|(hex, _)| ExpectedDigest::from_hex(hex).is_some());
+        let (name, hex) = match (sum_style, line.split_once('=')) {
+            (Some((hex, name)), _) => (name.trim_start().trim_start_matches('*'), hex),
+            (None, Some((name, hex))) => (name.trim(), hex.trim()),
+            (None, None) => return Err(format!("line {}: expected a digest and a name", i + 1)),
+        };
+        let digest = ExpectedDigest::from_hex(hex).ok_or_else(|| {
+            format!(
+                "line {}: '{}' is not a SHA-256 or CRC32 hex digest",
+                i + 1,
+                hex
+            )
+        })?;
+        if name.is_empty() {
+            return Err(format!("line {}: missing name", i + 1));
+        }
+        entries.push(ChecksumEntry {
+            name: name.to_string(),
+            digest,
+        });
+    }
+    Ok(entries)
+}
+
+/// How a listed component compared against the extracted tree
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChecksumOutcome {
+    /// Found at `path` with the expected digest
+    Match { path: String },
+    /// Found at `path`, but its digest is `actual`
+    Mismatch { path: String, actual: String },
+    /// No extracted file has the listed name
+    Missing,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChecksumResult {
+    pub entry: ChecksumEntry,
+    pub outcome: ChecksumOutcome,
+}
+
+impl ChecksumResult {
+    pub fn passed(&self) -> bool {
+        matches!(self.outcome, ChecksumOutcome::Match { .. })
+    }
+}
+
+/// Look up every listed name in the tree and compare digests
+/// A name matches a file's path below the root as it would be extracted, or any trailing
+/// part of it (`config.json` or `pkg/config.json`); the first match in tree order is used
+pub fn check_checksums(root: &ExtractedNode, list: &[ChecksumEntry]) -> Vec<ChecksumResult> {
+    let mut files = Vec::new();
+    if let Some(name) = &root.name {
+        files.push((name.clone(), &root.data[..]));
+    }
+    for child in &root.children {
+        collect_paths(child, "", &mut files);
+    }
+
+    list.iter()
+        .map(|entry| {
+            let found = files.iter().find(|(path, _)| {
+                path == &entry.name || path.ends_with(&format!("/{}", entry.name))
+            });
+            let outcome = match found {
+                Some((path, data)) if entry.digest.matches(data) => {
+                    ChecksumOutcome::Match { path: path.clone() }
+                }
+                Some((path, data)) => ChecksumOutcome::Mismatch {
+                    path: path.clone(),
+                    actual: entry.digest.compute_hex(data),
+                },
+                None => ChecksumOutcome::Missing,
+            };
+            ChecksumResult {
+                entry: entry.clone(),
+                outcome,
+            }
+        })
+        .collect()
+}
+
+fn collect_paths<'n>(node: &'n ExtractedNode, prefix: &str, files: &mut Vec<(String, &'n [u8])>) {
+    let name = match &node.name {
+        Some(name) => name.clone(),
+        None => format!("<entry {}>", node.index),
+    };
+    files.push((format!("{}{}", prefix, name), &node.data));
+
+    // Nested files land in a directory named after the file's stem, as when extracting
+    let stem = Path::new(&name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or(name.clone());
+    for child in &node.children {
+        collect_paths(child, &format!("{}{}/", prefix, stem), files);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_types::FileType;
+
+    fn node<'a>(name: &str, data: &'a [u8], children: Vec<ExtractedNode<'a>>) -> ExtractedNode<'a> {
+        ExtractedNode {
+            index: 0,
+            name: Some(name.to_string()),
+            file_type: FileType::Unknown,
+            data: data.into(),
+            children,
+        }
+    }
+
+    #[test]
+    fn test_parse_checksum_file() {
+        let sha = to_hex(&sha256(b"abc"));
+        let text = format!(
+            "# vendor list\n{}  gimbal.upg\n{} *pkg/cfg.json\n\nconfig.json = 0x352441c2\n",
+            sha, sha
+        );
+        let entries = parse_checksum_file(&text).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].name, "gimbal.upg");
+        assert_eq!(entries[1].name, "pkg/cfg.json");
+        assert_eq!(entries[0].digest.algorithm(), "sha256");
+        assert_eq!(entries[2].name, "config.json");
+        assert_eq!(entries[2].digest, ExpectedDigest::Crc32(0x352441c2));
+
+        assert!(parse_checksum_file("abc  name\n").is_err());
+        assert!(parse_checksum_file("justonefield\n").is_err());
+    }
+
+    #[test]
+    fn test_check_checksums() {
+        let root = node(
+            "fw.bin",
+            b"whole image",
+            vec![
+                node("config.json", b"abc", vec![]),
+                node("pkg.zip", b"zip", vec![node("cfg.json", b"{}", vec![])]),
+            ],
+        );
+        let list = parse_checksum_file(&format!(
+            "config.json={:08x}\n{}  pkg/cfg.json\nmissing.bin=00000000\n",
+            crc32fast::hash(b"abc"),
+            to_hex(&sha256(b"tampered"))
+        ))
+        .unwrap();
+
+        let results = check_checksums(&root, &list);
+        assert_eq!(
+            results[0].outcome,
+            ChecksumOutcome::Match {
+                path: "config.json".to_string()
+            }
+        );
+        assert_eq!(
+            results[1].outcome,
+            ChecksumOutcome::Mismatch {
+                path: "pkg/cfg.json".to_string(),
+                actual: to_hex(&sha256(b"{}")),
+            }
+        );
+        assert_eq!(results[2].outcome, ChecksumOutcome::Missing);
+        assert!(results[0].passed() && !results[1].passed());
+    }
+}
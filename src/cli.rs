@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+
+/// Command line arguments split into positionals and `--flag [value]` / `--flag=value` options
+#[derive(Debug, Default)]
+pub struct Args {
+    pub positional: Vec<String>,
+    flags: HashMap<String, Option<String>>,
+}
+
+impl Args {
+    /// Split `args` into positionals and flags
+    /// Flags listed in `value_flags` consume the following argument as their value
+    /// Any flag can take an attached value as `--flag=value`, which makes it optional
+    pub fn parse(args: &[String], value_flags: &[&str]) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        let mut rest = args.iter();
+
+        while let Some(arg) = rest.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                if let Some((name, value)) = name.split_once('=') {
+                    parsed
+                        .flags
+                        .insert(name.to_string(), Some(value.to_string()));
+                    continue;
+                }
+                let value = if value_flags.contains(&name) {
+                    match rest.next() {
+                        Some(v) => Some(v.clone()),
+                        None => return Err(format!("--{} requires a value", name)),
+                    }
+                } else {
+                    None
+                };
+                parsed.flags.insert(name.to_string(), value);
+            } else {
+                parsed.positional.push(arg.clone());
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Whether a flag was given at all
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.contains_key(name)
+    }
+
+    /// An on/off flag over a default (from a config file): `--<name>` turns it on and
+    /// `--no-<name>` off, the latter winning if both are given
+    pub fn switch(&self, name: &str, default: bool) -> bool {
+        if self.flag(&format!("no-{}", name)) {
+            false
+        } else {
+            self.flag(name) || default
+        }
+    }
+
+    /// The value passed to a value flag
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.flags.get(name).and_then(|v| v.as_deref())
+    }
+
+    /// A numeric flag value (decimal or `0x` hex)
+    pub fn number(&self, name: &str) -> Result<Option<usize>, String> {
+        match self.value(name) {
+            Some(v) => parse_number(v)
+                .map(Some)
+                .ok_or_else(|| format!("invalid number for --{}: '{}'", name, v)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Parse a decimal or `0x`-prefixed hexadecimal number
+pub fn parse_number(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_positional_and_flags() {
+        let parsed = Args::parse(
+            &args(&["fw.bin", "--offset", "0x10", "out", "--full-eocd-scan"]),
+            &["offset"],
+        )
+        .unwrap();
+        assert_eq!(parsed.positional, ["fw.bin", "out"]);
+        assert_eq!(parsed.value("offset"), Some("0x10"));
+        assert_eq!(parsed.number("offset"), Ok(Some(16)));
+        assert_eq!(parsed.value("missing"), None);
+        assert!(parsed.flag("full-eocd-scan"));
+        assert!(!parsed.flag("missing"));
+    }
+
+    #[test]
+    fn test_attached_value() {
+        let parsed = Args::parse(&args(&["--strings=8", "--strings-off", "fw.bin"]), &[]).unwrap();
+        assert_eq!(parsed.number("strings"), Ok(Some(8)));
+        assert_eq!(parsed.value("strings-off"), None);
+        assert_eq!(parsed.positional, ["fw.bin"]);
+    }
+
+    #[test]
+    fn test_switch() {
+        let parsed = Args::parse(&args(&["--strict", "--no-manifest"]), &[]).unwrap();
+        assert!(parsed.switch("strict", false));
+        assert!(!parsed.switch("manifest", true));
+        assert!(parsed.switch("sidecar", true));
+        assert!(!parsed.switch("sidecar", false));
+    }
+
+    #[test]
+    fn test_missing_value() {
+        assert!(Args::parse(&args(&["--offset"]), &["offset"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("42"), Some(42));
+        assert_eq!(parse_number("0x2a"), Some(42));
+        assert_eq!(parse_number("0X2A"), Some(42));
+        assert_eq!(parse_number("nope"), None);
+    }
+}
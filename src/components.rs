@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+
+use crate::extract::ExtractedNode;
+use crate::hash::sha256;
+
+/// How one named component compares across a set of firmware images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentStatus {
+    /// Byte-identical in every image
+    Shared,
+    /// In every image, but not identical in all of them
+    Changed,
+    /// Missing from at least one image
+    Partial,
+}
+
+impl ComponentStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ComponentStatus::Shared => "shared",
+            ComponentStatus::Changed => "changed",
+            ComponentStatus::Partial => "partial",
+        }
+    }
+}
+
+/// One leaf entry's digest in each image, None where the image lacks it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentRow {
+    pub path: String,
+    pub digests: Vec<Option<[u8; 32]>>,
+}
+
+impl ComponentRow {
+    pub fn status(&self) -> ComponentStatus {
+        let first = self.digests.first().copied().flatten();
+        if self.digests.iter().any(Option::is_none) {
+            ComponentStatus::Partial
+        } else if self.digests.iter().all(|d| *d == first) {
+            ComponentStatus::Shared
+        } else {
+            ComponentStatus::Changed
+        }
+    }
+
+    /// A letter per image naming its version of the component (`A`, `B`, ...), `-` where
+    /// it is missing, so images holding the same bytes get the same letter
+    pub fn variants(&self) -> Vec<char> {
+        let mut seen: Vec<[u8; 32]> = Vec::new();
+        self.digests
+            .iter()
+            .map(|digest| match digest {
+                None => '-',
+                Some(d) => {
+                    let index = seen.iter().position(|s| s == d).unwrap_or_else(|| {
+                        seen.push(*d);
+                        seen.len() - 1
+                    });
+                    (b'A' + (index % 26) as u8) as char
+                }
+            })
+            .collect()
+    }
+}
+
+/// SHA-256 of every leaf (an entry with nothing nested inside) keyed by its path below the
+/// root, so the same component lines up across images whatever the input file was called
+/// A path that repeats within one image gets a `~N` suffix
+pub fn leaf_digests(root: &ExtractedNode) -> BTreeMap<String, [u8; 32]> {
+    let mut digests = BTreeMap::new();
+    for child in &root.children {
+        collect_leaves(child, "", &mut digests);
+    }
+    digests
+}
+
+fn collect_leaves(node: &ExtractedNode, prefix: &str, digests: &mut BTreeMap<String, [u8; 32]>) {
+    let name = match &node.name {
+        Some(name) => name.clone(),
+        None => format!("<entry {}>", node.index),
+    };
+    let path = if prefix.is_empty() {
+        name
+    } else {
+        format!("{}/{}", prefix, name)
+    };
+
+    if !node.children.is_empty() {
+        for child in &node.children {
+            collect_leaves(child, &path, digests);
+        }
+        return;
+    }
+
+    let mut key = path.clone();
+    let mut n = 1;
+    while digests.contains_key(&key) {
+        n += 1;
+        key = format!("{}~{}", path, n);
+    }
+    digests.insert(key, sha256(&node.data));
+}
+
+/// Line up the leaves of several images by path, in path order
+pub fn component_matrix(images: &[BTreeMap<String, [u8; 32]>]) -> Vec<ComponentRow> {
+    let mut paths: Vec<&String> = images.iter().flat_map(|image| image.keys()).collect();
+    paths.sort();
+    paths.dedup();
+
+    paths
+        .into_iter()
+        .map(|path| ComponentRow {
+            path: path.clone(),
+            digests: images
+                .iter()
+                .map(|image| image.get(path).copied())
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_types::FileType;
+
+    fn node<'a>(name: &str, data: &'a [u8], children: Vec<ExtractedNode<'a>>) -> ExtractedNode<'a> {
+        ExtractedNode {
+            index: 0,
+            name: Some(name.to_string()),
+            file_type: FileType::Unknown,
+            data: data.into(),
+            children,
+        }
+    }
+
+    #[test]
+    fn test_leaf_digests_use_nested_paths() {
+        let root = node(
+            "fw_v1.bin",
+            b"",
+            vec![
+                node("pkg.zip", b"", vec![node("a.txt", b"a", vec![])]),
+                node("b.txt", b"b", vec![]),
+                node("b.txt", b"b2", vec![]),
+            ],
+        );
+        let digests = leaf_digests(&root);
+        let paths: Vec<_> = digests.keys().map(String::as_str).collect();
+        assert_eq!(paths, ["b.txt", "b.txt~2", "pkg.zip/a.txt"]);
+        assert_eq!(digests["pkg.zip/a.txt"], sha256(b"a"));
+    }
+
+    #[test]
+    fn test_component_matrix() {
+        let image = |entries: &[(&str, &[u8])]| {
+            entries
+                .iter()
+                .map(|(path, data)| (path.to_string(), sha256(data)))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let images = [
+            image(&[("boot.bin", b"same"), ("app.bin", b"v1")]),
+            image(&[("boot.bin", b"same"), ("app.bin", b"v2"), ("new.bin", b"x")]),
+            image(&[("boot.bin", b"same"), ("app.bin", b"v1")]),
+        ];
+
+        let rows = component_matrix(&images);
+        let summary: Vec<_> = rows
+            .iter()
+            .map(|r| (r.path.as_str(), r.status(), r.variants()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("app.bin", ComponentStatus::Changed, vec!['A', 'B', 'A']),
+                ("boot.bin", ComponentStatus::Shared, vec!['A', 'A', 'A']),
+                ("new.bin", ComponentStatus::Partial, vec!['-', 'A', '-']),
+            ]
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::display::OutputFormat;
+use crate::file_types::{file_type_from_arg, FileType, MAX_FRAMING_SKIP};
+use crate::naming::parse_transforms;
+use crate::options::{JsonStyle, ProcessOptions};
+use crate::signatures::{Signature, SignatureRegistry};
+
+/// Config file picked up from the working directory when `--config` isn't given
+pub const CONFIG_FILE_NAME: &str = "autel-parser.toml";
+
+/// Settings read from an `autel-parser.toml` file
+/// Everything is optional; unset values keep the built-in defaults
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub defaults: Defaults,
+    #[serde(rename = "signature")]
+    pub signatures: Vec<SignatureConfig>,
+    #[serde(rename = "override")]
+    pub overrides: Vec<OverrideConfig>,
+}
+
+/// Default option values, each overridden by the matching command-line flag
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Defaults {
+    pub format: Option<String>,
+    pub full_eocd_scan: bool,
+    pub strict: bool,
+    pub jobs: Option<usize>,
+    pub hash_members: bool,
+    pub include_empty: bool,
+    pub max_entries: Option<usize>,
+    pub max_files: Option<usize>,
+    pub entropy: bool,
+    pub crack_meta: bool,
+    pub header_histogram: bool,
+    pub align: Option<usize>,
+    /// Seconds
+    pub zip_timeout: Option<u64>,
+    pub zip_password: Option<String>,
+    pub write_manifest: bool,
+    pub sidecar: bool,
+    /// Comma-separated, as for `--name-transform`
+    pub name_transform: Option<String>,
+    /// Minimum run length for `--strings`; setting it turns string listing on
+    pub strings: Option<usize>,
+    pub recover: bool,
+    pub no_recurse: bool,
+    pub index_prefix: bool,
+    pub zip_trailer: bool,
+    /// Framing bytes to look past, as for `--peel-framing=N`
+    pub peel_framing: Option<usize>,
+    pub follow_json_refs: bool,
+    /// `original`, `pretty` or `minified`, as for `--json-style`
+    pub json_style: Option<String>,
+    pub group_by_device: bool,
+    pub skip_existing: bool,
+    pub verify_existing: bool,
+    pub verify_writes: bool,
+}
+
+/// A `[[signature]]` table: magic bytes (hex) at an offset and the type they identify
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignatureConfig {
+    pub name: String,
+    #[serde(default)]
+    pub offset: usize,
+    pub magic: String,
+    #[serde(rename = "type")]
+    pub file_type: String,
+}
+
+/// An `[[override]]` table: files whose name matches `glob` are processed as `type`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OverrideConfig {
+    pub glob: String,
+    #[serde(rename = "type")]
+    pub file_type: String,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn parse_type(name: &str) -> io::Result<FileType> {
+    file_type_from_arg(name).ok_or_else(|| invalid(format!("unknown file type: '{}'", name)))
+}
+
+/// Decode a hex string, allowing spaces between bytes
+fn parse_magic(text: &str) -> Option<Vec<u8>> {
+    let digits: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+impl Config {
+    pub fn parse(text: &str) -> io::Result<Config> {
+        toml::from_str(text).map_err(|e| invalid(e.to_string()))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Config> {
+        let text = fs::read_to_string(path)?;
+        Config::parse(&text).map_err(|e| invalid(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Load the explicitly given config file, or `autel-parser.toml` from the working
+    /// directory if there is one, or fall back to an empty config
+    pub fn discover(explicit: Option<&str>) -> io::Result<Config> {
+        match explicit {
+            Some(path) => Config::load(Path::new(path)),
+            None if Path::new(CONFIG_FILE_NAME).is_file() => {
+                Config::load(Path::new(CONFIG_FILE_NAME))
+            }
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// The configured default output format, if any
+    pub fn output_format(&self) -> io::Result<Option<OutputFormat>> {
+        self.defaults
+            .format
+            .as_deref()
+            .map(|name| {
+                OutputFormat::from_name(name)
+                    .ok_or_else(|| invalid(format!("unknown output format: '{}'", name)))
+            })
+            .transpose()
+    }
+
+    pub fn signature_registry(&self) -> io::Result<SignatureRegistry> {
+        let mut registry = SignatureRegistry::default();
+        for sig in &self.signatures {
+            let magic = parse_magic(&sig.magic).ok_or_else(|| {
+                invalid(format!(
+                    "signature '{}': invalid magic '{}'",
+                    sig.name, sig.magic
+                ))
+            })?;
+            registry.register(Signature {
+                name: sig.name.clone(),
+                offset: sig.offset,
+                magic,
+                file_type: parse_type(&sig.file_type)?,
+            });
+        }
+        Ok(registry)
+    }
+
+    /// Processing options as configured, before any command-line flags are applied
+    pub fn process_options(&self) -> io::Result<ProcessOptions> {
+        let mut type_overrides = Vec::new();
+        for o in &self.overrides {
+            let pattern = glob::Pattern::new(&o.glob)
+                .map_err(|e| invalid(format!("invalid pattern '{}': {}", o.glob, e)))?;
+            type_overrides.push((pattern, parse_type(&o.file_type)?));
+        }
+
+        let d = &self.defaults;
+        Ok(ProcessOptions {
+            full_eocd_scan: d.full_eocd_scan,
+            strict: d.strict,
+            jobs: d.jobs.unwrap_or(1),
+            hash_members: d.hash_members,
+            include_empty: d.include_empty,
+            max_entries: d.max_entries,
+            max_files: d.max_files,
+            entropy: d.entropy,
+            crack_meta: d.crack_meta,
+            header_histogram: d.header_histogram,
+            align: d.align,
+            zip_open_timeout: d.zip_timeout.map(Duration::from_secs),
+            zip_password: d.zip_password.clone(),
+            write_manifest: d.write_manifest,
+            sidecar: d.sidecar,
+            name_transforms: d
+                .name_transform
+                .as_deref()
+                .map(parse_transforms)
+                .transpose()
+                .map_err(invalid)?
+                .unwrap_or_default(),
+            strings_min_len: d.strings,
+            recover: d.recover,
+            no_recurse: d.no_recurse,
+            index_prefix: d.index_prefix,
+            zip_trailer: d.zip_trailer,
+            follow_json_refs: d.follow_json_refs,
+            framing_skip: match d.peel_framing {
+                Some(n) if n > MAX_FRAMING_SKIP => {
+                    return Err(invalid(format!(
+                        "peel_framing takes at most {} bytes, not {}",
+                        MAX_FRAMING_SKIP, n
+                    )))
+                }
+                n => n.unwrap_or(0),
+            },
+            json_style: d
+                .json_style
+                .as_deref()
+                .map(|name| {
+                    JsonStyle::from_name(name)
+                        .ok_or_else(|| invalid(format!("unknown JSON style: '{}'", name)))
+                })
+                .transpose()?
+                .unwrap_or_default(),
+            group_by_device: d.group_by_device,
+            skip_existing: d.skip_existing,
+            verify_existing: d.verify_existing,
+            verify_writes: d.verify_writes,
+            type_overrides,
+            signatures: self.signature_registry()?,
+            // Handlers are code, so only a library user can register them
+            handlers: Default::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+[defaults]
+format = "tree"
+strict = true
+jobs = 4
+
+[[signature]]
+name = "evo-lite"
+offset = 2
+magic = "de ad be ef"
+type = "zip"
+
+[[override]]
+glob = "*.fw"
+type = "AutelContainer"
+"#;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(SAMPLE).unwrap();
+        assert_eq!(config.output_format().unwrap(), Some(OutputFormat::Tree));
+
+        let options = config.process_options().unwrap();
+        assert!(options.strict);
+        assert!(!options.entropy);
+        assert_eq!(options.jobs, 4);
+        assert_eq!(
+            options.type_override(Some("main.fw")),
+            Some(FileType::AutelContainer)
+        );
+        assert_eq!(
+            options.forced_type(b"\0\0\xde\xad\xbe\xefzip", Some("blob")),
+            Some(FileType::Zip)
+        );
+        assert_eq!(options.forced_type(b"\xde\xad\xbe\xef", Some("blob")), None);
+    }
+
+    #[test]
+    fn test_empty_config_keeps_defaults() {
+        let options = Config::parse("").unwrap().process_options().unwrap();
+        assert!(!options.strict);
+        assert_eq!(options.jobs, 1);
+        assert!(options.signatures.is_empty());
+        assert!(options.type_overrides.is_empty());
+    }
+
+    #[test]
+    fn test_bad_config_is_rejected() {
+        assert!(Config::parse("[defaults]\nstrictt = true").is_err());
+        let bad_magic = "[[signature]]\nname = \"x\"\nmagic = \"abc\"\ntype = \"Zip\"";
+        assert!(Config::parse(bad_magic).unwrap().process_options().is_err());
+        let bad_type = "[[override]]\nglob = \"*\"\ntype = \"Floppy\"";
+        assert!(Config::parse(bad_type).unwrap().process_options().is_err());
+    }
+}
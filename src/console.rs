@@ -0,0 +1,45 @@
+//! Where the human-readable traversal report goes
+//!
+//! The report normally shares stdout with nothing else. When stdout carries data
+//! (e.g. `--stdout` streaming an entry's bytes), it is sent to stderr instead.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REPORT_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// `tracing` target of events that repeat a line of the report, such as warnings, so a
+/// subscriber printing next to the report can leave them out
+pub const REPORT_TARGET: &str = "firmparse::report";
+
+/// Send the report to stderr (true) or stdout (false, the default)
+pub fn set_report_to_stderr(to_stderr: bool) {
+    REPORT_TO_STDERR.store(to_stderr, Ordering::Relaxed);
+}
+
+pub fn report_to_stderr() -> bool {
+    REPORT_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// `println!` for the traversal report
+#[macro_export]
+macro_rules! report {
+    ($($arg:tt)*) => {
+        if $crate::console::report_to_stderr() {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// `print!` for the traversal report
+#[macro_export]
+macro_rules! report_inline {
+    ($($arg:tt)*) => {
+        if $crate::console::report_to_stderr() {
+            eprint!($($arg)*)
+        } else {
+            print!($($arg)*)
+        }
+    };
+}
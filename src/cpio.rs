@@ -0,0 +1,176 @@
+/// Magic of a "newc" cpio header, and of its variant with per-file checksums
+pub const CPIO_NEWC_MAGIC: &[u8; 6] = b"070701";
+pub const CPIO_CRC_MAGIC: &[u8; 6] = b"070702";
+
+/// Size of a newc header: the magic and thirteen 8-digit hex fields
+const HEADER_LEN: usize = 110;
+
+/// Name of the entry that ends the archive
+const TRAILER_NAME: &str = "TRAILER!!!";
+
+/// File type bits of `mode`
+const S_IFMT: u32 = 0o170000;
+const S_IFREG: u32 = 0o100000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+
+/// A member of a newc cpio archive
+#[derive(Debug, PartialEq)]
+pub struct CpioEntry {
+    pub name: String,
+    /// File type and permission bits, as in `st_mode`
+    pub mode: u32,
+    pub size: usize,
+    /// Offset of the member's data within the archive
+    pub offset: usize,
+}
+
+impl CpioEntry {
+    pub fn is_regular(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+
+    /// A short description of what the entry is, for entries without content to recurse into
+    pub fn kind(&self) -> &'static str {
+        match self.mode & S_IFMT {
+            S_IFREG => "file",
+            S_IFDIR => "directory",
+            S_IFLNK => "symlink",
+            _ => "special file",
+        }
+    }
+}
+
+/// Returns true if `data` starts with a newc cpio header, as initramfs images do
+pub fn is_cpio(data: &[u8]) -> bool {
+    matches!(data.get(..6), Some(m) if m == CPIO_NEWC_MAGIC || m == CPIO_CRC_MAGIC)
+        && data.len() >= HEADER_LEN
+}
+
+/// Read the 8-digit hex field at `index` (0 is `c_ino`)
+fn read_field(header: &[u8], index: usize) -> Option<u32> {
+    let start = 6 + index * 8;
+    let digits = std::str::from_utf8(header.get(start..start + 8)?).ok()?;
+    u32::from_str_radix(digits, 16).ok()
+}
+
+/// List the members of a newc archive, in archive order
+/// Stops at the `TRAILER!!!` entry or the first header that doesn't check out
+pub fn list_entries(data: &[u8]) -> Vec<CpioEntry> {
+    let mut entries = Vec::new();
+    let mut pos = 0;
+
+    while let Some(header) = data.get(pos..pos + HEADER_LEN) {
+        if !is_cpio(header) {
+            break;
+        }
+        let (Some(mode), Some(size), Some(name_size)) = (
+            read_field(header, 1),
+            read_field(header, 6),
+            read_field(header, 11),
+        ) else {
+            break;
+        };
+
+        // The name includes its NUL; header plus name, and then the data, pad to 4 bytes
+        let name_start = pos + HEADER_LEN;
+        let Some(name_bytes) = data.get(name_start..name_start + name_size as usize) else {
+            break;
+        };
+        let name_len = name_bytes
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(name_bytes.len());
+        let name = String::from_utf8_lossy(&name_bytes[..name_len]).to_string();
+        if name == TRAILER_NAME {
+            break;
+        }
+
+        let offset = (name_start + name_size as usize).next_multiple_of(4);
+        entries.push(CpioEntry {
+            name,
+            mode,
+            size: size as usize,
+            offset,
+        });
+        pos = offset.saturating_add(size as usize).next_multiple_of(4);
+    }
+
+    entries
+}
+
+/// The data of a member, clamped to the archive if it was truncated
+pub fn entry_data<'a>(data: &'a [u8], entry: &CpioEntry) -> &'a [u8] {
+    let start = entry.offset.min(data.len());
+    let end = entry.offset.saturating_add(entry.size).min(data.len());
+    &data[start..end]
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A newc archive of `(name, mode, data)` members, ending with the trailer
+    pub(crate) fn build_cpio(members: &[(&str, u32, &[u8])]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let trailer = [(TRAILER_NAME, 0, &[][..])];
+        for (ino, (name, mode, data)) in members.iter().chain(&trailer).enumerate() {
+            let fields = [
+                ino as u32,
+                *mode,
+                0,
+                0,
+                1,
+                0,
+                data.len() as u32,
+                0,
+                0,
+                0,
+                0,
+                name.len() as u32 + 1,
+                0,
+            ];
+            out.extend_from_slice(CPIO_NEWC_MAGIC);
+            for field in fields {
+                out.extend_from_slice(format!("{:08x}", field).as_bytes());
+            }
+            out.extend_from_slice(name.as_bytes());
+            out.push(0);
+            out.resize(out.len().next_multiple_of(4), 0);
+            out.extend_from_slice(data);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        out
+    }
+
+    #[test]
+    fn test_list_entries() {
+        let archive = build_cpio(&[
+            ("bin", 0o040755, b""),
+            ("bin/busybox", 0o100755, b"\x7fELF..."),
+            ("init", 0o120777, b"bin/busybox"),
+        ]);
+        assert!(is_cpio(&archive));
+
+        let entries = list_entries(&archive);
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["bin", "bin/busybox", "init"]);
+        assert_eq!(entries[1].mode, 0o100755);
+        assert!(entries[1].is_regular());
+        assert_eq!(entry_data(&archive, &entries[1]), b"\x7fELF...");
+        assert_eq!(entries[0].kind(), "directory");
+        assert_eq!(entries[2].kind(), "symlink");
+    }
+
+    #[test]
+    fn test_list_entries_truncated() {
+        let archive = build_cpio(&[("a.txt", 0o100644, b"hello world")]);
+        let cut = &archive[..HEADER_LEN + 6 + 5];
+
+        let entries = list_entries(cut);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entry_data(cut, &entries[0]), b"hello");
+        assert!(!is_cpio(b"070701"));
+        assert!(list_entries(b"not a cpio archive").is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::file_types::file_type_name;
+use crate::hash::{sha256, to_hex};
+use crate::out_archive::archive_name;
+use crate::processor::ExtractedFile;
+
+/// Subdirectory of the store holding one manifest per input
+pub const MANIFEST_DIR: &str = "manifests";
+
+/// A content-addressed blob store: every file is written once, as `<dir>/<sha256>`, and each
+/// input gets a manifest mapping its nesting paths to those digests
+/// Blobs already in the directory from earlier runs are reused, so many near-identical
+/// releases share their common components
+pub struct DedupStore {
+    dir: PathBuf,
+    /// Digests known to be stored, written this run or found on disk
+    known: HashSet<[u8; 32]>,
+    /// Manifest entries of the input being added
+    files: Vec<serde_json::Value>,
+    pub files_added: usize,
+    pub blobs_written: usize,
+    /// Bytes not written because an identical blob was already stored
+    pub bytes_deduplicated: u64,
+}
+
+impl DedupStore {
+    /// Open (creating if needed) a store rooted at `dir`
+    pub fn open(dir: &Path) -> io::Result<DedupStore> {
+        fs::create_dir_all(dir.join(MANIFEST_DIR))?;
+        Ok(DedupStore {
+            dir: dir.to_path_buf(),
+            known: HashSet::new(),
+            files: Vec::new(),
+            files_added: 0,
+            blobs_written: 0,
+            bytes_deduplicated: 0,
+        })
+    }
+
+    /// Where the blob with this digest lives
+    pub fn blob_path(&self, digest: &[u8; 32]) -> PathBuf {
+        self.dir.join(to_hex(digest))
+    }
+
+    /// Store a file's content unless an identical blob is already there, and record it in
+    /// the current input's manifest
+    pub fn add(&mut self, file: &ExtractedFile) -> io::Result<()> {
+        let digest = sha256(file.data);
+        let path = self.blob_path(&digest);
+        if self.known.contains(&digest) || path.is_file() {
+            self.bytes_deduplicated += file.data.len() as u64;
+        } else {
+            // Written under a temporary name first, so an interrupted run never leaves a
+            // partial blob under a digest it doesn't match
+            let partial = path.with_extension("partial");
+            fs::write(&partial, file.data)?;
+            fs::rename(&partial, &path)?;
+            self.blobs_written += 1;
+        }
+        self.known.insert(digest);
+
+        let mut entry = serde_json::json!({
+            "path": archive_name(&file.path),
+            "type": file_type_name(&file.file_type),
+            "size": file.data.len(),
+            "sha256": to_hex(&digest),
+        });
+        if let Some(device) = &file.device {
+            entry["device"] = device.as_str().into();
+        }
+        self.files.push(entry);
+        self.files_added += 1;
+        Ok(())
+    }
+
+    /// Write the manifest of everything added since the last call, as
+    /// `manifests/<input>.json`, and start a new one
+    /// The manifest is keyed by the input's relative path, so `a/fw.bin` and `b/fw.bin`
+    /// don't overwrite each other; an absolute path is taken relative to the working
+    /// directory where it can be, and root and `..` components are dropped
+    pub fn finish_input(&mut self, input: &str) -> io::Result<PathBuf> {
+        let input_path = Path::new(input);
+        let cwd = std::env::current_dir()?;
+        let relative: PathBuf = input_path
+            .strip_prefix(&cwd)
+            .unwrap_or(input_path)
+            .components()
+            .filter_map(|c| match c {
+                Component::Normal(part) => Some(part),
+                _ => None,
+            })
+            .collect();
+        let name = match relative.to_str() {
+            Some(name) if !name.is_empty() => name.replace('\\', "/"),
+            _ => input.to_string(),
+        };
+        let manifest = serde_json::json!({
+            "input": name,
+            "files": std::mem::take(&mut self.files),
+        });
+        let path = self.dir.join(MANIFEST_DIR).join(format!("{}.json", name));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_vec_pretty(&manifest)?)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_types::FileType;
+
+    fn file<'a>(path: &str, data: &'a [u8]) -> ExtractedFile<'a> {
+        ExtractedFile {
+            path: PathBuf::from(path),
+            file_type: FileType::Text,
+            data,
+            device: None,
+        }
+    }
+
+    #[test]
+    fn test_identical_files_stored_once() {
+        let dir = std::env::temp_dir().join(format!("firmparse-store-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut store = DedupStore::open(&dir).unwrap();
+        store.add(&file("v1/a.txt", b"same")).unwrap();
+        store.add(&file("v1/b.txt", b"same")).unwrap();
+        store.add(&file("v1/c.txt", b"other")).unwrap();
+        let manifest_path = store.finish_input("fw_v1.bin").unwrap();
+        assert_eq!(store.blobs_written, 2);
+        assert_eq!(store.bytes_deduplicated, 4);
+        assert_eq!(
+            fs::read(store.blob_path(&sha256(b"same"))).unwrap(),
+            b"same"
+        );
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(&manifest_path).unwrap()).unwrap();
+        assert_eq!(manifest["input"], "fw_v1.bin");
+        assert_eq!(manifest["files"].as_array().unwrap().len(), 3);
+        assert_eq!(manifest["files"][1]["path"], "v1/b.txt");
+        assert_eq!(manifest["files"][1]["sha256"], to_hex(&sha256(b"same")));
+
+        // Inputs with the same file name in different directories keep separate manifests
+        store.add(&file("v1/a.txt", b"same")).unwrap();
+        let nested = store.finish_input("beta/fw_v1.bin").unwrap();
+        assert_ne!(nested, manifest_path);
+        assert!(nested.ends_with("beta/fw_v1.bin.json"));
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(nested).unwrap()).unwrap();
+        assert_eq!(manifest["input"], "beta/fw_v1.bin");
+
+        // A later run reuses what is already on disk
+        let mut store = DedupStore::open(&dir).unwrap();
+        store.add(&file("v2/a.txt", b"same")).unwrap();
+        assert_eq!(store.blobs_written, 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,39 @@
+use std::fmt;
+
+/// How serious a diagnostic is
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem noticed while parsing, collected instead of printed so callers decide
+/// whether to show, test or ignore it
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Byte offset in the parsed buffer the problem relates to, if it has one
+    pub offset: Option<usize>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn warning(offset: Option<usize>, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            offset,
+            message,
+        }
+    }
+}
+
+/// Formatted the way the CLI prints it, e.g. `Warning: Declared content length ...`
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self.severity {
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        };
+        write!(f, "{}: {}", label, self.message)
+    }
+}
@@ -1,83 +1,502 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
 
+use crate::analysis::{metadata_summary, MetadataSummary};
+use crate::extract::ExtractedNode;
 use crate::file_entry::FileEntry;
+use crate::file_types::{file_type_name, FileType};
+use crate::hash::{sha256, to_hex};
+use crate::{report, report_inline};
+
+/// How the results of a run are rendered
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OutputFormat {
+    /// Streaming, indented human-readable output
+    Text,
+    /// A single JSON document describing the whole tree
+    Json,
+    /// A `tree`-style rendering of the nesting, printed once at the end
+    Tree,
+    /// One compact JSON object per extracted file, printed as it is found
+    JsonLines,
+    /// One aligned line per leaf entry and the per-type totals, with no previews
+    Table,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            "tree" => Some(OutputFormat::Tree),
+            "jsonl" | "json-lines" => Some(OutputFormat::JsonLines),
+            "table" => Some(OutputFormat::Table),
+            _ => None,
+        }
+    }
+}
+
+/// One node of the display tree: a file and what was found inside it
+#[derive(Debug)]
+pub struct TreeNode {
+    pub name: String,
+    pub file_type: FileType,
+    pub size: usize,
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    /// Build the display tree from an extracted tree
+    pub fn from_extracted(node: &ExtractedNode) -> TreeNode {
+        TreeNode {
+            name: node.name.clone().unwrap_or_else(|| "<unknown>".to_string()),
+            file_type: node.file_type,
+            size: node.data.len(),
+            children: node.children.iter().map(TreeNode::from_extracted).collect(),
+        }
+    }
+
+    fn label(&self) -> String {
+        format!(
+            "{} [{}] ({} bytes)",
+            self.name,
+            file_type_name(&self.file_type),
+            self.size
+        )
+    }
+}
+
+/// Render a tree with box-drawing characters, like the `tree` command
+pub fn render_tree(root: &TreeNode) -> String {
+    let mut out = root.label();
+    out.push('\n');
+    render_children(root, "", &mut out);
+    out
+}
+
+fn render_children(node: &TreeNode, prefix: &str, out: &mut String) {
+    for (i, child) in node.children.iter().enumerate() {
+        let last = i + 1 == node.children.len();
+        let (branch, continuation) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        out.push_str(prefix);
+        out.push_str(branch);
+        out.push_str(&child.label());
+        out.push('\n');
+        render_children(child, &format!("{}{}", prefix, continuation), out);
+    }
+}
+
+/// Format a byte count with a binary unit, e.g. `18.0 KiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Render rows as aligned columns: the first column left-aligned, the rest right-aligned
+pub fn render_table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let right: Vec<bool> = (0..headers.len()).map(|i| i > 0).collect();
+    render_columns(headers, rows, &right)
+}
+
+/// Render rows as aligned columns, right-aligning the columns flagged in `right`
+fn render_columns(headers: &[&str], rows: &[Vec<String>], right: &[bool]) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header_row: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+    let mut out = String::new();
+    for row in std::iter::once(&header_row).chain(rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .zip(right)
+            .map(|((cell, &width), &right)| {
+                if right {
+                    format!("{:>width$}", cell)
+                } else {
+                    format!("{:<width$}", cell)
+                }
+            })
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Render per-type counts and total sizes, one row per type seen
+pub fn render_type_table(totals: &BTreeMap<FileType, (usize, u64)>) -> String {
+    let rows: Vec<Vec<String>> = totals
+        .iter()
+        .map(|(file_type, &(count, bytes))| {
+            vec![
+                file_type_name(file_type).to_string(),
+                count.to_string(),
+                format_size(bytes),
+            ]
+        })
+        .collect();
+    render_table(&["Type", "Count", "Size"], &rows)
+}
+
+/// Render the distinct types with their counts on one line, e.g. `Zip×2, Json×3`
+/// Types use their short identifiers so the line is stable and easy to group by
+pub fn render_type_fingerprint(totals: &BTreeMap<FileType, (usize, u64)>) -> String {
+    totals
+        .iter()
+        .map(|(file_type, &(count, _))| format!("{:?}×{}", file_type, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Render one line per leaf (an entry with nothing nested inside) of an extracted tree:
+/// nesting depth, type, size, path below the root and a short SHA-256
+pub fn render_leaf_table(root: &ExtractedNode) -> String {
+    let mut rows = Vec::new();
+    for child in &root.children {
+        collect_leaf_rows(child, "", 1, &mut rows);
+    }
+    render_columns(
+        &["Depth", "Type", "Size", "Name", "SHA-256"],
+        &rows,
+        &[true, false, true, false, false],
+    )
+}
+
+fn collect_leaf_rows(
+    node: &ExtractedNode,
+    prefix: &str,
+    depth: usize,
+    rows: &mut Vec<Vec<String>>,
+) {
+    let name = match &node.name {
+        Some(name) => format!("{}{}", prefix, name),
+        None => format!("{}<entry {}>", prefix, node.index),
+    };
+    if node.children.is_empty() {
+        rows.push(vec![
+            depth.to_string(),
+            file_type_name(&node.file_type).to_string(),
+            format_size(node.data.len() as u64),
+            name,
+            to_hex(&sha256(&node.data))[..12].to_string(),
+        ]);
+        return;
+    }
+    for child in &node.children {
+        collect_leaf_rows(child, &format!("{}/", name), depth + 1, rows);
+    }
+}
+
+/// Count and total size of every node in an extracted tree, per detected type
+pub fn tree_type_totals(root: &ExtractedNode) -> BTreeMap<FileType, (usize, u64)> {
+    let mut totals = BTreeMap::new();
+    let mut pending = vec![root];
+    while let Some(node) = pending.pop() {
+        let entry = totals.entry(node.file_type).or_insert((0, 0));
+        entry.0 += 1;
+        entry.1 += node.data.len() as u64;
+        pending.extend(&node.children);
+    }
+    totals
+}
 
 /// Print a hexdump preview of data with indentation
 pub fn print_hexdump_preview_indented(data: &[u8], max_lines: usize, indent: &str) {
-    let mut offset = 0;
+    report_inline!("{}", render_hexdump(data, max_lines, indent));
+}
+
+/// Render up to `max_lines` rows of 16 bytes as offset, hex and ASCII columns
+pub fn render_hexdump(data: &[u8], max_lines: usize, indent: &str) -> String {
+    let mut out = String::new();
 
-    for chunk in data.chunks(16).take(max_lines) {
-        print!("{}  {:08x}  ", indent, offset);
+    for (row, chunk) in data.chunks(16).take(max_lines).enumerate() {
+        out.push_str(&format!("{}  {:08x}  ", indent, row * 16));
         for i in 0..16 {
-            if i < chunk.len() {
-                print!("{:02x} ", chunk[i]);
-            } else {
-                print!("   ");
+            match chunk.get(i) {
+                Some(b) => out.push_str(&format!("{:02x} ", b)),
+                None => out.push_str("   "),
             }
 
             if i == 7 {
-                print!(" ");
+                out.push(' ');
             }
         }
 
-        print!(" |");
+        out.push_str(" |");
         for &b in chunk {
             let c = if b.is_ascii_graphic() || b == b' ' {
                 b as char
             } else {
                 '.'
             };
-            print!("{}", c);
+            out.push(c);
         }
-        println!("|");
-
-        offset += 16;
+        out.push_str("|\n");
     }
 
-    if data.len() > max_lines * 16 {
-        println!(
-            "{}  ... ({} more bytes)",
+    if data.len() > max_lines.saturating_mul(16) {
+        out.push_str(&format!(
+            "{}  ... ({} more bytes)\n",
             indent,
             data.len() - max_lines * 16
-        );
+        ));
+    }
+    out
+}
+
+/// Print how often each `header_data` value occurs, most common first
+pub fn print_header_histogram(histogram: &BTreeMap<[u8; 4], usize>, indent: &str) {
+    let mut rows: Vec<_> = histogram.iter().collect();
+    rows.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    report!();
+    report!("{}=== Header Values ===", indent);
+    for (header, count) in rows {
+        let plural = if *count == 1 { "entry" } else { "entries" };
+        report!("{}{:02x?} → {} {}", indent, header, count, plural);
     }
 }
 
 /// Summarize the header and content metadata across entries
 pub fn summarize_metadata(entries: &[FileEntry], indent: &str) {
-    let mut pairs: HashMap<(String, String), Vec<String>> = HashMap::new();
-
-    for entry in entries {
-        let header = entry
-            .header_data
-            .map(|h| format!("{:02x?}", h))
-            .unwrap_or("[none]".to_string());
-        let content_meta = entry
-            .content_meta
-            .map(|m| format!("{:02x?}", m))
-            .unwrap_or("[none]".to_string());
-
-        let ext = entry
-            .filename
-            .as_ref()
-            .and_then(|f| f.rsplit('.').next())
-            .unwrap_or("<none>")
-            .to_lowercase();
-
-        pairs.entry((header, content_meta)).or_default().push(ext);
-    }
-
-    println!();
-    println!("{}=== Header + Content Meta Summary ===", indent);
-    for ((header, meta), exts) in pairs {
-        let mut counts = HashMap::new();
-        for ext in exts {
-            *counts.entry(ext).or_insert(0) += 1;
+    print_metadata_summary(&metadata_summary(entries), indent);
+}
+
+/// Print an already built header/meta summary, e.g. one merged across inputs
+pub fn print_metadata_summary(summary: &MetadataSummary, indent: &str) {
+    let field = |bytes: &Option<[u8; 4]>| {
+        bytes
+            .map(|b| format!("{:02x?}", b))
+            .unwrap_or("[none]".to_string())
+    };
+
+    report!();
+    report!("{}=== Header + Content Meta Summary ===", indent);
+    for ((header, meta), counts) in summary {
+        let summary_str: Vec<_> = counts.iter().map(|(k, v)| format!("{}.{}", v, k)).collect();
+        report!(
+            "{}{} + {} → {}",
+            indent,
+            field(header),
+            field(meta),
+            summary_str.join(", ")
+        );
+    }
+}
+
+/// Write the header/meta summary as CSV: `header_hex,meta_hex,extension,count`, one row per
+/// extension, with an empty field for a missing header or meta
+pub fn write_metadata_csv(summary: &MetadataSummary, out: &mut dyn Write) -> io::Result<()> {
+    let hex = |bytes: &Option<[u8; 4]>| bytes.map(|b| to_hex(&b)).unwrap_or_default();
+    writeln!(out, "header_hex,meta_hex,extension,count")?;
+    for ((header, meta), counts) in summary {
+        for (ext, count) in counts {
+            writeln!(
+                out,
+                "{},{},{},{}",
+                hex(header),
+                hex(meta),
+                csv_field(ext),
+                count
+            )?;
         }
-        let summary_str: Vec<_> = counts
-            .iter()
-            .map(|(k, v)| format!("{}.{}", v, k))
-            .collect();
-        println!("{}{} + {} → {}", indent, header, meta, summary_str.join(", "));
+    }
+    Ok(())
+}
+
+/// Quote a CSV field if it holds a comma, quote or line break (extensions come from
+/// firmware file names, which can hold anything)
+pub(crate) fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, file_type: FileType, size: usize, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode {
+            name: name.to_string(),
+            file_type,
+            size,
+            children,
+        }
+    }
+
+    #[test]
+    fn test_write_metadata_csv() {
+        let header = [0xfd, 0xce, 0x69, 0x48];
+        let mut summary = MetadataSummary::new();
+        let counts = summary.entry((Some(header), Some([0; 4]))).or_default();
+        counts.insert("json".to_string(), 2);
+        counts.insert("a,b".to_string(), 1);
+        summary
+            .entry((None, None))
+            .or_default()
+            .insert("<none>".to_string(), 1);
+
+        let mut out = Vec::new();
+        write_metadata_csv(&summary, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "header_hex,meta_hex,extension,count\n\
+             ,,<none>,1\n\
+             fdce6948,00000000,\"a,b\",1\n\
+             fdce6948,00000000,json,2\n"
+        );
+    }
+
+    #[test]
+    fn test_render_tree() {
+        let root = node(
+            "fw.bin",
+            FileType::AutelContainer,
+            100,
+            vec![
+                node("config.json", FileType::Json, 10, vec![]),
+                node(
+                    "pkg.zip",
+                    FileType::Zip,
+                    50,
+                    vec![
+                        node("a.txt", FileType::Text, 1, vec![]),
+                        node("b.txt", FileType::Text, 2, vec![]),
+                    ],
+                ),
+                node("gps.bin", FileType::GpsBin, 20, vec![]),
+            ],
+        );
+
+        let expected = "\
+fw.bin [Autel Container] (100 bytes)
+├── config.json [JSON] (10 bytes)
+├── pkg.zip [ZIP Archive] (50 bytes)
+│   ├── a.txt [Text] (1 bytes)
+│   └── b.txt [Text] (2 bytes)
+└── gps.bin [GPS Binary] (20 bytes)
+";
+        assert_eq!(render_tree(&root), expected);
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(18 * 1024), "18.0 KiB");
+        assert_eq!(format_size(2_202_010), "2.1 MiB");
+    }
+
+    #[test]
+    fn test_render_type_table() {
+        let mut totals = BTreeMap::new();
+        totals.insert(FileType::UpgGimbal, (1, 2_202_010));
+        totals.insert(FileType::Json, (4, 18 * 1024));
+
+        let expected = "\
+Type          Count      Size
+JSON              4  18.0 KiB
+UPG (Gimbal)      1   2.1 MiB
+";
+        assert_eq!(render_type_table(&totals), expected);
+    }
+
+    #[test]
+    fn test_render_type_fingerprint() {
+        let mut totals = BTreeMap::new();
+        totals.insert(FileType::Json, (3, 900));
+        totals.insert(FileType::AutelContainer, (1, 4096));
+        totals.insert(FileType::Zip, (2, 2048));
+
+        assert_eq!(
+            render_type_fingerprint(&totals),
+            "AutelContainer×1, Zip×2, Json×3"
+        );
+        assert_eq!(render_type_fingerprint(&BTreeMap::new()), "");
+    }
+
+    #[test]
+    fn test_render_leaf_table() {
+        fn extracted<'a>(
+            name: &str,
+            file_type: FileType,
+            data: &'a [u8],
+            children: Vec<ExtractedNode<'a>>,
+        ) -> ExtractedNode<'a> {
+            ExtractedNode {
+                index: 0,
+                name: Some(name.to_string()),
+                file_type,
+                data: data.into(),
+                children,
+            }
+        }
+        let root = extracted(
+            "fw.bin",
+            FileType::AutelContainer,
+            b"whole",
+            vec![
+                extracted("config.json", FileType::Json, b"{}", vec![]),
+                extracted(
+                    "pkg.zip",
+                    FileType::Zip,
+                    b"zip",
+                    vec![extracted("readme.txt", FileType::Text, b"abc", vec![])],
+                ),
+            ],
+        );
+
+        let expected = "\
+Depth  Type  Size  Name                SHA-256
+    1  JSON   2 B  config.json         44136fa355b3
+    2  Text   3 B  pkg.zip/readme.txt  ba7816bf8f01
+";
+        assert_eq!(render_leaf_table(&root), expected);
+
+        let totals = tree_type_totals(&root);
+        assert_eq!(totals[&FileType::Zip], (1, 3));
+        assert_eq!(totals.values().map(|t| t.0).sum::<usize>(), 4);
+    }
+
+    #[test]
+    fn test_render_hexdump() {
+        let data: Vec<u8> = (b'A'..=b'R').collect();
+        assert_eq!(
+            render_hexdump(&data, 1, ""),
+            "  00000000  41 42 43 44 45 46 47 48  49 4a 4b 4c 4d 4e 4f 50  |ABCDEFGHIJKLMNOP|\n  ... (2 more bytes)\n"
+        );
+    }
+
+    #[test]
+    fn test_output_format_names() {
+        assert_eq!(OutputFormat::from_name("tree"), Some(OutputFormat::Tree));
+        assert_eq!(OutputFormat::from_name("json"), Some(OutputFormat::Json));
+        assert_eq!(
+            OutputFormat::from_name("jsonl"),
+            Some(OutputFormat::JsonLines)
+        );
+        assert_eq!(OutputFormat::from_name("table"), Some(OutputFormat::Table));
+        assert_eq!(OutputFormat::from_name("xml"), None);
     }
 }
@@ -0,0 +1,88 @@
+use std::io::{self, Write};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+/// Bytes of input per line of hex output
+const HEX_LINE_BYTES: usize = 32;
+
+/// Bytes of input per line of base64 output (76 characters, as in MIME)
+const BASE64_LINE_BYTES: usize = 57;
+
+/// How `dump` encodes an entry's content
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DumpFormat {
+    /// Lowercase hex, 32 bytes per line
+    Hex,
+    /// Standard base64, wrapped at 76 characters
+    Base64,
+    /// The bytes unchanged
+    Raw,
+}
+
+impl DumpFormat {
+    pub fn from_name(name: &str) -> Option<DumpFormat> {
+        match name {
+            "hex" => Some(DumpFormat::Hex),
+            "base64" => Some(DumpFormat::Base64),
+            "raw" => Some(DumpFormat::Raw),
+            _ => None,
+        }
+    }
+}
+
+/// Write `data` to `out` in the given encoding, a line at a time
+pub fn write_dump(data: &[u8], format: DumpFormat, out: &mut dyn Write) -> io::Result<()> {
+    match format {
+        DumpFormat::Raw => out.write_all(data)?,
+        DumpFormat::Hex => {
+            for chunk in data.chunks(HEX_LINE_BYTES) {
+                for b in chunk {
+                    write!(out, "{:02x}", b)?;
+                }
+                writeln!(out)?;
+            }
+        }
+        DumpFormat::Base64 => {
+            // Whole 3-byte groups per line, so the lines concatenate into one valid encoding
+            for chunk in data.chunks(BASE64_LINE_BYTES) {
+                writeln!(out, "{}", STANDARD.encode(chunk))?;
+            }
+        }
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(data: &[u8], format: DumpFormat) -> String {
+        let mut out = Vec::new();
+        write_dump(data, format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_dump_hex() {
+        assert_eq!(dump(b"\x00\xffAB", DumpFormat::Hex), "00ff4142\n");
+        let lines = dump(&[0u8; 40], DumpFormat::Hex);
+        assert_eq!(lines.lines().map(str::len).collect::<Vec<_>>(), [64, 16]);
+    }
+
+    #[test]
+    fn test_dump_base64_lines_concatenate() {
+        let data: Vec<u8> = (0..200u8).collect();
+        let text = dump(&data, DumpFormat::Base64);
+        assert!(text.lines().all(|l| l.len() <= 76));
+        let joined: String = text.lines().collect();
+        assert_eq!(STANDARD.decode(joined).unwrap(), data);
+    }
+
+    #[test]
+    fn test_dump_raw() {
+        assert_eq!(dump(b"hello", DumpFormat::Raw), "hello");
+        assert_eq!(DumpFormat::from_name("base64"), Some(DumpFormat::Base64));
+        assert_eq!(DumpFormat::from_name("octal"), None);
+    }
+}
@@ -0,0 +1,217 @@
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use firmparse::display::{format_size, render_hexdump};
+use firmparse::extract::{extract_to_depth, ExtractedNode};
+use firmparse::file_types::file_type_name;
+use firmparse::processor::write_output;
+
+const HELP: &str = "\
+Commands:
+  ls                    list the entries at this level
+  cd <n|name>           enter a nested container, ZIP, tar or gzip entry
+  cd ..  /  cd /        go up one level / back to the top
+  pwd                   show the current nesting path
+  cat <n|name>          print an entry as text
+  hexdump <n|name> [N]  print the first N rows (default 16) of an entry as hex
+  extract <n|name> <path>
+                        write an entry to disk
+  help                  show this list
+  quit                  leave
+";
+
+/// An interactive walk through a firmware image, one nesting level at a time
+/// Each level is unpacked only one deep, so entering a container costs a single parse
+pub struct Session<'a> {
+    stack: Vec<ExtractedNode<'a>>,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(data: &'a [u8], name: &str) -> Session<'a> {
+        Session {
+            stack: vec![extract_to_depth(data, Some(name), 1)],
+        }
+    }
+
+    fn current(&self) -> &ExtractedNode<'a> {
+        self.stack.last().expect("session stack is never empty")
+    }
+
+    /// Slash-separated names from the top level down to the current one
+    pub fn path(&self) -> String {
+        let names: Vec<&str> = self.stack.iter().map(node_name).collect();
+        format!("/{}", names[1..].join("/"))
+    }
+
+    /// Find an entry at the current level by index or by name
+    fn lookup(&self, key: &str) -> Option<&ExtractedNode<'a>> {
+        let children = &self.current().children;
+        children
+            .iter()
+            .find(|c| c.name.as_deref() == Some(key))
+            .or_else(|| key.parse::<usize>().ok().and_then(|i| children.get(i)))
+    }
+
+    /// Run one command line, writing its output to `out`
+    /// Returns false once the user asks to leave
+    pub fn execute(&mut self, line: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, rest)) = words.split_first() else {
+            return Ok(true);
+        };
+
+        match (command, rest) {
+            ("quit" | "exit", _) => return Ok(false),
+            ("help" | "?", _) => write!(out, "{}", HELP)?,
+            ("pwd", _) => writeln!(out, "{}", self.path())?,
+            ("ls", _) => self.list(out)?,
+            ("cd", ["/"]) => self.stack.truncate(1),
+            ("cd", [".."]) => {
+                if self.stack.len() > 1 {
+                    self.stack.pop();
+                }
+            }
+            ("cd", [key]) => match self.lookup(key) {
+                Some(child) => {
+                    let level = extract_to_depth(&child.data, child.name.as_deref(), 1);
+                    if level.children.is_empty() {
+                        writeln!(
+                            out,
+                            "{} is a {} with nothing inside to browse",
+                            node_name(child),
+                            file_type_name(&child.file_type)
+                        )?;
+                    } else {
+                        // A level borrows from the one above it, which the stack can't hold
+                        self.stack.push(level.into_owned());
+                    }
+                }
+                None => writeln!(out, "No entry {}", key)?,
+            },
+            ("cat", [key]) => match self.lookup(key) {
+                Some(child) => {
+                    let text = String::from_utf8_lossy(&child.data);
+                    write!(out, "{}", text)?;
+                    if !text.ends_with('\n') {
+                        writeln!(out)?;
+                    }
+                }
+                None => writeln!(out, "No entry {}", key)?,
+            },
+            ("hexdump", [key, rows @ ..]) if rows.len() <= 1 => {
+                let max_lines = match rows.first().map(|n| n.parse::<usize>()) {
+                    None => 16,
+                    Some(Ok(n)) => n,
+                    Some(Err(_)) => {
+                        writeln!(out, "Row count must be a number")?;
+                        return Ok(true);
+                    }
+                };
+                match self.lookup(key) {
+                    Some(child) => write!(out, "{}", render_hexdump(&child.data, max_lines, ""))?,
+                    None => writeln!(out, "No entry {}", key)?,
+                }
+            }
+            ("extract", [key, path]) => match self.lookup(key) {
+                Some(child) => match write_output(Path::new(path), &child.data) {
+                    Ok(()) => writeln!(out, "→ Written {} bytes to {}", child.data.len(), path)?,
+                    Err(e) => writeln!(out, "Could not write {}: {}", path, e)?,
+                },
+                None => writeln!(out, "No entry {}", key)?,
+            },
+            _ => writeln!(out, "Unknown command: {} (try 'help')", line.trim())?,
+        }
+        Ok(true)
+    }
+
+    fn list(&self, out: &mut dyn Write) -> io::Result<()> {
+        let children = &self.current().children;
+        if children.is_empty() {
+            return writeln!(out, "(no entries)");
+        }
+        for (i, child) in children.iter().enumerate() {
+            writeln!(
+                out,
+                "{:>4}  {:<20}  {:>10}  {}",
+                i,
+                file_type_name(&child.file_type),
+                format_size(child.data.len() as u64),
+                node_name(child)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn node_name<'n>(node: &'n ExtractedNode) -> &'n str {
+    node.name.as_deref().unwrap_or("<unknown>")
+}
+
+/// Read commands from stdin until `quit` or end of input
+pub fn run_repl(data: &[u8], name: &str) -> io::Result<()> {
+    let mut session = Session::new(data, name);
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    writeln!(stdout, "Exploring {} (type 'help' for commands)", name)?;
+    loop {
+        write!(stdout, "{}> ", session.path())?;
+        stdout.flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            writeln!(stdout)?;
+            return Ok(());
+        }
+        if !session.execute(&line, &mut stdout)? {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::build_test_containers;
+
+    fn run(session: &mut Session, line: &str) -> String {
+        let mut out = Vec::new();
+        session.execute(line, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_navigate_nested_gzip() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"hello explorer\n").unwrap();
+        let notes = encoder.finish().unwrap();
+        let outer =
+            build_test_containers(&[("config.json", b"{\"a\": 1}"), ("notes.txt.gz", &notes)]);
+        let mut session = Session::new(&outer, "fw.bin");
+
+        let listing = run(&mut session, "ls");
+        assert!(listing.contains("config.json"));
+        assert!(listing.contains("notes.txt.gz"));
+
+        assert_eq!(run(&mut session, "cd notes.txt.gz"), "");
+        assert_eq!(run(&mut session, "pwd"), "/notes.txt.gz\n");
+        assert_eq!(run(&mut session, "cat 0"), "hello explorer\n");
+
+        run(&mut session, "cd ..");
+        assert_eq!(run(&mut session, "pwd"), "/\n");
+        assert!(run(&mut session, "cd 0").contains("nothing inside to browse"));
+        assert_eq!(run(&mut session, "cd 9"), "No entry 9\n");
+    }
+
+    #[test]
+    fn test_hexdump_and_quit() {
+        let outer = build_test_containers(&[("a.bin", b"ABCD")]);
+        let mut session = Session::new(&outer, "fw.bin");
+
+        assert!(run(&mut session, "hexdump a.bin 1").contains("|ABCD|"));
+        assert!(run(&mut session, "bogus").starts_with("Unknown command"));
+        assert!(!session.execute("quit", &mut Vec::new()).unwrap());
+    }
+}
@@ -0,0 +1,444 @@
+//! The recursively extracted firmware tree, held in memory
+//!
+//! Nodes borrow from the buffer being extracted wherever the bytes are stored as-is:
+//! container entries, tar and cpio members, and stored (uncompressed) ZIP members, at any
+//! depth of nesting. A copy is only made where the bytes don't exist in the buffer: gzip
+//! output, compressed ZIP members and images decoded from hex records. Everything nested
+//! inside such a copy is copied out of it too, since a node can't borrow from its parent.
+
+use std::borrow::Cow;
+use std::io::{Cursor, Read};
+use std::ops::Range;
+use std::path::Path;
+use zip::read::ZipArchive;
+use zip::CompressionMethod;
+
+use crate::cpio;
+use crate::file_types::{detect_file_type, file_type_name, FileType};
+use crate::gzip::{self, derive_decompressed_name, parse_gzip_header};
+use crate::hexfile;
+use crate::parser::iter_file_entries;
+use crate::tar;
+use crate::zip_utils::slice_to_eocd_with;
+
+/// A node of the recursively extracted firmware tree, held in memory
+#[derive(Debug)]
+pub struct ExtractedNode<'a> {
+    /// Position within the parent, in the parent's physical order
+    pub index: usize,
+    pub name: Option<String>,
+    pub file_type: FileType,
+    /// Borrowed from the extracted buffer unless it had to be decompressed or decoded
+    pub data: Cow<'a, [u8]>,
+    pub children: Vec<ExtractedNode<'a>>,
+}
+
+impl ExtractedNode<'_> {
+    /// Copy the node and its children out of the buffer they borrow from
+    pub fn into_owned(self) -> ExtractedNode<'static> {
+        ExtractedNode {
+            index: self.index,
+            name: self.name,
+            file_type: self.file_type,
+            data: Cow::Owned(self.data.into_owned()),
+            children: self
+                .children
+                .into_iter()
+                .map(ExtractedNode::into_owned)
+                .collect(),
+        }
+    }
+
+    /// Where the node's bytes sit in `buffer`, if the node borrows them from it
+    /// None for decompressed or decoded data, which has no place in the buffer
+    pub fn range_in(&self, buffer: &[u8]) -> Option<Range<usize>> {
+        let Cow::Borrowed(data) = self.data else {
+            return None;
+        };
+        crate::offset_map::range_in(buffer, data)
+    }
+
+    /// Convert the node (and its children, in order) into a JSON value
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "index": self.index,
+            "name": self.name,
+            "type": file_type_name(&self.file_type),
+            "size": self.data.len(),
+            "children": self.children.iter().map(|c| c.to_json()).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Recursively extract a buffer into an in-memory tree without touching the disk
+pub fn extract_to_memory<'a>(data: &'a [u8], filename: Option<&str>) -> ExtractedNode<'a> {
+    extract_to_depth(data, filename, usize::MAX)
+}
+
+/// Like `extract_to_memory`, honouring `--full-eocd-scan`
+pub fn extract_to_memory_with<'a>(
+    data: &'a [u8],
+    filename: Option<&str>,
+    full_eocd_scan: bool,
+) -> ExtractedNode<'a> {
+    extract_to_depth_with(data, filename, usize::MAX, full_eocd_scan)
+}
+
+/// Like `extract_to_memory`, but only unpack `levels` levels of nesting
+/// Nodes at the last level keep their data but have no children listed
+pub fn extract_to_depth<'a>(
+    data: &'a [u8],
+    filename: Option<&str>,
+    levels: usize,
+) -> ExtractedNode<'a> {
+    extract_to_depth_with(data, filename, levels, false)
+}
+
+/// Like `extract_to_depth`, but with `full_eocd_scan` each ZIP's EOCD record is searched for
+/// through the whole buffer, as `--full-eocd-scan` does for extraction to disk
+pub fn extract_to_depth_with<'a>(
+    data: &'a [u8],
+    filename: Option<&str>,
+    levels: usize,
+    full_eocd_scan: bool,
+) -> ExtractedNode<'a> {
+    extract_node(Cow::Borrowed(data), filename, 0, levels, full_eocd_scan)
+}
+
+fn extract_node<'a>(
+    data: Cow<'a, [u8]>,
+    filename: Option<&str>,
+    index: usize,
+    levels: usize,
+    full_scan: bool,
+) -> ExtractedNode<'a> {
+    let file_type = detect_file_type(&data, filename);
+
+    let children = match (levels.checked_sub(1), &data) {
+        (Some(levels), Cow::Borrowed(data)) => {
+            extract_children(data, filename, file_type, levels, full_scan)
+        }
+        // This node will own the bytes, so its children can't borrow them
+        (Some(levels), Cow::Owned(data)) => {
+            extract_children(data, filename, file_type, levels, full_scan)
+                .into_iter()
+                .map(ExtractedNode::into_owned)
+                .collect()
+        }
+        (None, _) => Vec::new(),
+    };
+
+    ExtractedNode {
+        index,
+        name: filename.map(|s| s.to_string()),
+        file_type,
+        data,
+        children,
+    }
+}
+
+/// The files directly inside `data`, each unpacked a further `levels` levels
+fn extract_children<'a>(
+    data: &'a [u8],
+    filename: Option<&str>,
+    file_type: FileType,
+    levels: usize,
+    full_scan: bool,
+) -> Vec<ExtractedNode<'a>> {
+    match file_type {
+        FileType::AutelContainer => iter_file_entries(data)
+            .map(|entry| {
+                extract_node(
+                    Cow::Borrowed(entry.content),
+                    entry.filename.as_deref(),
+                    entry.index,
+                    levels,
+                    full_scan,
+                )
+            })
+            .collect(),
+        FileType::Zip => extract_zip_children(data, levels, full_scan),
+        FileType::Tar => tar::list_entries(data)
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let content = Cow::Borrowed(tar::entry_data(data, entry));
+                extract_node(content, Some(&entry.name), i, levels, full_scan)
+            })
+            .collect(),
+        FileType::Cpio => cpio::list_entries(data)
+            .iter()
+            .filter(|entry| entry.is_regular())
+            .enumerate()
+            .map(|(i, entry)| {
+                let content = Cow::Borrowed(cpio::entry_data(data, entry));
+                extract_node(content, Some(&entry.name), i, levels, full_scan)
+            })
+            .collect(),
+        FileType::Gzip => extract_gzip_child(data, filename, levels, full_scan),
+        FileType::IntelHex | FileType::SRecord => {
+            extract_hex_image(data, filename, file_type, levels, full_scan)
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Rebuild the binary image of a record file as its single child node
+fn extract_hex_image(
+    data: &[u8],
+    filename: Option<&str>,
+    file_type: FileType,
+    levels: usize,
+    full_scan: bool,
+) -> Vec<ExtractedNode<'static>> {
+    let decoded = if file_type == FileType::IntelHex {
+        hexfile::decode_intel_hex(data)
+    } else {
+        hexfile::decode_srecord(data)
+    };
+    match decoded {
+        Ok(image) => {
+            let stem = filename
+                .and_then(|n| Path::new(n).file_stem())
+                .and_then(|s| s.to_str())
+                .unwrap_or("image");
+            vec![extract_node(
+                Cow::Owned(image.data),
+                Some(&format!("{}.bin", stem)),
+                0,
+                levels,
+                full_scan,
+            )]
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Decompress a gzip file into its single child node
+fn extract_gzip_child(
+    data: &[u8],
+    filename: Option<&str>,
+    levels: usize,
+    full_scan: bool,
+) -> Vec<ExtractedNode<'static>> {
+    let header = match parse_gzip_header(data) {
+        Some(h) => h,
+        None => return Vec::new(),
+    };
+    match gzip::decompress(data) {
+        Ok(decompressed) => {
+            let name = derive_decompressed_name(filename.unwrap_or("gzip"), &header);
+            vec![extract_node(
+                Cow::Owned(decompressed),
+                Some(&name),
+                0,
+                levels,
+                full_scan,
+            )]
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Extract ZIP members in archive order, skipping directories and unreadable members
+/// Stored members are sliced out of `data` (once their CRC32 checks out); compressed ones
+/// are inflated into a copy
+fn extract_zip_children(data: &[u8], levels: usize, full_scan: bool) -> Vec<ExtractedNode<'_>> {
+    let mut children = Vec::new();
+
+    let zip_slice = match slice_to_eocd_with(data, full_scan) {
+        Some(slice) => slice,
+        None => return children,
+    };
+    let mut archive = match ZipArchive::new(Cursor::new(zip_slice)) {
+        Ok(a) => a,
+        Err(_) => return children,
+    };
+
+    for i in 0..archive.len() {
+        let mut file = match archive.by_index(i) {
+            Ok(f) => f,
+            Err(_) => continue,
+        };
+        let name = file.name().to_string();
+        if name.ends_with('/') {
+            continue;
+        }
+
+        if let Some(stored) = stored_member(zip_slice, &file) {
+            children.push(extract_node(
+                Cow::Borrowed(stored),
+                Some(&name),
+                i,
+                levels,
+                full_scan,
+            ));
+            continue;
+        }
+        let mut contents = Vec::new();
+        if file.read_to_end(&mut contents).is_err() {
+            continue;
+        }
+        children.push(extract_node(
+            Cow::Owned(contents),
+            Some(&name),
+            i,
+            levels,
+            full_scan,
+        ));
+    }
+
+    children
+}
+
+/// A stored, unencrypted member's bytes as they sit in the archive, if its CRC32 matches
+fn stored_member<'a>(zip: &'a [u8], file: &zip::read::ZipFile<'_>) -> Option<&'a [u8]> {
+    if file.compression() != CompressionMethod::Stored || file.encrypted() {
+        return None;
+    }
+    let start = usize::try_from(file.data_start()).ok()?;
+    let len = usize::try_from(file.size()).ok()?;
+    let bytes = zip.get(start..start.checked_add(len)?)?;
+    (crc32fast::hash(bytes) == file.crc32()).then_some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_file_entries;
+    use crate::test_support::build_test_container;
+
+    #[test]
+    fn test_entry_order_is_preserved() {
+        let mut buffer = build_test_container("z", b"zzz");
+        buffer.extend_from_slice(&build_test_container("a", b"aaa"));
+        buffer.extend_from_slice(&build_test_container("m", b"mmm"));
+        let expected = ["z", "a", "m"];
+
+        let parsed: Vec<_> = parse_file_entries(&buffer)
+            .into_iter()
+            .map(|e| e.filename.unwrap())
+            .collect();
+        assert_eq!(parsed, expected);
+
+        let iterated: Vec<_> = iter_file_entries(&buffer)
+            .map(|e| e.filename.unwrap())
+            .collect();
+        assert_eq!(iterated, expected);
+
+        let tree = extract_to_memory(&buffer, Some("fw.bin"));
+        assert_eq!(tree.file_type, FileType::AutelContainer);
+        let names: Vec<_> = tree
+            .children
+            .iter()
+            .map(|c| c.name.clone().unwrap())
+            .collect();
+        assert_eq!(names, expected);
+        let indices: Vec<_> = tree.children.iter().map(|c| c.index).collect();
+        assert_eq!(indices, [0, 1, 2]);
+
+        let json = tree.to_json();
+        let json_names: Vec<_> = json["children"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(json_names, expected);
+        assert_eq!(json["children"][1]["index"], 1);
+    }
+
+    #[test]
+    fn test_extract_nested_container() {
+        let inner = build_test_container("inner.txt", b"hello");
+        let outer = build_test_container("nested.bin", &inner);
+
+        let tree = extract_to_memory(&outer, None);
+        assert_eq!(tree.children.len(), 1);
+        assert_eq!(tree.children[0].file_type, FileType::AutelContainer);
+        assert_eq!(&tree.children[0].children[0].data[..], b"hello");
+    }
+
+    /// Every node of the tree, depth first
+    fn all_nodes<'t, 'a>(node: &'t ExtractedNode<'a>, out: &mut Vec<&'t ExtractedNode<'a>>) {
+        out.push(node);
+        for child in &node.children {
+            all_nodes(child, out);
+        }
+    }
+
+    #[test]
+    fn test_nested_containers_borrow_the_buffer() {
+        let mut buffer = build_test_container("leaf.txt", b"deep");
+        for level in 0..5 {
+            buffer = build_test_container(&format!("level{}.bin", level), &buffer);
+        }
+
+        let tree = extract_to_memory(&buffer, Some("fw.bin"));
+        let mut nodes = Vec::new();
+        all_nodes(&tree, &mut nodes);
+        assert_eq!(nodes.len(), 7);
+        assert_eq!(&nodes[6].data[..], b"deep");
+
+        // No node holds a copy: each is a slice of the original buffer
+        let range = buffer.as_ptr_range();
+        for node in nodes {
+            assert!(
+                matches!(node.data, Cow::Borrowed(_)),
+                "{:?} was copied",
+                node.name
+            );
+            assert!(range.contains(&node.data.as_ptr()));
+        }
+    }
+
+    #[test]
+    fn test_only_compressed_zip_members_are_copied() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("stored.txt", stored).unwrap();
+        writer.write_all(b"kept in place").unwrap();
+        writer
+            .start_file("deflated.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&[b'x'; 200]).unwrap();
+        let zip = writer.finish().unwrap().into_inner();
+        let buffer = build_test_container("pkg.zip", &zip);
+
+        let tree = extract_to_memory(&buffer, Some("fw.bin"));
+        let members = &tree.children[0].children;
+        assert_eq!(&members[0].data[..], b"kept in place");
+        assert!(matches!(members[0].data, Cow::Borrowed(_)));
+        assert_eq!(members[1].data.len(), 200);
+        assert!(matches!(members[1].data, Cow::Owned(_)));
+
+        let owned = tree.into_owned();
+        assert!(matches!(owned.children[0].data, Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_full_eocd_scan_finds_zip_before_long_trailer() {
+        use std::io::Write;
+        use zip::write::SimpleFileOptions;
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("inner.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"past the window").unwrap();
+        let mut zip = writer.finish().unwrap().into_inner();
+        zip.extend_from_slice(&vec![0u8; 70 * 1024]);
+        let buffer = build_test_container("pkg.zip", &zip);
+
+        let tree = extract_to_memory(&buffer, Some("fw.bin"));
+        assert!(tree.children[0].children.is_empty());
+
+        let tree = extract_to_memory_with(&buffer, Some("fw.bin"), true);
+        let members = &tree.children[0].children;
+        assert_eq!(members.len(), 1);
+        assert_eq!(&members[0].data[..], b"past the window");
+    }
+}
@@ -1,15 +1,302 @@
+use std::io::{Cursor, Read};
+
+use serde::{Deserialize, Serialize};
+
+use crate::diagnostics::Diagnostic;
+use crate::file_types::{detect_file_type, FileType};
+
 /// Represents a parsed file entry from an Autel container
 #[derive(Debug)]
 pub struct FileEntry<'a> {
+    /// Position of this entry within its container, in byte-offset order
+    pub index: usize,
     pub filename: Option<String>,
+    /// Filename bytes exactly as stored, before decoding
+    pub raw_name_bytes: Option<&'a [u8]>,
     pub header_data: Option<&'a [u8; 4]>,
     pub content_meta: Option<&'a [u8; 4]>,
     pub content: &'a [u8],
-    // Fields below are kept for potential future use (debugging, raw access)
-    #[allow(dead_code)]
     pub content_length: usize,
+    // Fields below are kept for potential future use (debugging, raw access)
     #[allow(dead_code)]
     pub raw_content_data: &'a [u8],
-    #[allow(dead_code)]
     pub content_data_offset: usize,
 }
+
+impl FileEntry<'_> {
+    /// Offset just past this entry's content in the container buffer
+    pub fn end_offset(&self) -> usize {
+        self.content_data_offset + self.raw_content_data.len()
+    }
+
+    /// The content as a reader, for code that also handles ZIP members or feeds
+    /// decompressors and hashers
+    pub fn reader(&self) -> impl Read + '_ {
+        Cursor::new(self.content)
+    }
+
+    /// Offset the entry's length field says its content ends at, which is past the end
+    /// of the buffer for a truncated entry
+    pub fn declared_end(&self) -> usize {
+        match self.content_meta {
+            Some(_) => self
+                .content_data_offset
+                .saturating_add(8)
+                .saturating_add(self.content_length),
+            None => self.end_offset(),
+        }
+    }
+
+    /// Bytes the length field declares beyond what the buffer holds; 0 unless truncated
+    pub fn missing_bytes(&self) -> usize {
+        self.content_length.saturating_sub(self.content.len())
+    }
+
+    /// Detect the type of this entry's content, using its filename as a hint
+    pub fn detected_type(&self) -> FileType {
+        detect_file_type(self.content, self.filename.as_deref())
+    }
+}
+
+/// An owned, serializable copy of a `FileEntry`
+/// Header and meta bytes serialize as hex strings, content (when kept) as base64
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OwnedFileEntry {
+    pub index: usize,
+    pub filename: Option<String>,
+    pub file_type: FileType,
+    #[serde(with = "hex_bytes")]
+    pub header_data: Option<[u8; 4]>,
+    #[serde(with = "hex_bytes")]
+    pub content_meta: Option<[u8; 4]>,
+    pub content_length: usize,
+    /// Bytes actually available (less than `content_length` if truncated)
+    pub size: usize,
+    pub content_data_offset: usize,
+    /// In a manifest, the file the entry was written to (relative to the manifest) when
+    /// that isn't `filename`, e.g. after `--name-transform` or `--index-prefix`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stored_as: Option<String>,
+    /// Only kept when asked for, so manifests don't embed whole payloads
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "base64_bytes"
+    )]
+    pub content: Option<Vec<u8>>,
+}
+
+impl OwnedFileEntry {
+    /// Copy an entry, keeping its content bytes only if `include_content` is set
+    pub fn from_entry(entry: &FileEntry, include_content: bool) -> OwnedFileEntry {
+        OwnedFileEntry {
+            index: entry.index,
+            filename: entry.filename.clone(),
+            file_type: entry.detected_type(),
+            header_data: entry.header_data.copied(),
+            content_meta: entry.content_meta.copied(),
+            content_length: entry.content_length,
+            size: entry.content.len(),
+            content_data_offset: entry.content_data_offset,
+            stored_as: None,
+            content: include_content.then(|| entry.content.to_vec()),
+        }
+    }
+}
+
+/// Copies everything except the content bytes
+impl From<&FileEntry<'_>> for OwnedFileEntry {
+    fn from(entry: &FileEntry) -> Self {
+        OwnedFileEntry::from_entry(entry, false)
+    }
+}
+
+mod hex_bytes {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    use crate::hash::to_hex;
+
+    pub fn serialize<S: Serializer>(
+        bytes: &Option<[u8; 4]>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(b) => serializer.serialize_some(&to_hex(b)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<[u8; 4]>, D::Error> {
+        let hex = match Option::<String>::deserialize(deserializer)? {
+            Some(h) => h,
+            None => return Ok(None),
+        };
+        let mut bytes = [0u8; 4];
+        if hex.len() != 8 || !hex.is_ascii() {
+            return Err(de::Error::custom(format!(
+                "expected 8 hex digits, got '{}'",
+                hex
+            )));
+        }
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).map_err(de::Error::custom)?;
+        }
+        Ok(Some(bytes))
+    }
+}
+
+mod base64_bytes {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        bytes: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(b) => serializer.serialize_some(&STANDARD.encode(b)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(encoded) => STANDARD
+                .decode(encoded)
+                .map(Some)
+                .map_err(de::Error::custom),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A fully parsed Autel container
+#[derive(Debug)]
+pub struct Container<'a> {
+    pub entries: Vec<FileEntry<'a>>,
+    /// Bytes between the end of the last entry and the end of the buffer
+    /// (often an appended signature or CRC)
+    pub trailer: Option<&'a [u8]>,
+    /// Number of entries skipped because their tag sequence was incomplete
+    pub malformed: usize,
+    /// Parsing stopped at the entry cap, so `entries` is incomplete
+    pub cap_reached: bool,
+    /// Warnings raised while parsing, in the order they occurred
+    pub diagnostics: Vec<Diagnostic>,
+    /// Unparsed bytes between consecutive entries, in buffer order
+    pub gaps: Vec<Gap>,
+    /// Entries found after the first structural error
+    pub salvaged: usize,
+    /// How the tags of the parsed entries are written; None if no tag was parsed
+    pub tag_style: Option<TagStyle>,
+}
+
+/// How a container writes its `<filetransfer>`/`<fileinfo>`/`<filecontent>` tags
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TagStyle {
+    /// Wrapped in double quotes (`"<filetransfer>"`), as in every known firmware
+    Quoted,
+    /// Without the quotes (`<filetransfer>`)
+    Bare,
+    /// Both forms in the one container
+    Mixed,
+}
+
+impl TagStyle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TagStyle::Quoted => "quoted",
+            TagStyle::Bare => "bare",
+            TagStyle::Mixed => "mixed quoted and bare",
+        }
+    }
+}
+
+impl Container<'_> {
+    /// Total bytes not accounted for between entries
+    pub fn gap_bytes(&self) -> usize {
+        self.gaps.iter().map(|g| g.len).sum()
+    }
+}
+
+/// Bytes between the end of one entry's content and the next `<filetransfer>` tag
+/// Any gap at all means padding or structure the parser doesn't model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gap {
+    pub offset: usize,
+    pub len: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detected_type_uses_filename_hint() {
+        let content = b"not json, but named like it";
+        let entry = FileEntry {
+            index: 0,
+            filename: Some("config.json".to_string()),
+            raw_name_bytes: None,
+            header_data: None,
+            content_meta: None,
+            content,
+            content_length: content.len(),
+            raw_content_data: content,
+            content_data_offset: 0,
+        };
+        assert_eq!(entry.detected_type(), FileType::Json);
+    }
+
+    #[test]
+    fn test_reader_yields_content() {
+        let content = b"streamed";
+        let entry = FileEntry {
+            index: 0,
+            filename: None,
+            raw_name_bytes: None,
+            header_data: None,
+            content_meta: None,
+            content,
+            content_length: content.len(),
+            raw_content_data: content,
+            content_data_offset: 0,
+        };
+        let mut read = Vec::new();
+        entry.reader().read_to_end(&mut read).unwrap();
+        assert_eq!(read, content);
+    }
+
+    #[test]
+    fn test_owned_entry_serde_roundtrip() {
+        let content = b"hello";
+        let entry = FileEntry {
+            index: 2,
+            filename: Some("notes.txt".to_string()),
+            raw_name_bytes: None,
+            header_data: Some(&[0xfd, 0xce, 0x69, 0x48]),
+            content_meta: Some(&[0x33, 0xa8, 0x3b, 0x1f]),
+            content,
+            content_length: content.len(),
+            raw_content_data: content,
+            content_data_offset: 64,
+        };
+
+        let manifest = serde_json::to_value(OwnedFileEntry::from(&entry)).unwrap();
+        assert_eq!(manifest["header_data"], "fdce6948");
+        assert_eq!(manifest["content_meta"], "33a83b1f");
+        assert_eq!(manifest["file_type"], "Text");
+        assert!(manifest.get("content").is_none());
+
+        let owned = OwnedFileEntry::from_entry(&entry, true);
+        let json = serde_json::to_string(&owned).unwrap();
+        assert!(json.contains("\"content\":\"aGVsbG8=\""));
+        let parsed: OwnedFileEntry = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, owned);
+    }
+}
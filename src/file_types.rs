@@ -1,9 +1,25 @@
+use std::path::Path;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::cpio::is_cpio;
+use crate::hash::to_hex;
+use crate::hexfile::{is_intel_hex, is_srecord};
+use crate::image::{JPEG_MAGIC, PNG_MAGIC};
+use crate::parser::find_container_start;
+use crate::squashfs::is_squashfs;
+use crate::tar::is_tar;
+
 /// File type enumeration for detected firmware formats
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub enum FileType {
     AutelContainer, // "<filetransfer>" format
     Zip,
+    Tar,
+    Cpio,     // "070701" (newc)
+    SquashFs, // "hsqs" / "sqsh"
     Gzip,
+    Xz,
     Json,
     UpgGimbal, // 34 12 ef be
     UpgFcs,    // 55 50 46 53 "UPFS"
@@ -11,97 +27,425 @@ pub enum FileType {
     UpgEsc,    // 00 00 00 00 xx
     UpgRcMcu,  // 34 12 ef be 0e
     GpsBin,    // @TD1050x
+    IntelHex,  // ":" records
+    SRecord,   // "S0".."S9" records
+    Png,       // 89 50 4e 47
+    Jpeg,      // ff d8 ff
     Text,
     Unknown,
 }
 
-/// Detect the file type based on magic bytes and filename
-pub fn detect_file_type(data: &[u8], filename: Option<&str>) -> FileType {
-    if data.len() < 4 {
-        return FileType::Unknown;
-    }
+impl FileType {
+    /// Every file type, in declaration order
+    pub const ALL: [FileType; 20] = [
+        FileType::AutelContainer,
+        FileType::Zip,
+        FileType::Tar,
+        FileType::Cpio,
+        FileType::SquashFs,
+        FileType::Gzip,
+        FileType::Xz,
+        FileType::Json,
+        FileType::UpgGimbal,
+        FileType::UpgFcs,
+        FileType::UpgBms,
+        FileType::UpgEsc,
+        FileType::UpgRcMcu,
+        FileType::GpsBin,
+        FileType::IntelHex,
+        FileType::SRecord,
+        FileType::Png,
+        FileType::Jpeg,
+        FileType::Text,
+        FileType::Unknown,
+    ];
+}
+
+/// Broad grouping of file types, handy for filtering
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FileFamily {
+    Container,
+    Archive,
+    Compressed,
+    /// Any of the `Upg*` device firmware images
+    Upg,
+    Gps,
+    Image,
+    Text,
+    Unknown,
+}
 
-    // Check for Autel container format
-    if data.len() >= 16 {
-        if let Some(pos) = data
-            .windows(14)
-            .position(|w| w == b"\"<filetransfer>\"".as_slice().get(..14).unwrap_or(&[]))
-        {
-            if pos < 100 {
-                return FileType::AutelContainer;
+impl FileType {
+    pub fn family(&self) -> FileFamily {
+        match self {
+            FileType::AutelContainer => FileFamily::Container,
+            FileType::Zip | FileType::Tar | FileType::Cpio | FileType::SquashFs => {
+                FileFamily::Archive
             }
-        }
-        // Also check without quotes
-        for i in 0..100.min(data.len().saturating_sub(16)) {
-            if &data[i..i + 2] == b"\"<" {
-                if let Ok(s) = std::str::from_utf8(&data[i..i + 16.min(data.len() - i)]) {
-                    if s.contains("<filetransfer>") {
-                        return FileType::AutelContainer;
-                    }
-                }
+            FileType::Gzip | FileType::Xz => FileFamily::Compressed,
+            FileType::UpgGimbal
+            | FileType::UpgFcs
+            | FileType::UpgBms
+            | FileType::UpgEsc
+            | FileType::UpgRcMcu => FileFamily::Upg,
+            FileType::GpsBin => FileFamily::Gps,
+            FileType::Png | FileType::Jpeg => FileFamily::Image,
+            FileType::Json | FileType::IntelHex | FileType::SRecord | FileType::Text => {
+                FileFamily::Text
             }
+            FileType::Unknown => FileFamily::Unknown,
+        }
+    }
+}
+
+/// Subsystem folder a file type belongs to when grouping output by device
+pub fn device_folder(ft: FileType) -> Option<&'static str> {
+    match ft {
+        FileType::UpgGimbal => Some("gimbal"),
+        FileType::UpgFcs => Some("fcs"),
+        FileType::UpgBms => Some("battery"),
+        FileType::UpgEsc => Some("esc"),
+        FileType::UpgRcMcu => Some("rc"),
+        FileType::GpsBin => Some("gps"),
+        _ => None,
+    }
+}
+
+/// Magic shared by the gimbal and RC MCU images; the next byte picks the device
+const UPG_DEVICE_MAGIC: [u8; 4] = [0x34, 0x12, 0xef, 0xbe];
+
+/// The device/sub-type byte that follows the `34 12 ef be` magic, if present
+pub fn upg_device_id(data: &[u8]) -> Option<u8> {
+    match data {
+        [a, b, c, d, id, ..] if [*a, *b, *c, *d] == UPG_DEVICE_MAGIC => Some(*id),
+        _ => None,
+    }
+}
+
+/// Name of a known `34 12 ef be` device id
+pub fn upg_device_name(id: u8) -> Option<&'static str> {
+    match id {
+        0x0e => Some("RC MCU"),
+        _ => None,
+    }
+}
+
+/// Label for a `34 12 ef be` device id: `UPG (RC MCU)` for a known one, and the raw id,
+/// as in `UPG (device 0x21)`, for any other
+pub fn upg_device_label(id: u8) -> String {
+    match upg_device_name(id) {
+        Some(name) => format!("UPG ({})", name),
+        None => format!("UPG (device 0x{:02x})", id),
+    }
+}
+
+/// Bytes after the ESC header sampled by `is_plausible_esc_body`
+const ESC_BODY_SAMPLE: usize = 512;
+
+/// Whether what follows an `00 00 00 00 <id>` header looks like firmware code
+/// Four zero bytes open countless padded binaries, so the header alone is weak evidence.
+/// A body that is mostly zeros is padding, not code; a bare header gets the benefit of the doubt
+fn is_plausible_esc_body(data: &[u8]) -> bool {
+    let body = data.get(8..).unwrap_or(&[]);
+    let sample = &body[..body.len().min(ESC_BODY_SAMPLE)];
+    let zeros = sample.iter().filter(|&&b| b == 0).count();
+    zeros * 4 < sample.len() * 3 || sample.is_empty()
+}
+
+/// How much weight a detection deserves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    /// Only the filename or a loose content heuristic (any UTF-8 is "text")
+    Low,
+    /// A structural check passed, or a marker was found somewhere other than offset 0
+    Medium,
+    /// Magic bytes matched at their fixed offset
+    High,
+}
+
+/// Why data was classified as it was
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub file_type: FileType,
+    /// Short name of the rule that decided, None when nothing matched
+    pub matched_rule: Option<&'static str>,
+    /// Where in the data the deciding bytes were found, if the rule looks at specific bytes
+    pub match_offset: Option<usize>,
+    pub confidence: Confidence,
+    /// Framing bytes skipped in front of the payload the rule matched, 0 if none
+    pub skipped: usize,
+}
+
+impl Detection {
+    fn new(
+        file_type: FileType,
+        rule: &'static str,
+        offset: Option<usize>,
+        confidence: Confidence,
+    ) -> Detection {
+        Detection {
+            file_type,
+            matched_rule: Some(rule),
+            match_offset: offset,
+            confidence,
+            skipped: 0,
+        }
+    }
+
+    fn magic(file_type: FileType, rule: &'static str) -> Detection {
+        Detection::new(file_type, rule, Some(0), Confidence::High)
+    }
+
+    fn unknown() -> Detection {
+        Detection {
+            file_type: FileType::Unknown,
+            matched_rule: None,
+            match_offset: None,
+            confidence: Confidence::Low,
+            skipped: 0,
         }
     }
+}
+
+/// Detect the file type based on magic bytes and filename
+pub fn detect_file_type(data: &[u8], filename: Option<&str>) -> FileType {
+    detect_and_describe(data, filename).file_type
+}
+
+/// Like `detect_file_type`, but also report which rule matched, where, and how surely
+pub fn detect_and_describe(data: &[u8], filename: Option<&str>) -> Detection {
+    let detection = describe(data, filename);
+    tracing::trace!(
+        file_type = file_type_name(&detection.file_type),
+        rule = detection.matched_rule,
+        offset = detection.match_offset,
+        size = data.len(),
+        "detected"
+    );
+    detection
+}
+
+/// Widest framing prefix `detect_after_framing` will skip; further in, magic bytes start
+/// matching by chance
+pub const MAX_FRAMING_SKIP: usize = 4;
+
+/// Like `detect_and_describe`, but when nothing matches, retry up to `max_skip` bytes in for
+/// a payload behind a framing/type byte (see `detect_after_framing`)
+pub fn detect_and_describe_skipping(
+    data: &[u8],
+    filename: Option<&str>,
+    max_skip: usize,
+) -> Detection {
+    let detection = detect_and_describe(data, filename);
+    if detection.file_type != FileType::Unknown {
+        return detection;
+    }
+    detect_after_framing(data, max_skip).unwrap_or(detection)
+}
+
+/// Look for magic bytes 1 to `max_skip` (at most `MAX_FRAMING_SKIP`) bytes into data that
+/// matched nothing, as when an entry prepends a framing byte (0x0e) to a UPG image
+/// Only high-confidence magic matches count; text and extension rules would match anything
+pub fn detect_after_framing(data: &[u8], max_skip: usize) -> Option<Detection> {
+    (1..=max_skip.min(MAX_FRAMING_SKIP))
+        .take_while(|&skip| skip < data.len())
+        .find_map(|skip| {
+            let inner = describe(&data[skip..], None);
+            (inner.confidence == Confidence::High).then(|| Detection {
+                match_offset: inner.match_offset.map(|o| o + skip),
+                skipped: skip,
+                ..inner
+            })
+        })
+        .inspect(|d| {
+            tracing::trace!(
+                file_type = file_type_name(&d.file_type),
+                rule = d.matched_rule,
+                skipped = d.skipped,
+                "detected after framing"
+            )
+        })
+}
+
+/// "Detected <type> after skipping N framing byte(s) (<hex>)", for a detection that skipped
+/// a prefix of `data`
+pub fn framing_note(detection: &Detection, data: &[u8]) -> String {
+    let framing = &data[..detection.skipped.min(data.len())];
+    format!(
+        "Detected {} after skipping {} framing byte{} ({})",
+        file_type_name(&detection.file_type),
+        framing.len(),
+        if framing.len() == 1 { "" } else { "s" },
+        to_hex(framing)
+    )
+}
+
+fn describe(data: &[u8], filename: Option<&str>) -> Detection {
+    if data.len() < 4 {
+        return Detection::unknown();
+    }
+
+    if let Some(pos) = find_container_start(data) {
+        let confidence = if pos == 0 {
+            Confidence::High
+        } else {
+            Confidence::Medium
+        };
+        return Detection::new(
+            FileType::AutelContainer,
+            "filetransfer-tag",
+            Some(pos),
+            confidence,
+        );
+    }
 
     // Check magic bytes
     if &data[0..4] == b"PK\x03\x04" {
-        return FileType::Zip;
+        return Detection::magic(FileType::Zip, "zip-local-header");
     }
 
     if data.len() >= 2 && data[0..2] == [0x1f, 0x8b] {
-        return FileType::Gzip;
+        return Detection::magic(FileType::Gzip, "gzip-magic");
+    }
+
+    if data.len() >= 6 && data[0..6] == [0xfd, b'7', b'z', b'X', b'Z', 0x00] {
+        return Detection::magic(FileType::Xz, "xz-magic");
     }
 
-    if data[0..4] == [0x34, 0x12, 0xef, 0xbe] {
-        if data.len() >= 5 && data[4] == 0x0e {
-            return FileType::UpgRcMcu;
+    if data[0..4] == UPG_DEVICE_MAGIC {
+        if upg_device_id(data) == Some(0x0e) {
+            return Detection::magic(FileType::UpgRcMcu, "upg-device-magic-rc-mcu");
         }
-        return FileType::UpgGimbal;
+        return Detection::magic(FileType::UpgGimbal, "upg-device-magic");
     }
 
     if &data[0..4] == b"UPFS" {
-        return FileType::UpgFcs;
+        return Detection::magic(FileType::UpgFcs, "upfs-magic");
     }
 
     if data[0..4] == [0x02, 0xaa, 0x55, 0xaa] {
-        return FileType::UpgBms;
+        return Detection::magic(FileType::UpgBms, "bms-magic");
     }
 
     if data[0..4] == [0x00, 0x00, 0x00, 0x00] && data.len() >= 5 {
         // ESC firmware has 00 00 00 00 followed by ESC ID (0x14-0x17)
-        if data[4] >= 0x14 && data[4] <= 0x17 {
-            return FileType::UpgEsc;
+        if data[4] >= 0x14 && data[4] <= 0x17 && is_plausible_esc_body(data) {
+            return Detection::new(FileType::UpgEsc, "esc-header", Some(0), Confidence::Medium);
         }
     }
 
+    if is_cpio(data) {
+        return Detection::magic(FileType::Cpio, "cpio-newc-magic");
+    }
+
+    if is_squashfs(data) {
+        return Detection::magic(FileType::SquashFs, "squashfs-magic");
+    }
+
     if data.len() >= 8 && &data[0..8] == b"@TD1050x" {
-        return FileType::GpsBin;
+        return Detection::magic(FileType::GpsBin, "gps-magic");
     }
 
-    // Check by filename extension
-    if let Some(name) = filename {
-        if name.ends_with(".json") {
-            return FileType::Json;
-        }
-        if name.ends_with(".zip") {
-            return FileType::Zip;
+    if data.starts_with(&PNG_MAGIC) {
+        return Detection::magic(FileType::Png, "png-magic");
+    }
+
+    if data.starts_with(&JPEG_MAGIC) {
+        return Detection::magic(FileType::Jpeg, "jpeg-magic");
+    }
+
+    if is_tar(data) {
+        // Old-style (pre-POSIX) headers are only recognized by their checksum
+        if data.get(257..262) == Some(b"ustar".as_slice()) {
+            return Detection::new(FileType::Tar, "ustar-magic", Some(257), Confidence::High);
         }
+        return Detection::new(
+            FileType::Tar,
+            "tar-header-checksum",
+            Some(0),
+            Confidence::Medium,
+        );
+    }
+
+    // Check by filename extension
+    if let Some(ft) = filename.and_then(detect_by_extension) {
+        return Detection::new(ft, "extension", None, Confidence::Low);
     }
 
     // Check if it's valid UTF-8 text
     if std::str::from_utf8(data).is_ok() {
+        if is_intel_hex(data) {
+            return Detection::new(
+                FileType::IntelHex,
+                "intel-hex-records",
+                Some(0),
+                Confidence::Medium,
+            );
+        }
+        if is_srecord(data) {
+            return Detection::new(
+                FileType::SRecord,
+                "srecord-records",
+                Some(0),
+                Confidence::Medium,
+            );
+        }
+
         // Check if it looks like JSON
         let trimmed = data
             .iter()
             .position(|&b| !b.is_ascii_whitespace())
             .unwrap_or(0);
         if data.len() > trimmed && (data[trimmed] == b'{' || data[trimmed] == b'[') {
-            return FileType::Json;
+            return Detection::new(
+                FileType::Json,
+                "json-bracket",
+                Some(trimmed),
+                Confidence::Low,
+            );
         }
-        return FileType::Text;
+        return Detection::new(FileType::Text, "utf8-text", None, Confidence::Low);
     }
 
-    FileType::Unknown
+    Detection::unknown()
+}
+
+/// Map a filename extension (case-insensitive) to a file type
+/// Only consulted when no magic bytes matched
+fn detect_by_extension(name: &str) -> Option<FileType> {
+    let lower = name.to_lowercase();
+    let ext = Path::new(&lower).extension()?.to_str()?;
+
+    match ext {
+        "json" => Some(FileType::Json),
+        "zip" => Some(FileType::Zip),
+        "tar" => Some(FileType::Tar),
+        "cpio" => Some(FileType::Cpio),
+        "gz" | "tgz" => Some(FileType::Gzip),
+        "xz" => Some(FileType::Xz),
+        "upg" => upg_variant_from_name(&lower),
+        "bin" if lower.contains("gps") => Some(FileType::GpsBin),
+        _ => None,
+    }
+}
+
+/// Pick a UPG variant from hints in a (lowercased) filename
+fn upg_variant_from_name(lower: &str) -> Option<FileType> {
+    let stem = Path::new(lower).file_stem()?.to_str()?;
+
+    if stem.contains("gimbal") {
+        Some(FileType::UpgGimbal)
+    } else if stem.contains("fcs") {
+        Some(FileType::UpgFcs)
+    } else if stem.contains("bms") || stem.contains("battery") {
+        Some(FileType::UpgBms)
+    } else if stem.contains("esc") {
+        Some(FileType::UpgEsc)
+    } else if stem.contains("rc_mcu") || stem.contains("rcmcu") {
+        Some(FileType::UpgRcMcu)
+    } else {
+        None
+    }
 }
 
 /// Get a human-readable name for a file type
@@ -109,7 +453,11 @@ pub fn file_type_name(ft: &FileType) -> &'static str {
     match ft {
         FileType::AutelContainer => "Autel Container",
         FileType::Zip => "ZIP Archive",
+        FileType::Tar => "Tar Archive",
+        FileType::Cpio => "CPIO Archive",
+        FileType::SquashFs => "SquashFS Image",
         FileType::Gzip => "Gzip Compressed",
+        FileType::Xz => "XZ Compressed",
         FileType::Json => "JSON",
         FileType::UpgGimbal => "UPG (Gimbal)",
         FileType::UpgFcs => "UPG (Flight Control System)",
@@ -117,15 +465,167 @@ pub fn file_type_name(ft: &FileType) -> &'static str {
         FileType::UpgEsc => "UPG (ESC)",
         FileType::UpgRcMcu => "UPG (RC MCU)",
         FileType::GpsBin => "GPS Binary",
+        FileType::IntelHex => "Intel HEX",
+        FileType::SRecord => "Motorola S-record",
+        FileType::Png => "PNG Image",
+        FileType::Jpeg => "JPEG Image",
         FileType::Text => "Text",
         FileType::Unknown => "Unknown",
     }
 }
 
+/// Look up a file type by its human-readable name (the inverse of `file_type_name`)
+pub fn file_type_from_name(name: &str) -> Option<FileType> {
+    FileType::ALL
+        .into_iter()
+        .find(|ft| file_type_name(ft) == name)
+}
+
+/// Look up a file type by its variant name (`AutelContainer`) or display name
+/// (`Autel Container`), ignoring case; meant for command line arguments
+pub fn file_type_from_arg(name: &str) -> Option<FileType> {
+    FileType::ALL.into_iter().find(|ft| {
+        format!("{:?}", ft).eq_ignore_ascii_case(name)
+            || file_type_name(ft).eq_ignore_ascii_case(name)
+    })
+}
+
+/// Serialized as its human-readable name, e.g. `"UPG (Gimbal)"`
+impl Serialize for FileType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(file_type_name(self))
+    }
+}
+
+impl<'de> Deserialize<'de> for FileType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        file_type_from_name(&name)
+            .ok_or_else(|| de::Error::custom(format!("unknown file type: '{}'", name)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_detect_and_describe() {
+        let mut container = b"junk".to_vec();
+        container.extend_from_slice(b"\"<filetransfer>\"\"<fileinfo>\"");
+        let detection = detect_and_describe(&container, None);
+        assert_eq!(detection.file_type, FileType::AutelContainer);
+        assert_eq!(detection.matched_rule, Some("filetransfer-tag"));
+        assert_eq!(detection.match_offset, Some(4));
+        assert_eq!(detection.confidence, Confidence::Medium);
+
+        let detection = detect_and_describe(b"PK\x03\x04rest", None);
+        assert_eq!(detection.matched_rule, Some("zip-local-header"));
+        assert_eq!(detection.confidence, Confidence::High);
+
+        let detection = detect_and_describe(b"  {\"a\": 1}", None);
+        assert_eq!(detection.file_type, FileType::Json);
+        assert_eq!(detection.match_offset, Some(2));
+        assert_eq!(detection.confidence, Confidence::Low);
+
+        let detection = detect_and_describe(&[0xff, 0x00, 0xfe, 0x01], Some("blob.xz"));
+        assert_eq!(detection.file_type, FileType::Xz);
+        assert_eq!(detection.matched_rule, Some("extension"));
+        assert_eq!(detection.match_offset, None);
+
+        let detection = detect_and_describe(&[0xff, 0x00, 0xfe, 0x01], None);
+        assert_eq!(detection.file_type, FileType::Unknown);
+        assert_eq!(detection.matched_rule, None);
+    }
+
+    #[test]
+    fn test_detect_after_framing_byte() {
+        let mut upg = vec![0x0e];
+        upg.extend_from_slice(&UPG_DEVICE_MAGIC);
+        upg.extend_from_slice(&[0x03, 0x00, 0x10, 0x20]);
+        assert_eq!(detect_file_type(&upg, None), FileType::Unknown);
+
+        let detection = detect_and_describe_skipping(&upg, None, 1);
+        assert_eq!(detection.file_type, FileType::UpgGimbal);
+        assert_eq!(detection.skipped, 1);
+        assert_eq!(detection.match_offset, Some(1));
+        assert_eq!(
+            framing_note(&detection, &upg),
+            "Detected UPG (Gimbal) after skipping 1 framing byte (0e)"
+        );
+
+        // Off by default, and a match further in than the window is not found
+        assert_eq!(detect_and_describe_skipping(&upg, None, 0).skipped, 0);
+        let mut deeper = vec![0xff, 0xfe];
+        deeper.extend_from_slice(&upg);
+        assert_eq!(detect_after_framing(&deeper, 2), None);
+        assert_eq!(detect_after_framing(&deeper, 3).unwrap().skipped, 3);
+
+        // Something already recognized is left alone
+        let detection = detect_and_describe_skipping(b"PK\x03\x04rest", None, 4);
+        assert_eq!(detection.skipped, 0);
+    }
+
+    #[test]
+    fn test_file_type_name_roundtrip() {
+        for ft in FileType::ALL {
+            assert_eq!(file_type_from_name(file_type_name(&ft)), Some(ft));
+        }
+        assert_eq!(file_type_from_name("nope"), None);
+    }
+
+    #[test]
+    fn test_file_type_from_arg() {
+        assert_eq!(
+            file_type_from_arg("AutelContainer"),
+            Some(FileType::AutelContainer)
+        );
+        assert_eq!(file_type_from_arg("zip"), Some(FileType::Zip));
+        assert_eq!(file_type_from_arg("ZIP Archive"), Some(FileType::Zip));
+        assert_eq!(
+            file_type_from_arg("upg (gimbal)"),
+            Some(FileType::UpgGimbal)
+        );
+        assert_eq!(file_type_from_arg("bogus"), None);
+    }
+
+    #[test]
+    fn test_file_type_serde() {
+        let json = serde_json::to_string(&FileType::UpgGimbal).unwrap();
+        assert_eq!(json, "\"UPG (Gimbal)\"");
+        let parsed: FileType = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, FileType::UpgGimbal);
+        assert!(serde_json::from_str::<FileType>("\"Bogus\"").is_err());
+    }
+
+    #[test]
+    fn test_detect_cpio() {
+        // A newc header is all ASCII, so it must win over the text check
+        let mut data = b"070701".to_vec();
+        data.extend_from_slice(&[b'0'; 104]);
+        let detection = detect_and_describe(&data, None);
+        assert_eq!(detection.file_type, FileType::Cpio);
+        assert_eq!(detection.matched_rule, Some("cpio-newc-magic"));
+        assert_eq!(
+            detect_file_type(b"not really", Some("initramfs.cpio")),
+            FileType::Cpio
+        );
+    }
+
+    #[test]
+    fn test_detect_squashfs() {
+        let mut data = vec![0u8; 96];
+        data[..4].copy_from_slice(b"hsqs");
+        data[28] = 4;
+        let detection = detect_and_describe(&data, None);
+        assert_eq!(detection.file_type, FileType::SquashFs);
+        assert_eq!(detection.matched_rule, Some("squashfs-magic"));
+
+        // The magic alone, with an impossible version, is not enough
+        data[28] = 0x40;
+        assert_ne!(detect_file_type(&data, None), FileType::SquashFs);
+    }
+
     #[test]
     fn test_detect_zip_by_magic() {
         let zip_data = b"PK\x03\x04some zip content here";
@@ -138,6 +638,130 @@ mod tests {
         assert_eq!(detect_file_type(data, Some("file.zip")), FileType::Zip);
     }
 
+    #[test]
+    fn test_detect_extension_case_insensitive() {
+        let data = b"plain text content";
+        assert_eq!(detect_file_type(data, Some("CONFIG.JSON")), FileType::Json);
+        assert_eq!(detect_file_type(data, Some("Archive.Zip")), FileType::Zip);
+        assert_eq!(detect_file_type(data, Some("logs.tar.GZ")), FileType::Gzip);
+        assert_eq!(detect_file_type(data, Some("rootfs.XZ")), FileType::Xz);
+    }
+
+    #[test]
+    fn test_detect_upg_by_extension() {
+        let data = &[0xde, 0xad, 0xbe, 0xef, 0x00, 0xff, 0x80, 0x7f];
+        assert_eq!(
+            detect_file_type(data, Some("Gimbal_V1.2.UPG")),
+            FileType::UpgGimbal
+        );
+        assert_eq!(detect_file_type(data, Some("fcs.upg")), FileType::UpgFcs);
+        assert_eq!(
+            detect_file_type(data, Some("battery.upg")),
+            FileType::UpgBms
+        );
+        assert_eq!(detect_file_type(data, Some("esc.upg")), FileType::UpgEsc);
+        assert_eq!(
+            detect_file_type(data, Some("rc_mcu.upg")),
+            FileType::UpgRcMcu
+        );
+        // No hint in the name leaves it to content detection
+        assert_eq!(
+            detect_file_type(data, Some("firmware.UPG")),
+            FileType::Unknown
+        );
+    }
+
+    #[test]
+    fn test_detect_record_text() {
+        let ihex = b":10000000000102030405060708090A0B0C0D0E0F78\n:00000001FF\n";
+        assert_eq!(detect_file_type(ihex, None), FileType::IntelHex);
+        let srec = b"S1070100DEADBEEFBF\nS9030000FC\n";
+        assert_eq!(detect_file_type(srec, None), FileType::SRecord);
+        assert_eq!(detect_file_type(b"Some plain text", None), FileType::Text);
+    }
+
+    #[test]
+    fn test_detect_tar() {
+        let mut data = vec![0u8; 1024];
+        data[..5].copy_from_slice(b"a.txt");
+        data[257..263].copy_from_slice(b"ustar\0");
+        assert_eq!(detect_file_type(&data, None), FileType::Tar);
+        assert_eq!(
+            detect_file_type(b"plain text", Some("rootfs.TAR")),
+            FileType::Tar
+        );
+    }
+
+    #[test]
+    fn test_detect_images() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR";
+        assert_eq!(detect_file_type(png, Some("logo.bin")), FileType::Png);
+        assert_eq!(
+            detect_file_type(b"\xff\xd8\xff\xe0\0\x10JFIF", None),
+            FileType::Jpeg
+        );
+        assert_eq!(FileType::Jpeg.family(), FileFamily::Image);
+    }
+
+    #[test]
+    fn test_device_folder() {
+        assert_eq!(device_folder(FileType::UpgRcMcu), Some("rc"));
+        assert_eq!(device_folder(FileType::GpsBin), Some("gps"));
+        assert_eq!(device_folder(FileType::Json), None);
+    }
+
+    #[test]
+    fn test_upg_device_id() {
+        let rc_mcu = [0x34, 0x12, 0xef, 0xbe, 0x0e, 0x00];
+        let other = [0x34, 0x12, 0xef, 0xbe, 0x21, 0x00];
+        assert_eq!(upg_device_id(&rc_mcu), Some(0x0e));
+        assert_eq!(upg_device_name(0x0e), Some("RC MCU"));
+        assert_eq!(upg_device_id(&other), Some(0x21));
+        assert_eq!(upg_device_name(0x21), None);
+        assert_eq!(upg_device_label(0x0e), "UPG (RC MCU)");
+        assert_eq!(upg_device_label(0x21), "UPG (device 0x21)");
+        assert_eq!(upg_device_label(0x00), "UPG (device 0x00)");
+        assert_eq!(upg_device_id(&[0x34, 0x12, 0xef, 0xbe]), None);
+        assert_eq!(upg_device_id(b"UPFS\x0e"), None);
+    }
+
+    #[test]
+    fn test_family() {
+        assert_eq!(FileType::UpgGimbal.family(), FileFamily::Upg);
+        assert_eq!(FileType::UpgRcMcu.family(), FileFamily::Upg);
+        assert_eq!(FileType::UpgEsc.family(), FileFamily::Upg);
+        assert_eq!(FileType::Gzip.family(), FileFamily::Compressed);
+        assert_eq!(FileType::Json.family(), FileFamily::Text);
+        assert_eq!(FileType::GpsBin.family(), FileFamily::Gps);
+    }
+
+    #[test]
+    fn test_detect_bin_by_extension() {
+        let data = &[0xde, 0xad, 0xbe, 0xef, 0x00, 0xff, 0x80, 0x7f];
+        assert_eq!(detect_file_type(data, Some("GPS.BIN")), FileType::GpsBin);
+        assert_eq!(detect_file_type(data, Some("other.bin")), FileType::Unknown);
+    }
+
+    #[test]
+    fn test_magic_beats_extension() {
+        let gimbal = &[0x34, 0x12, 0xef, 0xbe, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(
+            detect_file_type(gimbal, Some("fcs.upg")),
+            FileType::UpgGimbal
+        );
+        let zip_data = b"PK\x03\x04some zip content here";
+        assert_eq!(
+            detect_file_type(zip_data, Some("config.json")),
+            FileType::Zip
+        );
+    }
+
+    #[test]
+    fn test_detect_xz() {
+        let xz_data = &[0xfd, b'7', b'z', b'X', b'Z', 0x00, 0x00, 0x04];
+        assert_eq!(detect_file_type(xz_data, None), FileType::Xz);
+    }
+
     #[test]
     fn test_detect_gzip() {
         let gzip_data = &[0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00];
@@ -181,6 +805,21 @@ mod tests {
         assert_eq!(detect_file_type(esc_data_17, None), FileType::UpgEsc);
     }
 
+    #[test]
+    fn test_detect_esc_rejects_zero_padding() {
+        // A padded calibration blob whose fifth byte happens to fall in the ESC ID range
+        let mut padded = vec![0x00, 0x00, 0x00, 0x00, 0x15];
+        padded.resize(1024, 0);
+        padded.extend_from_slice(b"CAL\xff");
+        assert_eq!(detect_file_type(&padded, None), FileType::Unknown);
+        // The filename can still vouch for it
+        assert_eq!(detect_file_type(&padded, Some("esc.upg")), FileType::UpgEsc);
+
+        let mut esc = vec![0x00, 0x00, 0x00, 0x00, 0x16, 0x00, 0x01, 0x00];
+        esc.extend((0..1024u32).map(|i| (i * 37 + 11) as u8));
+        assert_eq!(detect_file_type(&esc, None), FileType::UpgEsc);
+    }
+
     #[test]
     fn test_detect_esc_boundary() {
         let not_esc_low = &[0x00, 0x00, 0x00, 0x00, 0x13, 0x00, 0x00, 0x00];
@@ -258,13 +897,17 @@ mod tests {
         assert_eq!(file_type_name(&FileType::AutelContainer), "Autel Container");
         assert_eq!(file_type_name(&FileType::Zip), "ZIP Archive");
         assert_eq!(file_type_name(&FileType::Gzip), "Gzip Compressed");
+        assert_eq!(file_type_name(&FileType::Xz), "XZ Compressed");
         assert_eq!(file_type_name(&FileType::Json), "JSON");
         assert_eq!(file_type_name(&FileType::UpgGimbal), "UPG (Gimbal)");
         assert_eq!(
             file_type_name(&FileType::UpgFcs),
             "UPG (Flight Control System)"
         );
-        assert_eq!(file_type_name(&FileType::UpgBms), "UPG (Battery Management)");
+        assert_eq!(
+            file_type_name(&FileType::UpgBms),
+            "UPG (Battery Management)"
+        );
         assert_eq!(file_type_name(&FileType::UpgEsc), "UPG (ESC)");
         assert_eq!(file_type_name(&FileType::UpgRcMcu), "UPG (RC MCU)");
         assert_eq!(file_type_name(&FileType::GpsBin), "GPS Binary");
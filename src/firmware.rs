@@ -0,0 +1,135 @@
+use std::fmt;
+use std::path::Path;
+
+/// Words in a firmware file name that say what it is rather than which model it is for
+const GENERIC_WORDS: [&str; 6] = ["fw", "firmware", "update", "upgrade", "pkg", "package"];
+
+/// Model and version as encoded in a firmware file name, e.g. `EVO_FW_V1.5.8.bin`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FirmwareInfo {
+    /// Words before the version, joined by spaces (`EVO`, `EVO II Pro`)
+    pub model: String,
+    /// Dotted version without its `v` prefix (`1.5.8`)
+    pub version: String,
+}
+
+impl fmt::Display for FirmwareInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} v{}", self.model, self.version)
+    }
+}
+
+/// Pick the model and version out of a firmware file name
+/// The name is split on `_`, `-` and spaces; the version is the first word of dotted numbers,
+/// optionally prefixed with `v`, and the model is the words before it minus generic ones
+/// such as `FW`. Returns None unless both are found
+pub fn parse_firmware_name(name: &str) -> Option<FirmwareInfo> {
+    let name = Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(name);
+    // Only a non-numeric extension is dropped, so `EVO_V1.5.8` keeps its last component
+    let stem = match name.rsplit_once('.') {
+        Some((stem, ext)) if ext.bytes().any(|b| b.is_ascii_alphabetic()) => stem,
+        _ => name,
+    };
+
+    let words: Vec<&str> = stem
+        .split(['_', '-', ' '])
+        .filter(|w| !w.is_empty())
+        .collect();
+    let at = words.iter().position(|w| version_word(w).is_some())?;
+    let model: Vec<&str> = words[..at]
+        .iter()
+        .filter(|w| !GENERIC_WORDS.contains(&w.to_ascii_lowercase().as_str()))
+        .copied()
+        .collect();
+    if model.is_empty() {
+        return None;
+    }
+
+    Some(FirmwareInfo {
+        model: model.join(" "),
+        version: version_word(words[at])?.to_string(),
+    })
+}
+
+/// The version in a word like `V1.5.8` or `1.5`, without the prefix
+fn version_word(word: &str) -> Option<&str> {
+    let version = word
+        .strip_prefix(['v', 'V'])
+        .unwrap_or(word)
+        .trim_end_matches('.');
+    let parts: Vec<&str> = version.split('.').collect();
+    let numeric = parts
+        .iter()
+        .all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()));
+    (parts.len() >= 2 && numeric).then_some(version)
+}
+
+/// Whether two version strings name the same version, ignoring a `v` prefix, leading zeros
+/// and trailing `.0` components (`v1.5.8`, `01.05.08` and `1.5.8.0` all agree)
+pub fn versions_agree(a: &str, b: &str) -> bool {
+    match (version_numbers(a), version_numbers(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.trim().eq_ignore_ascii_case(b.trim()),
+    }
+}
+
+fn version_numbers(version: &str) -> Option<Vec<u64>> {
+    let version = version.trim();
+    let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+    let mut numbers = version
+        .split('.')
+        .map(|p| p.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    while numbers.len() > 1 && numbers.last() == Some(&0) {
+        numbers.pop();
+    }
+    Some(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(model: &str, version: &str) -> Option<FirmwareInfo> {
+        Some(FirmwareInfo {
+            model: model.to_string(),
+            version: version.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_parse_firmware_name() {
+        assert_eq!(
+            parse_firmware_name("EVO_FW_V1.5.8.bin"),
+            info("EVO", "1.5.8")
+        );
+        assert_eq!(
+            parse_firmware_name("downloads/EVO_II_Pro-firmware-v2.0.14.bin"),
+            info("EVO II Pro", "2.0.14")
+        );
+        assert_eq!(parse_firmware_name("EVO_V1.5.8"), info("EVO", "1.5.8"));
+        assert_eq!(
+            parse_firmware_name("EVO_FW_V1.5.8.bin")
+                .unwrap()
+                .to_string(),
+            "EVO v1.5.8"
+        );
+
+        assert_eq!(parse_firmware_name("FW_V1.5.8.bin"), None);
+        assert_eq!(parse_firmware_name("EVO_FW.bin"), None);
+        assert_eq!(parse_firmware_name("EVO_V1.bin"), None);
+        assert_eq!(parse_firmware_name("fw.bin"), None);
+    }
+
+    #[test]
+    fn test_versions_agree() {
+        assert!(versions_agree("1.5.8", "v1.5.8"));
+        assert!(versions_agree("01.05.08", "1.5.8.0"));
+        assert!(!versions_agree("1.5.8", "1.5.9"));
+        assert!(versions_agree("beta", "BETA"));
+        assert!(!versions_agree("1.5.8", "beta"));
+    }
+}
@@ -0,0 +1,207 @@
+use std::io::{self, Read};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+
+const FHCRC: u8 = 0x02;
+const FEXTRA: u8 = 0x04;
+const FNAME: u8 = 0x08;
+const FCOMMENT: u8 = 0x10;
+
+/// Fields of a gzip member header (RFC 1952)
+#[derive(Debug, Default, PartialEq)]
+pub struct GzipHeader {
+    /// Modification time as a Unix timestamp (0 if not recorded)
+    pub mtime: u32,
+    /// Original filename from the FNAME field
+    pub filename: Option<String>,
+    pub comment: Option<String>,
+}
+
+/// Read a NUL-terminated Latin-1 string, returning it and the position after the NUL
+fn read_cstr(data: &[u8], start: usize) -> Option<(String, usize)> {
+    let len = data.get(start..)?.iter().position(|&b| b == 0)?;
+    let s = data[start..start + len]
+        .iter()
+        .map(|&b| b as char)
+        .collect();
+    Some((s, start + len + 1))
+}
+
+/// Parse the header of the first gzip member
+/// Returns None if the magic is wrong or the header is cut short
+pub fn parse_gzip_header(data: &[u8]) -> Option<GzipHeader> {
+    if data.len() < 10 || data[0..2] != [0x1f, 0x8b] {
+        return None;
+    }
+
+    let flags = data[3];
+    let mtime = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let mut pos = 10;
+
+    if flags & FEXTRA != 0 {
+        let xlen = u16::from_le_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+        pos += 2 + xlen;
+    }
+
+    let mut filename = None;
+    if flags & FNAME != 0 {
+        let (name, next) = read_cstr(data, pos)?;
+        filename = Some(name);
+        pos = next;
+    }
+
+    let mut comment = None;
+    if flags & FCOMMENT != 0 {
+        let (text, next) = read_cstr(data, pos)?;
+        comment = Some(text);
+        pos = next;
+    }
+
+    if flags & FHCRC != 0 && data.len() < pos + 2 {
+        return None;
+    }
+
+    Some(GzipHeader {
+        mtime,
+        filename,
+        comment,
+    })
+}
+
+/// Pick an output name for a decompressed gzip member
+/// Uses the embedded FNAME if present, otherwise derives it from the parent name:
+/// `logs.tar.gz` → `logs.tar`, `fw.tgz` → `fw.tar`, anything else gets `.out` appended
+pub fn derive_decompressed_name(parent: &str, header: &GzipHeader) -> String {
+    // Only keep the final path component of an embedded name
+    if let Some(name) = header
+        .filename
+        .as_deref()
+        .and_then(|n| Path::new(n).file_name())
+        .and_then(|n| n.to_str())
+    {
+        if !name.is_empty() {
+            return name.to_string();
+        }
+    }
+
+    let lower = parent.to_ascii_lowercase();
+    if lower.ends_with(".tgz") {
+        format!("{}.tar", &parent[..parent.len() - 4])
+    } else if lower.ends_with(".gz") && parent.len() > 3 {
+        parent[..parent.len() - 3].to_string()
+    } else {
+        format!("{}.out", parent)
+    }
+}
+
+/// Most bytes a gzip stream is decompressed to, so a small gzip bomb can't exhaust memory
+pub const MAX_DECOMPRESSED_SIZE: u64 = 1 << 30;
+
+/// Decompress all members of a gzip stream, up to `MAX_DECOMPRESSED_SIZE` bytes
+pub fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    decompress_limited(data, MAX_DECOMPRESSED_SIZE).map(|(out, _)| out)
+}
+
+/// Decompress all members of a gzip stream, stopping after `limit` bytes
+/// Also returns whether the output was cut short at the limit
+pub fn decompress_limited(data: &[u8], limit: u64) -> io::Result<(Vec<u8>, bool)> {
+    // One byte past the limit tells a stream that fits exactly from one that doesn't
+    let mut decoder = MultiGzDecoder::new(data).take(limit.saturating_add(1));
+    let mut out = Vec::new();
+    decoder.read_to_end(&mut out)?;
+    let truncated = out.len() as u64 > limit;
+    out.truncate(limit as usize);
+    Ok((out, truncated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::{Compression, GzBuilder};
+    use std::io::Write;
+
+    fn gzip(data: &[u8], name: Option<&str>) -> Vec<u8> {
+        let builder = GzBuilder::new().mtime(1_600_000_000);
+        let builder = match name {
+            Some(n) => builder.filename(n),
+            None => builder,
+        };
+        let mut encoder = builder.write(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_parse_header_with_name() {
+        let data = gzip(b"hello", Some("inner.tar"));
+        let header = parse_gzip_header(&data).unwrap();
+        assert_eq!(header.filename.as_deref(), Some("inner.tar"));
+        assert_eq!(header.mtime, 1_600_000_000);
+    }
+
+    #[test]
+    fn test_parse_header_without_name() {
+        let data = gzip(b"hello", None);
+        let header = parse_gzip_header(&data).unwrap();
+        assert!(header.filename.is_none());
+    }
+
+    #[test]
+    fn test_parse_header_rejects_non_gzip() {
+        assert!(parse_gzip_header(b"not gzip data").is_none());
+        assert!(parse_gzip_header(&[0x1f, 0x8b, 0x08]).is_none());
+    }
+
+    #[test]
+    fn test_derive_name_from_fname() {
+        let header = GzipHeader {
+            filename: Some("build/rootfs.img".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(derive_decompressed_name("x.gz", &header), "rootfs.img");
+    }
+
+    #[test]
+    fn test_derive_name_strips_gz() {
+        let header = GzipHeader::default();
+        assert_eq!(derive_decompressed_name("logs.tar.gz", &header), "logs.tar");
+        assert_eq!(derive_decompressed_name("LOGS.TAR.GZ", &header), "LOGS.TAR");
+        assert_eq!(derive_decompressed_name("fw.tgz", &header), "fw.tar");
+    }
+
+    #[test]
+    fn test_derive_name_appends_out() {
+        let header = GzipHeader::default();
+        assert_eq!(derive_decompressed_name("payload", &header), "payload.out");
+        assert_eq!(derive_decompressed_name(".gz", &header), ".gz.out");
+    }
+
+    #[test]
+    fn test_decompress_roundtrip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"firmware payload").unwrap();
+        let data = encoder.finish().unwrap();
+        assert_eq!(decompress(&data).unwrap(), b"firmware payload");
+    }
+
+    #[test]
+    fn test_decompress_corrupt() {
+        assert!(decompress(&[0x1f, 0x8b, 0x08, 0x00, 0xff, 0xff]).is_err());
+    }
+
+    #[test]
+    fn test_decompress_limited() {
+        let data = gzip(&[0u8; 10_000], None);
+        assert_eq!(
+            decompress_limited(&data, 10_000).unwrap(),
+            (vec![0; 10_000], false)
+        );
+        assert_eq!(
+            decompress_limited(&data, 4096).unwrap(),
+            (vec![0; 4096], true)
+        );
+        assert_eq!(decompress(&data).unwrap().len(), 10_000);
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::file_types::{file_type_name, FileType};
+use crate::processor::{process_builtin, Context};
+
+/// A file reached by the traversal, as handed to a `TypeHandler`
+#[derive(Debug, Clone, Copy)]
+pub struct Node<'a> {
+    pub data: &'a [u8],
+    pub filename: Option<&'a str>,
+    pub file_type: FileType,
+    /// Nesting path (relative to the output root) the file lives in
+    pub rel_dir: &'a Path,
+    /// Nesting depth, 0 for the input itself
+    pub depth: usize,
+}
+
+impl Node<'_> {
+    /// Indentation for report lines about this file
+    pub fn indent(&self) -> String {
+        "  ".repeat(self.depth)
+    }
+
+    /// Where the file itself is written, if it has a name
+    pub fn output_path(&self) -> Option<PathBuf> {
+        self.filename.map(|name| self.rel_dir.join(name))
+    }
+}
+
+/// Processing for one file type, in place of the built-in processing
+/// A handler writes what it wants through `Context::emit` and may recurse into nested data
+/// with `process_node`; calling `BuiltinHandler` from it extends the built-in processing
+/// instead of replacing it
+pub trait TypeHandler: Send + Sync {
+    fn handle(&self, ctx: &mut Context, node: &Node) -> io::Result<()>;
+}
+
+/// The processing every type gets unless a handler is registered for it
+pub struct BuiltinHandler;
+
+impl TypeHandler for BuiltinHandler {
+    fn handle(&self, ctx: &mut Context, node: &Node) -> io::Result<()> {
+        process_builtin(ctx, node)
+    }
+}
+
+/// Handlers registered per file type, consulted before the built-in processing
+/// Lets library users add or override handling (say, their own GPS parser) without editing
+/// the traversal
+#[derive(Clone, Default)]
+pub struct HandlerRegistry {
+    handlers: BTreeMap<FileType, Arc<dyn TypeHandler>>,
+}
+
+impl HandlerRegistry {
+    /// Handle `file_type` with `handler`, replacing any handler registered for it before
+    pub fn register(&mut self, file_type: FileType, handler: impl TypeHandler + 'static) {
+        self.handlers.insert(file_type, Arc::new(handler));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+
+    /// The handler registered for `file_type`, if any
+    pub fn get(&self, file_type: FileType) -> Option<&dyn TypeHandler> {
+        self.handlers.get(&file_type).map(|h| h.as_ref())
+    }
+}
+
+impl fmt::Debug for HandlerRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set()
+            .entries(self.handlers.keys().map(file_type_name))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::ProcessOptions;
+    use crate::processor::process_file_with;
+
+    /// Writes GPS images under a `.gps` name and nothing else
+    struct RenameGps;
+
+    impl TypeHandler for RenameGps {
+        fn handle(&self, ctx: &mut Context, node: &Node) -> io::Result<()> {
+            let path = node.output_path().unwrap().with_extension("gps");
+            ctx.emit(path, node.file_type, node.data)
+        }
+    }
+
+    /// Runs the built-in processing, then writes an extra note file
+    struct AnnotateText;
+
+    impl TypeHandler for AnnotateText {
+        fn handle(&self, ctx: &mut Context, node: &Node) -> io::Result<()> {
+            BuiltinHandler.handle(ctx, node)?;
+            let note = node.rel_dir.join("note.txt");
+            ctx.emit(note, FileType::Text, b"seen")
+        }
+    }
+
+    fn paths(data: &[u8], name: &str, options: &ProcessOptions) -> Vec<String> {
+        let mut paths = Vec::new();
+        process_file_with(data, Some(name), options, &mut |f| {
+            paths.push(f.path.to_string_lossy().into_owned());
+            Ok(())
+        })
+        .unwrap();
+        paths
+    }
+
+    #[test]
+    fn test_registered_handler_replaces_builtin() {
+        let mut options = ProcessOptions::default();
+        assert_eq!(paths(b"\x01\x02", "nav.bin", &options), ["nav.bin"]);
+
+        options.handlers.register(FileType::Unknown, RenameGps);
+        assert_eq!(paths(b"\x01\x02", "nav.bin", &options), ["nav.gps"]);
+        assert_eq!(format!("{:?}", options.handlers), "{\"Unknown\"}");
+    }
+
+    #[test]
+    fn test_handler_can_extend_builtin() {
+        let mut options = ProcessOptions::default();
+        options.handlers.register(FileType::Text, AnnotateText);
+        assert_eq!(
+            paths(b"hello", "readme.txt", &options),
+            ["readme.txt", "note.txt"]
+        );
+    }
+}
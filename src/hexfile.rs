@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+
+/// Lines inspected when sniffing for a record format
+const SNIFF_LINES: usize = 8;
+
+/// Largest address span a decoded image may cover; guards against sparse
+/// records making us allocate gigabytes of padding
+const MAX_IMAGE_SPAN: u64 = 256 * 1024 * 1024;
+
+/// A binary image rebuilt from Intel HEX or S-record text
+#[derive(Debug, PartialEq)]
+pub struct DecodedImage {
+    /// Load address of the first byte of `data`
+    pub base_address: u32,
+    /// Record data laid out by address, gaps filled with 0xff
+    pub data: Vec<u8>,
+    /// 1-based line numbers of records whose checksum didn't match (their data is still used)
+    pub checksum_errors: Vec<usize>,
+}
+
+/// The first few non-empty lines, if the data is ASCII text
+fn sniff_lines(data: &[u8]) -> Option<Vec<&str>> {
+    let text = std::str::from_utf8(data.get(..data.len().min(4096))?).ok()?;
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .take(SNIFF_LINES)
+        .collect();
+    // The last line of a cut-off prefix may be partial
+    let complete = if data.len() > 4096 && lines.len() > 1 {
+        &lines[..lines.len() - 1]
+    } else {
+        &lines[..]
+    };
+    (!complete.is_empty()).then(|| complete.to_vec())
+}
+
+fn is_hex_digits(s: &str) -> bool {
+    !s.is_empty() && s.len().is_multiple_of(2) && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Returns true if every leading line looks like an Intel HEX record (`:` + hex pairs)
+pub fn is_intel_hex(data: &[u8]) -> bool {
+    sniff_lines(data).is_some_and(|lines| {
+        lines.iter().all(|l| {
+            l.strip_prefix(':')
+                .is_some_and(|r| r.len() >= 10 && is_hex_digits(r))
+        })
+    })
+}
+
+/// Returns true if every leading line looks like a Motorola S-record (`S0`-`S9` + hex pairs)
+pub fn is_srecord(data: &[u8]) -> bool {
+    sniff_lines(data).is_some_and(|lines| {
+        lines.iter().all(|l| {
+            let b = l.as_bytes();
+            b.len() >= 10 && b[0] == b'S' && b[1].is_ascii_digit() && is_hex_digits(&l[2..])
+        })
+    })
+}
+
+fn decode_hex_pairs(s: &str) -> Option<Vec<u8>> {
+    if !is_hex_digits(s) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Lay out `(address, bytes)` chunks into one contiguous image
+fn build_image(
+    chunks: BTreeMap<u64, Vec<u8>>,
+    checksum_errors: Vec<usize>,
+) -> Result<DecodedImage, String> {
+    let start = match chunks.keys().next() {
+        Some(&s) => s,
+        None => return Err("no data records".to_string()),
+    };
+    let end = chunks
+        .iter()
+        .map(|(addr, bytes)| addr + bytes.len() as u64)
+        .max()
+        .unwrap_or(start);
+    if end - start > MAX_IMAGE_SPAN {
+        return Err(format!(
+            "records span 0x{:x}..0x{:x}, too sparse to rebuild",
+            start, end
+        ));
+    }
+
+    let mut data = vec![0xff; (end - start) as usize];
+    for (addr, bytes) in chunks {
+        let offset = (addr - start) as usize;
+        data[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+
+    Ok(DecodedImage {
+        base_address: start as u32,
+        data,
+        checksum_errors,
+    })
+}
+
+/// Rebuild the binary image described by Intel HEX records
+/// Handles data (00), end of file (01) and extended segment/linear address (02/04) records
+pub fn decode_intel_hex(data: &[u8]) -> Result<DecodedImage, String> {
+    let text = std::str::from_utf8(data).map_err(|e| e.to_string())?;
+    let mut chunks = BTreeMap::new();
+    let mut checksum_errors = Vec::new();
+    let mut upper: u64 = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bytes = line
+            .strip_prefix(':')
+            .and_then(decode_hex_pairs)
+            .ok_or_else(|| format!("line {}: not an Intel HEX record", line_no))?;
+        if bytes.len() < 5 || bytes.len() != 5 + bytes[0] as usize {
+            return Err(format!("line {}: record length mismatch", line_no));
+        }
+        if bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)) != 0 {
+            checksum_errors.push(line_no);
+        }
+
+        let address = u16::from_be_bytes([bytes[1], bytes[2]]) as u64;
+        let payload = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            0x00 => {
+                chunks.insert(upper + address, payload.to_vec());
+            }
+            0x01 => break,
+            0x02 if payload.len() == 2 => {
+                upper = (u16::from_be_bytes([payload[0], payload[1]]) as u64) << 4;
+            }
+            0x04 if payload.len() == 2 => {
+                upper = (u16::from_be_bytes([payload[0], payload[1]]) as u64) << 16;
+            }
+            // Start address records don't affect the image
+            0x03 | 0x05 => {}
+            other => {
+                return Err(format!(
+                    "line {}: unknown record type {:02x}",
+                    line_no, other
+                ))
+            }
+        }
+    }
+
+    build_image(chunks, checksum_errors)
+}
+
+/// Rebuild the binary image described by Motorola S-records
+/// S1/S2/S3 carry data with 16/24/32-bit addresses; header, count and start records are skipped
+pub fn decode_srecord(data: &[u8]) -> Result<DecodedImage, String> {
+    let text = std::str::from_utf8(data).map_err(|e| e.to_string())?;
+    let mut chunks = BTreeMap::new();
+    let mut checksum_errors = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (record_type, bytes) = match line.as_bytes() {
+            [b'S', t, ..] if t.is_ascii_digit() => (*t - b'0', decode_hex_pairs(&line[2..])),
+            _ => (0, None),
+        };
+        let bytes = bytes.ok_or_else(|| format!("line {}: not an S-record", line_no))?;
+        if bytes.is_empty() || bytes.len() != 1 + bytes[0] as usize {
+            return Err(format!("line {}: record length mismatch", line_no));
+        }
+        if bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)) != 0xff {
+            checksum_errors.push(line_no);
+        }
+
+        let address_len = match record_type {
+            1 => 2,
+            2 => 3,
+            3 => 4,
+            _ => continue,
+        };
+        if bytes.len() < 2 + address_len {
+            return Err(format!("line {}: record too short", line_no));
+        }
+        let address = bytes[1..1 + address_len]
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 8) | b as u64);
+        chunks.insert(address, bytes[1 + address_len..bytes.len() - 1].to_vec());
+    }
+
+    build_image(chunks, checksum_errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTEL_HEX: &str = "\
+:020000040800F2
+:10000000000102030405060708090A0B0C0D0E0F78
+:04001000DEADBEEFB4
+:00000001FF
+";
+
+    const SRECORD: &str = "\
+S00600004844521B
+S1070100DEADBEEFBF
+S1050108AA55F2
+S9030000FC
+";
+
+    #[test]
+    fn test_detect_record_formats() {
+        assert!(is_intel_hex(INTEL_HEX.as_bytes()));
+        assert!(!is_srecord(INTEL_HEX.as_bytes()));
+        assert!(is_srecord(SRECORD.as_bytes()));
+        assert!(!is_intel_hex(SRECORD.as_bytes()));
+        assert!(!is_intel_hex(b":not hex at all"));
+        assert!(!is_srecord(b"Some text that starts with S"));
+    }
+
+    #[test]
+    fn test_decode_intel_hex() {
+        let image = decode_intel_hex(INTEL_HEX.as_bytes()).unwrap();
+        assert_eq!(image.base_address, 0x0800_0000);
+        assert_eq!(image.data.len(), 0x14);
+        assert_eq!(&image.data[..4], &[0, 1, 2, 3]);
+        assert_eq!(&image.data[0x10..], &[0xde, 0xad, 0xbe, 0xef]);
+        assert!(image.checksum_errors.is_empty());
+    }
+
+    #[test]
+    fn test_decode_srecord_fills_gaps() {
+        let image = decode_srecord(SRECORD.as_bytes()).unwrap();
+        assert_eq!(image.base_address, 0x0100);
+        assert_eq!(
+            image.data,
+            [0xde, 0xad, 0xbe, 0xef, 0xff, 0xff, 0xff, 0xff, 0xaa, 0x55]
+        );
+        assert!(image.checksum_errors.is_empty());
+    }
+
+    #[test]
+    fn test_bad_checksums_are_reported() {
+        let corrupted = INTEL_HEX.replace("DEADBEEFB4", "DEADBEEFB5");
+        let image = decode_intel_hex(corrupted.as_bytes()).unwrap();
+        assert_eq!(image.checksum_errors, [3]);
+
+        let corrupted = SRECORD.replace("AA55F2", "AA55F3");
+        let image = decode_srecord(corrupted.as_bytes()).unwrap();
+        assert_eq!(image.checksum_errors, [3]);
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed() {
+        assert!(decode_intel_hex(b":0400000001\n").is_err());
+        assert!(decode_intel_hex(b":00000001FF\n").is_err());
+        assert!(decode_srecord(b"S1zz\n").is_err());
+    }
+}
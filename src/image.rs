@@ -0,0 +1,90 @@
+/// PNG file signature
+pub const PNG_MAGIC: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+/// JPEG start-of-image marker followed by the first segment's marker prefix
+pub const JPEG_MAGIC: [u8; 3] = [0xff, 0xd8, 0xff];
+
+/// Width and height from a PNG's IHDR chunk, which must come first
+pub fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.get(..8)? != PNG_MAGIC || data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+    Some((width, height))
+}
+
+/// Width and height from the first start-of-frame segment of a JPEG
+/// Walks the segment list from the start, so a truncated file may still yield dimensions
+pub fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.get(..3)? != JPEG_MAGIC {
+        return None;
+    }
+
+    let mut pos = 2;
+    loop {
+        // Markers may be preceded by any number of 0xff fill bytes
+        while *data.get(pos)? == 0xff && *data.get(pos + 1)? == 0xff {
+            pos += 1;
+        }
+        if *data.get(pos)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+
+        // SOF0..SOF15, except DHT (c4), JPG (c8) and DAC (cc)
+        if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+            let height = u16::from_be_bytes([*data.get(pos + 5)?, *data.get(pos + 6)?]);
+            let width = u16::from_be_bytes([*data.get(pos + 7)?, *data.get(pos + 8)?]);
+            return Some((width as u32, height as u32));
+        }
+        // Start of scan: entropy-coded data follows, no frame header was seen
+        if marker == 0xda || len < 2 {
+            return None;
+        }
+        pos += 2 + len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = PNG_MAGIC.to_vec();
+        data.extend_from_slice(&13u32.to_be_bytes());
+        data.extend_from_slice(b"IHDR");
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[8, 6, 0, 0, 0]);
+        data
+    }
+
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let mut data = vec![0xff, 0xd8];
+        // APP0 segment to skip over
+        data.extend_from_slice(&[0xff, 0xe0, 0x00, 0x06, b'J', b'F', b'I', b'F']);
+        data.extend_from_slice(&[0xff, 0xc0, 0x00, 0x0b, 0x08]);
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&[0x01, 0x01, 0x11, 0x00]);
+        data.extend_from_slice(&[0xff, 0xd9]);
+        data
+    }
+
+    #[test]
+    fn test_png_dimensions() {
+        assert_eq!(png_dimensions(&png(256, 128)), Some((256, 128)));
+        assert_eq!(png_dimensions(&png(256, 128)[..20]), None);
+        assert_eq!(png_dimensions(b"not a png at all, really"), None);
+    }
+
+    #[test]
+    fn test_jpeg_dimensions() {
+        assert_eq!(jpeg_dimensions(&jpeg(640, 480)), Some((640, 480)));
+        // Cut off before the frame header
+        assert_eq!(jpeg_dimensions(&jpeg(640, 480)[..10]), None);
+        assert_eq!(jpeg_dimensions(&[0xff, 0xd8, 0xff, 0xda, 0x00, 0x02]), None);
+    }
+}
@@ -0,0 +1,448 @@
+use std::io::{Cursor, Read};
+use zip::read::ZipArchive;
+
+use crate::cpio;
+use crate::file_entry::FileEntry;
+use crate::file_types::{detect_file_type, FileType};
+use crate::gzip::{self, derive_decompressed_name, parse_gzip_header};
+use crate::hexfile;
+use crate::manifest::{detect_manifest, parse_manifest};
+use crate::parser::parse_file_entries;
+use crate::tar;
+use crate::zip_utils::{check_central_directory, find_eocd_with, scan_local_headers, EocdError};
+
+/// Result of checking a single entry in the tree
+#[derive(Debug)]
+pub struct EntryCheck {
+    pub path: String,
+    pub problems: Vec<String>,
+}
+
+impl EntryCheck {
+    pub fn passed(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Integrity checks collected over a whole (recursive) firmware tree
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    pub checks: Vec<EntryCheck>,
+}
+
+impl IntegrityReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.passed())
+    }
+
+    pub fn failures(&self) -> usize {
+        self.checks.iter().filter(|c| !c.passed()).count()
+    }
+}
+
+/// Returns true if the entry's content_meta is a CRC32 of its content (either endianness)
+pub fn meta_matches_crc32(entry: &FileEntry) -> bool {
+    match entry.content_meta {
+        Some(meta) => {
+            let crc = crc32fast::hash(entry.content);
+            u32::from_be_bytes(*meta) == crc || u32::from_le_bytes(*meta) == crc
+        }
+        None => false,
+    }
+}
+
+/// Returns true if the declared content length exceeds the available data
+pub fn is_truncated(entry: &FileEntry) -> bool {
+    entry.content.len() < entry.content_length
+}
+
+/// For an entry holding a nested container, already parsed into `nested`, the bytes the
+/// nested entries span versus the outer declared content length, if they disagree
+/// Either the outer or an inner length field is wrong, or the format has structure the
+/// parser doesn't model
+pub fn nested_length_mismatch(nested: &[FileEntry], declared: usize) -> Option<(usize, usize)> {
+    let spanned = nested.last()?.declared_end();
+    (spanned != declared).then_some((spanned, declared))
+}
+
+/// Check a buffer and everything nested inside it
+pub fn verify_data(data: &[u8], filename: Option<&str>) -> IntegrityReport {
+    verify_data_with(data, filename, false)
+}
+
+/// Like `verify_data`, but with `full_eocd_scan` each ZIP's EOCD record is searched for
+/// through the whole buffer rather than only the trailing comment window
+pub fn verify_data_with(
+    data: &[u8],
+    filename: Option<&str>,
+    full_eocd_scan: bool,
+) -> IntegrityReport {
+    let mut report = IntegrityReport::default();
+    verify_into(
+        data,
+        filename.unwrap_or("<input>"),
+        full_eocd_scan,
+        &mut report,
+    );
+    report
+}
+
+fn verify_into(data: &[u8], path: &str, full_scan: bool, report: &mut IntegrityReport) {
+    let name = path.rsplit('/').next();
+    let file_type = detect_file_type(data, name);
+    match file_type {
+        FileType::AutelContainer => verify_container(data, path, full_scan, report),
+        FileType::Zip => verify_zip(data, path, full_scan, report),
+        FileType::Tar => verify_tar(data, path, full_scan, report),
+        FileType::Cpio => verify_cpio(data, path, full_scan, report),
+        FileType::IntelHex | FileType::SRecord => {
+            let decoded = if file_type == FileType::IntelHex {
+                hexfile::decode_intel_hex(data)
+            } else {
+                hexfile::decode_srecord(data)
+            };
+            let problems = match decoded {
+                Ok(image) if image.checksum_errors.is_empty() => Vec::new(),
+                Ok(image) => vec![format!(
+                    "record checksum mismatch on lines {:?}",
+                    image.checksum_errors
+                )],
+                Err(e) => vec![format!("failed to decode records: {}", e)],
+            };
+            report.checks.push(EntryCheck {
+                path: path.to_string(),
+                problems,
+            });
+        }
+        FileType::Gzip => match gzip::decompress_limited(data, gzip::MAX_DECOMPRESSED_SIZE) {
+            Ok((decompressed, truncated)) => {
+                if truncated {
+                    report.checks.push(EntryCheck {
+                        path: path.to_string(),
+                        problems: vec![format!(
+                            "gzip output exceeds {} bytes, truncated",
+                            gzip::MAX_DECOMPRESSED_SIZE
+                        )],
+                    });
+                }
+                let header = parse_gzip_header(data).unwrap_or_default();
+                let name = derive_decompressed_name(name.unwrap_or("gzip"), &header);
+                verify_into(
+                    &decompressed,
+                    &format!("{}/{}", path, name),
+                    full_scan,
+                    report,
+                );
+            }
+            Err(e) => report.checks.push(EntryCheck {
+                path: path.to_string(),
+                problems: vec![format!("failed to decompress gzip: {}", e)],
+            }),
+        },
+        _ => report.checks.push(EntryCheck {
+            path: path.to_string(),
+            problems: Vec::new(),
+        }),
+    }
+}
+
+fn verify_container(data: &[u8], path: &str, full_scan: bool, report: &mut IntegrityReport) {
+    verify_entries(&parse_file_entries(data), path, full_scan, report);
+}
+
+fn verify_entries(
+    entries: &[FileEntry],
+    path: &str,
+    full_scan: bool,
+    report: &mut IntegrityReport,
+) {
+    if entries.is_empty() {
+        report.checks.push(EntryCheck {
+            path: path.to_string(),
+            problems: vec!["no file entries found in container".to_string()],
+        });
+        return;
+    }
+
+    // Only treat content_meta as a checksum if it matches for at least one sibling
+    let meta_is_crc32 = entries.iter().any(meta_matches_crc32);
+    let manifest = detect_manifest(entries).and_then(|m| parse_manifest(m.content));
+    if let Some(manifest) = &manifest {
+        let missing = manifest.missing(entries);
+        if !missing.is_empty() {
+            report.checks.push(EntryCheck {
+                path: path.to_string(),
+                problems: missing
+                    .iter()
+                    .map(|name| format!("manifest declares {} but no entry has that name", name))
+                    .collect(),
+            });
+        }
+    }
+
+    for entry in entries {
+        let entry_path = format!(
+            "{}/{}",
+            path,
+            entry.filename.as_deref().unwrap_or("<unknown>")
+        );
+        let mut problems = Vec::new();
+
+        if is_truncated(entry) {
+            problems.push(format!(
+                "truncated: declared {} bytes, only {} available",
+                entry.content_length,
+                entry.content.len()
+            ));
+        }
+        if meta_is_crc32 && !meta_matches_crc32(entry) {
+            problems.push(format!(
+                "checksum mismatch: meta {:02x?}, crc32 {:08x}",
+                entry.content_meta.unwrap_or(&[0; 4]),
+                crc32fast::hash(entry.content)
+            ));
+        }
+        if let Some(manifest) = &manifest {
+            problems.extend(manifest.check_entry(entry).unwrap_or_default());
+        }
+        // Parsed once here, both for the length check and to verify its entries
+        let nested = (entry.detected_type() == FileType::AutelContainer)
+            .then(|| parse_file_entries(entry.content));
+        if let Some((spanned, declared)) = nested
+            .as_deref()
+            .and_then(|nested| nested_length_mismatch(nested, entry.content_length))
+        {
+            problems.push(format!(
+                "nested/declared length mismatch: nested entries span {} bytes, entry declares {}",
+                spanned, declared
+            ));
+        }
+
+        if problems.is_empty() {
+            match &nested {
+                Some(nested) => verify_entries(nested, &entry_path, full_scan, report),
+                None => verify_into(entry.content, &entry_path, full_scan, report),
+            }
+        } else {
+            report.checks.push(EntryCheck {
+                path: entry_path,
+                problems,
+            });
+        }
+    }
+}
+
+fn verify_tar(data: &[u8], path: &str, full_scan: bool, report: &mut IntegrityReport) {
+    for entry in tar::list_entries(data) {
+        let member_path = format!("{}/{}", path, entry.name);
+        let contents = tar::entry_data(data, &entry);
+        if contents.len() < entry.size {
+            report.checks.push(EntryCheck {
+                path: member_path,
+                problems: vec![format!(
+                    "truncated: declared {} bytes, only {} available",
+                    entry.size,
+                    contents.len()
+                )],
+            });
+        } else {
+            verify_into(contents, &member_path, full_scan, report);
+        }
+    }
+}
+
+fn verify_cpio(data: &[u8], path: &str, full_scan: bool, report: &mut IntegrityReport) {
+    for entry in cpio::list_entries(data).iter().filter(|e| e.is_regular()) {
+        let member_path = format!("{}/{}", path, entry.name);
+        let contents = cpio::entry_data(data, entry);
+        if contents.len() < entry.size {
+            report.checks.push(EntryCheck {
+                path: member_path,
+                problems: vec![format!(
+                    "truncated: declared {} bytes, only {} available",
+                    entry.size,
+                    contents.len()
+                )],
+            });
+        } else {
+            verify_into(contents, &member_path, full_scan, report);
+        }
+    }
+}
+
+fn verify_zip(data: &[u8], path: &str, full_scan: bool, report: &mut IntegrityReport) {
+    let fail = |report: &mut IntegrityReport, problem: String| {
+        report.checks.push(EntryCheck {
+            path: path.to_string(),
+            problems: vec![problem],
+        });
+    };
+
+    let zip_slice = match find_eocd_with(data, full_scan) {
+        Ok(eocd) => &data[..eocd.total_size],
+        Err(e @ EocdError::CommentOverrunsBuffer { .. }) => {
+            return fail(report, e.to_string());
+        }
+        Err(e) => {
+            let local_headers = scan_local_headers(data).len();
+            let problem = if local_headers > 0 {
+                format!(
+                    "ZIP appears truncated ({}), {} local entries scanned",
+                    e, local_headers
+                )
+            } else {
+                format!("ZIP is unreadable: {}", e)
+            };
+            return fail(report, problem);
+        }
+    };
+    if let Err(problem) = check_central_directory(zip_slice) {
+        return fail(report, format!("suspicious central directory: {}", problem));
+    }
+    let mut archive = match ZipArchive::new(Cursor::new(zip_slice)) {
+        Ok(a) => a,
+        Err(e) => return fail(report, format!("failed to open ZIP: {}", e)),
+    };
+
+    for i in 0..archive.len() {
+        // An encrypted member can't be checked, but that doesn't make it corrupt
+        if let Some(name) = archive
+            .by_index_raw(i)
+            .ok()
+            .filter(|raw| raw.encrypted())
+            .map(|raw| raw.name().to_string())
+        {
+            report.checks.push(EntryCheck {
+                path: format!("{}/{}", path, name),
+                problems: vec!["encrypted, cannot check".to_string()],
+            });
+            continue;
+        }
+        let mut file = match archive.by_index(i) {
+            Ok(f) => f,
+            Err(e) => {
+                fail(report, format!("failed to read ZIP member {}: {}", i, e));
+                continue;
+            }
+        };
+        let member_path = format!("{}/{}", path, file.name());
+        if file.name().ends_with('/') {
+            continue;
+        }
+
+        let mut contents = Vec::new();
+        match file.read_to_end(&mut contents) {
+            Ok(_) => verify_into(&contents, &member_path, full_scan, report),
+            Err(e) => report.checks.push(EntryCheck {
+                path: member_path,
+                problems: vec![format!("read error: {}", e)],
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::build_entry;
+
+    #[test]
+    fn test_verify_clean_container() {
+        let content = b"hello world";
+        let crc = crc32fast::hash(content).to_be_bytes();
+        let buffer = build_entry("a.txt", content, crc, content.len() as u32);
+
+        let report = verify_data(&buffer, Some("fw.bin"));
+        assert!(report.passed());
+        assert_eq!(report.checks.len(), 1);
+        assert_eq!(report.checks[0].path, "fw.bin/a.txt");
+    }
+
+    #[test]
+    fn test_verify_checksum_mismatch() {
+        let good = b"hello world";
+        let mut buffer = build_entry(
+            "a.txt",
+            good,
+            crc32fast::hash(good).to_le_bytes(),
+            good.len() as u32,
+        );
+        buffer.extend_from_slice(&build_entry("b.txt", b"other", [1, 2, 3, 4], 5));
+
+        let report = verify_data(&buffer, None);
+        assert!(!report.passed());
+        assert_eq!(report.failures(), 1);
+        assert!(report.checks[1].problems[0].contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn test_verify_meta_not_checksum_is_ignored() {
+        let buffer = build_entry("a.txt", b"data", [0x33, 0xa8, 0x3b, 0x1f], 4);
+        assert!(verify_data(&buffer, None).passed());
+    }
+
+    #[test]
+    fn test_verify_truncated_entry() {
+        let buffer = build_entry("a.bin", b"short", [0; 4], 100);
+        let report = verify_data(&buffer, None);
+        assert!(!report.passed());
+        assert!(report.checks[0].problems[0].starts_with("truncated"));
+    }
+
+    #[test]
+    fn test_nested_length_mismatch() {
+        let inner = build_entry("a.txt", b"hello", [0; 4], 5);
+        let exact = build_entry("inner.bin", &inner, [0; 4], inner.len() as u32);
+        assert!(verify_data(&exact, None).passed());
+
+        // Outer length covers padding the nested container doesn't account for
+        let mut padded = inner.clone();
+        padded.extend_from_slice(&[0; 8]);
+        let buffer = build_entry("inner.bin", &padded, [0; 4], padded.len() as u32);
+        let entries = parse_file_entries(&buffer);
+        assert_eq!(
+            nested_length_mismatch(
+                &parse_file_entries(entries[0].content),
+                entries[0].content_length
+            ),
+            Some((inner.len(), padded.len()))
+        );
+        let report = verify_data(&buffer, None);
+        assert!(report.checks[0].problems[0].contains("nested/declared length mismatch"));
+    }
+
+    #[test]
+    fn test_verify_against_manifest() {
+        let manifest = br#"{"version": "2.0", "files": [{"name": "a.bin", "size": 3}]}"#;
+        let mut buffer = build_entry("manifest.json", manifest, [0; 4], manifest.len() as u32);
+        buffer.extend_from_slice(&build_entry("a.bin", b"abc", [0; 4], 3));
+        assert!(verify_data(&buffer, None).passed());
+
+        let mut buffer = build_entry("manifest.json", manifest, [0; 4], manifest.len() as u32);
+        buffer.extend_from_slice(&build_entry("a.bin", b"abcd", [0; 4], 4));
+        let report = verify_data(&buffer, None);
+        assert_eq!(report.failures(), 1);
+        assert!(report.checks[1].problems[0].contains("declares 3 bytes"));
+    }
+
+    #[test]
+    fn test_verify_hex_record_checksum() {
+        let good = b":04000000DEADBEEFC4\n:00000001FF\n";
+        let buffer = build_entry("mcu.hex", good, [0; 4], good.len() as u32);
+        assert!(verify_data(&buffer, None).passed());
+
+        let bad = b":04000000DEADBEEFC5\n:00000001FF\n";
+        let buffer = build_entry("mcu.hex", bad, [0; 4], bad.len() as u32);
+        let report = verify_data(&buffer, None);
+        assert!(!report.passed());
+        assert!(report.checks[0].problems[0].contains("lines [1]"));
+    }
+
+    #[test]
+    fn test_verify_broken_zip() {
+        let data = b"PK\x03\x04broken zip without end record";
+        let report = verify_data(data, Some("broken.zip"));
+        assert!(!report.passed());
+        assert!(report.checks[0].problems[0].contains("truncated"));
+    }
+}
@@ -0,0 +1,43 @@
+//! Parsing and extraction of Autel firmware containers
+//!
+//! The binary in `main.rs` is a thin CLI over these modules; library users can
+//! drive the same traversal with [`processor::process_file_with`] to receive
+//! each extracted artifact instead of having it written to disk.
+
+pub mod analysis;
+pub mod checksums;
+pub mod components;
+pub mod config;
+pub mod console;
+pub mod cpio;
+pub mod dedup_store;
+pub mod diagnostics;
+pub mod display;
+pub mod dump;
+pub mod extract;
+pub mod file_entry;
+pub mod file_types;
+pub mod firmware;
+pub mod gzip;
+pub mod handlers;
+pub mod hash;
+pub mod hexfile;
+pub mod image;
+pub mod integrity;
+pub mod manifest;
+pub mod naming;
+pub mod offset_map;
+pub mod options;
+pub mod out_archive;
+pub mod pack;
+pub mod parser;
+pub mod parts;
+pub mod processor;
+pub mod signatures;
+pub mod squashfs;
+pub mod strings;
+pub mod tar;
+#[cfg(test)]
+pub(crate) mod test_support;
+pub mod timestamp;
+pub mod zip_utils;
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::str::FromStr;
+
+use firmparse::console::REPORT_TARGET;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::format::{self, FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::FmtContext;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+/// Level logged when `--log` isn't given: warnings and errors, as the tool always printed
+pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::WARN;
+
+/// Prints warnings and errors as plain `Warning: ...` / `Error: ...` lines, and anything
+/// more verbose with its level, spans and fields
+struct ConsoleFormat {
+    verbose: format::Format<format::Full, ()>,
+}
+
+/// Collects just the message of an event
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = value.to_string();
+        }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for ConsoleFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let label = match *event.metadata().level() {
+            Level::ERROR => "Error",
+            Level::WARN => "Warning",
+            _ => return self.verbose.format_event(ctx, writer, event),
+        };
+        let mut message = Message::default();
+        event.record(&mut message);
+        writeln!(writer, "{}: {}", label, message.0)
+    }
+}
+
+/// Install the console subscriber, logging to stderr at `level` and below
+/// Events repeating a report line are left out, as the report already shows them
+pub fn init(level: LevelFilter) {
+    let filter = Targets::new()
+        .with_target(REPORT_TARGET, LevelFilter::OFF)
+        .with_default(level);
+    let console = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .event_format(ConsoleFormat {
+            verbose: format::format().without_time(),
+        });
+    tracing_subscriber::registry()
+        .with(console.with_filter(filter))
+        .init();
+}
+
+/// Take `--log <level>` / `--log=<level>` out of `args`, returning the level asked for
+pub fn take_log_level(args: &mut Vec<String>) -> Result<Option<LevelFilter>, String> {
+    let Some(pos) = args
+        .iter()
+        .position(|a| a == "--log" || a.starts_with("--log="))
+    else {
+        return Ok(None);
+    };
+    let value = match args.remove(pos).strip_prefix("--log=") {
+        Some(value) => value.to_string(),
+        None if pos < args.len() => args.remove(pos),
+        None => return Err("--log requires a value".to_string()),
+    };
+    LevelFilter::from_str(&value)
+        .map(Some)
+        .map_err(|_| format!("invalid --log level: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_take_log_level() {
+        let mut a = args(&["prog", "fw.bin", "--log", "debug", "out"]);
+        assert_eq!(take_log_level(&mut a), Ok(Some(LevelFilter::DEBUG)));
+        assert_eq!(a, args(&["prog", "fw.bin", "out"]));
+
+        let mut a = args(&["prog", "--log=trace", "fw.bin"]);
+        assert_eq!(take_log_level(&mut a), Ok(Some(LevelFilter::TRACE)));
+        assert_eq!(a, args(&["prog", "fw.bin"]));
+
+        assert_eq!(take_log_level(&mut args(&["prog", "fw.bin"])), Ok(None));
+        assert!(take_log_level(&mut args(&["prog", "--log", "loud"])).is_err());
+        assert!(take_log_level(&mut args(&["prog", "--log"])).is_err());
+    }
+}
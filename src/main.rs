@@ -1,72 +1,1250 @@
-mod display;
-mod file_entry;
-mod file_types;
-mod parser;
-mod processor;
-mod zip_utils;
+mod cli;
+mod explore;
+mod logging;
+// Shared with the library's tests; the binary's tests don't build every fixture
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "test_support.rs"]
+mod test_support;
 
+use std::collections::HashSet;
 use std::env;
-use std::fs::File;
-use std::io::{self, Read};
-use std::path::Path;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use cli::{parse_number, Args};
+use firmparse::analysis::{shannon_entropy, HIGH_ENTROPY};
+use firmparse::checksums::{check_checksums, parse_checksum_file, ChecksumOutcome};
+use firmparse::components::{component_matrix, leaf_digests, ComponentStatus};
+use firmparse::config::Config;
+use firmparse::console::set_report_to_stderr;
+use firmparse::dedup_store::DedupStore;
+use firmparse::display::{
+    print_hexdump_preview_indented, render_leaf_table, render_table, render_tree,
+    render_type_fingerprint, render_type_table, tree_type_totals, write_metadata_csv, OutputFormat,
+    TreeNode,
+};
+use firmparse::dump::{write_dump, DumpFormat};
+use firmparse::extract::{extract_to_memory, extract_to_memory_with, ExtractedNode};
+use firmparse::file_types::{
+    detect_and_describe_skipping, detect_file_type, file_type_from_arg, file_type_name,
+    framing_note, FileType, MAX_FRAMING_SKIP,
+};
+use firmparse::integrity::verify_data_with;
+use firmparse::naming::{parse_transforms, transform_path};
+use firmparse::offset_map::{offset_map_json, write_offset_csv, OffsetRegion};
+use firmparse::options::{JsonStyle, ProcessOptions};
+use firmparse::out_archive::{archive_name, ArchiveWriter};
+use firmparse::parser::{find_tag, iter_file_entries, MIN_CONTAINER_LEN};
+use firmparse::parts::{find_parts, read_parts, split_part_name};
+use firmparse::processor::{
+    open_output, process_file, process_file_as, process_file_with, recurses_into, write_output,
+    ExtractedFile, ProcessStats, TypeTotals,
+};
+use firmparse::strings::DEFAULT_MIN_STRING_LEN;
+use firmparse::{report, report_inline};
+
+fn usage(program: &str) -> ! {
+    eprintln!(
+        "Usage: {} <input_file> [output_dir] [--format text|json|tree|jsonl|table] [--full-eocd-scan] [--strict] [--jobs N] [--hash-members] [--include-empty] [--max-entries N] [--max-files N] [--entropy] [--crack-meta] [--header-histogram] [--align N] [--manifest] [--sidecar] [--group-by-device] [--zip-timeout SECS] [--zip-password <pw>] [--name-transform lowercase,replace-spaces,prefix:<str>] [--strings[=MINLEN]] [--recover] [--no-recurse] [--index-prefix] [--zip-trailer] [--peel-framing[=N]] [--follow-json-refs] [--auto-out [--force]] [--stats-json[=FILE]] [--summary-csv <file>] [--offset-map <file.json|file.csv>] [--json-style original|pretty|minified] [--force-type <type> --name-glob <pattern>] [--skip-existing | --verify-existing] [--verify-writes] [--config <file>] [--log error|warn|info|debug|trace]",
+        program
+    );
+    eprintln!(
+        "       {} <input_files or globs...> --out <output_dir> [options]",
+        program
+    );
+    eprintln!(
+        "       {} <input_files...> --out-archive <file.zip|file.tar> [options]",
+        program
+    );
+    eprintln!(
+        "       {} <input_files...> --dedup-store <dir> [options]",
+        program
+    );
+    eprintln!(
+        "       {} <input_file> --entry <name> --stdout [options]",
+        program
+    );
+    eprintln!(
+        "       {} parse <input_file> [output_dir] --start-offset <N> [--strict]",
+        program
+    );
+    eprintln!(
+        "       {} verify <input_file> [--checksums <file>] [--full-eocd-scan]",
+        program
+    );
+    eprintln!(
+        "       {} dump <input_file> --entry <index> [--format hex|base64|raw] [--out <file>]",
+        program
+    );
+    eprintln!("       {} components <a.bin> <b.bin> [<c.bin>...]", program);
+    eprintln!("       {} types <input_files...>", program);
+    eprintln!("       {} explore <input_file>", program);
+    eprintln!(
+        "       {} inspect <input_file> [--as <type>] [--strings[=MINLEN]] [--strict]",
+        program
+    );
+    eprintln!(
+        "       {} carve <input_file> --offset <N> --len <M> [--out <file>]",
+        program
+    );
+    eprintln!("On/off flags a config file turns on can be turned off with --no-<flag>");
+    std::process::exit(1);
+}
 
-use processor::process_file;
+/// Print an argument error and exit
+fn fail(message: &str) -> ! {
+    eprintln!("Error: {}", message);
+    std::process::exit(1);
+}
 
-fn main() -> io::Result<()> {
-    let args: Vec<String> = env::args().collect();
+fn main() {
+    let mut args: Vec<String> = env::args().collect();
+    let level = logging::take_log_level(&mut args).unwrap_or_else(|e| fail(&e));
+    logging::init(level.unwrap_or(logging::DEFAULT_LEVEL));
     if args.len() < 2 {
-        eprintln!("Usage: {} <input_file> [output_dir]", args[0]);
+        usage(&args[0]);
+    }
+
+    if let Err(e) = run(&args) {
+        eprintln!("Error: {}", e);
         std::process::exit(1);
     }
+}
 
-    let input_path = &args[1];
-    let output_dir = args.get(2).map(|s| s.as_str());
+fn run(args: &[String]) -> io::Result<()> {
+    match args[1].as_str() {
+        "verify" => {
+            let parsed = Args::parse(&args[2..], &["checksums"]).unwrap_or_else(|e| fail(&e));
+            let input_path = match parsed.positional.first() {
+                Some(p) => p,
+                None => usage(&args[0]),
+            };
+            let full_eocd_scan = parsed.flag("full-eocd-scan");
+            if !run_verify(input_path, parsed.value("checksums"), full_eocd_scan)? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        "parse" => {
+            let parsed = parse_command_args(&args[2..]).unwrap_or_else(|e| fail(&e));
+            run_parse(&parsed, &args[0])
+        }
+        "dump" => {
+            let parsed =
+                Args::parse(&args[2..], &["entry", "format", "out"]).unwrap_or_else(|e| fail(&e));
+            run_dump(&parsed)
+        }
+        "components" => {
+            let parsed = Args::parse(&args[2..], &[]).unwrap_or_else(|e| fail(&e));
+            if parsed.positional.len() < 2 {
+                fail("components needs at least two firmware images to compare");
+            }
+            run_components(&parsed.positional)
+        }
+        "types" => {
+            let parsed = Args::parse(&args[2..], &[]).unwrap_or_else(|e| fail(&e));
+            if parsed.positional.is_empty() {
+                usage(&args[0]);
+            }
+            run_types(&parsed.positional)
+        }
+        "explore" => {
+            let parsed = Args::parse(&args[2..], &[]).unwrap_or_else(|e| fail(&e));
+            let input_path = match parsed.positional.first() {
+                Some(p) => p,
+                None => usage(&args[0]),
+            };
+            let buffer = read_input(input_path)?;
+            explore::run_repl(&buffer, input_name(input_path))
+        }
+        "inspect" => {
+            let parsed = Args::parse(&args[2..], &[&["as"], OPTION_VALUE_FLAGS].concat())
+                .unwrap_or_else(|e| fail(&e));
+            run_inspect(&parsed)
+        }
+        "carve" => {
+            let parsed =
+                Args::parse(&args[2..], &["offset", "len", "out"]).unwrap_or_else(|e| fail(&e));
+            run_carve(&parsed)
+        }
+        _ => {
+            let parsed = Args::parse(
+                &args[1..],
+                &[
+                    &[
+                        "format",
+                        "out",
+                        "entry",
+                        "out-archive",
+                        "dedup-store",
+                        "summary-csv",
+                        "offset-map",
+                    ],
+                    OPTION_VALUE_FLAGS,
+                ]
+                .concat(),
+            )
+            .unwrap_or_else(|e| fail(&e));
+            run_extract(&parsed, &args[0])
+        }
+    }
+}
 
-    let mut file = File::open(input_path)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
+/// Arguments of the `parse` subcommand
+fn parse_command_args(args: &[String]) -> Result<Args, String> {
+    Args::parse(
+        args,
+        &[&["start-offset", "out", "summary-csv"], OPTION_VALUE_FLAGS].concat(),
+    )
+}
 
-    let input_filename = Path::new(input_path)
+/// Get the file name component of an input path for display
+fn input_name(input_path: &str) -> &str {
+    Path::new(input_path)
         .file_name()
         .and_then(|s| s.to_str())
+        .unwrap_or("output")
+}
+
+/// Read an input file, noting on stderr when it is too small to be a container
+/// Processing carries on either way, since a tiny file can still be some other format
+/// An input that doesn't exist but has split parts next to it is read as their concatenation
+fn read_input(input_path: &str) -> io::Result<Vec<u8>> {
+    let buffer = match fs::read(input_path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => read_split_input(input_path)?,
+        result => result?,
+    };
+    if buffer.is_empty() {
+        eprintln!("Note: {} is empty, nothing to parse", input_path);
+    } else if buffer.len() < MIN_CONTAINER_LEN {
+        eprintln!(
+            "Note: {} is only {} byte{}, too small to be Autel firmware (a container needs at least {})",
+            input_path,
+            buffer.len(),
+            if buffer.len() == 1 { "" } else { "s" },
+            MIN_CONTAINER_LEN
+        );
+    }
+    Ok(buffer)
+}
+
+/// Reassemble `<input>.001`, `<input>.002`, ... into one buffer
+fn read_split_input(input_path: &str) -> io::Result<Vec<u8>> {
+    let parts = find_parts(Path::new(input_path))?;
+    let (buffer, sizes) = read_parts(&parts)?;
+    eprintln!(
+        "Note: joined {} parts of {} ({} bytes)",
+        parts.len(),
+        input_path,
+        buffer.len()
+    );
+    // Split tools cut at a fixed size, so only the last part may be shorter
+    if let Some((&first, middle)) = sizes.split_first() {
+        let middle = &middle[..middle.len().saturating_sub(1)];
+        for (part, &size) in parts[1..].iter().zip(middle) {
+            if size != first {
+                eprintln!(
+                    "Note: {} is {} bytes but the first part is {}; it may be incomplete",
+                    part.display(),
+                    size,
+                    first
+                );
+            }
+        }
+    }
+    Ok(buffer)
+}
+
+/// The `--config` file, or `autel-parser.toml` in the working directory if present
+fn load_config(args: &Args) -> Config {
+    Config::discover(args.value("config")).unwrap_or_else(|e| fail(&format!("config: {}", e)))
+}
+
+/// Flags read by `process_options` that take their value as the next argument
+const OPTION_VALUE_FLAGS: &[&str] = &[
+    "config",
+    "jobs",
+    "max-entries",
+    "max-files",
+    "force-type",
+    "name-glob",
+    "align",
+    "zip-timeout",
+    "zip-password",
+    "name-transform",
+    "json-style",
+];
+
+/// Traversal options shared by the extracting modes
+/// Starts from the config file's values; command-line flags take precedence
+fn process_options(args: &Args, config: &Config) -> ProcessOptions {
+    let base = config
+        .process_options()
+        .unwrap_or_else(|e| fail(&format!("config: {}", e)));
+
+    // Command-line overrides are checked first, so they win over the config's
+    let mut overrides = type_overrides(args);
+    overrides.extend(base.type_overrides);
+
+    ProcessOptions {
+        full_eocd_scan: args.switch("full-eocd-scan", base.full_eocd_scan),
+        strict: args.switch("strict", base.strict),
+        jobs: args
+            .number("jobs")
+            .unwrap_or_else(|e| fail(&e))
+            .unwrap_or(base.jobs),
+        hash_members: args.switch("hash-members", base.hash_members),
+        include_empty: args.switch("include-empty", base.include_empty),
+        max_entries: args
+            .number("max-entries")
+            .unwrap_or_else(|e| fail(&e))
+            .or(base.max_entries),
+        max_files: match args.number("max-files").unwrap_or_else(|e| fail(&e)) {
+            Some(0) => fail("--max-files must be at least 1"),
+            Some(n) => Some(n),
+            None => base.max_files,
+        },
+        entropy: args.switch("entropy", base.entropy),
+        crack_meta: args.switch("crack-meta", base.crack_meta),
+        header_histogram: args.switch("header-histogram", base.header_histogram),
+        align: match args.number("align").unwrap_or_else(|e| fail(&e)) {
+            Some(0) => fail("--align must be at least 1"),
+            align => align.or(base.align),
+        },
+        zip_open_timeout: args
+            .number("zip-timeout")
+            .unwrap_or_else(|e| fail(&e))
+            .map(|secs| Duration::from_secs(secs as u64))
+            .or(base.zip_open_timeout),
+        zip_password: args
+            .value("zip-password")
+            .map(str::to_string)
+            .or(base.zip_password),
+        group_by_device: args.switch("group-by-device", base.group_by_device),
+        write_manifest: args.switch("manifest", base.write_manifest),
+        sidecar: args.switch("sidecar", base.sidecar),
+        strings_min_len: match args.value("strings") {
+            Some(v) => match parse_number(v) {
+                Some(0) | None => fail(&format!("invalid minimum length for --strings: '{}'", v)),
+                len => len,
+            },
+            None if args.flag("strings") => Some(DEFAULT_MIN_STRING_LEN),
+            None => base.strings_min_len,
+        },
+        recover: args.switch("recover", base.recover),
+        no_recurse: !args.switch("recurse", !base.no_recurse),
+        index_prefix: args.switch("index-prefix", base.index_prefix),
+        zip_trailer: args.switch("zip-trailer", base.zip_trailer),
+        framing_skip: match args.value("peel-framing") {
+            Some(v) => match parse_number(v) {
+                Some(n) if (1..=MAX_FRAMING_SKIP).contains(&n) => n,
+                _ => fail(&format!(
+                    "--peel-framing takes 1 to {} bytes, not '{}'",
+                    MAX_FRAMING_SKIP, v
+                )),
+            },
+            None if args.flag("peel-framing") => 1,
+            None => base.framing_skip,
+        },
+        json_style: match args.value("json-style") {
+            Some(name) => JsonStyle::from_name(name)
+                .unwrap_or_else(|| fail(&format!("unknown JSON style: '{}'", name))),
+            None => base.json_style,
+        },
+        name_transforms: match args.value("name-transform") {
+            Some(spec) => parse_transforms(spec).unwrap_or_else(|e| fail(&e)),
+            None => base.name_transforms,
+        },
+        skip_existing: args.switch("skip-existing", base.skip_existing),
+        verify_existing: args.switch("verify-existing", base.verify_existing),
+        verify_writes: args.switch("verify-writes", base.verify_writes),
+        follow_json_refs: args.switch("follow-json-refs", base.follow_json_refs),
+        type_overrides: overrides,
+        signatures: base.signatures,
+        handlers: base.handlers,
+    }
+}
+
+/// The `--force-type <type> --name-glob <pattern>` override, if given
+fn type_overrides(args: &Args) -> Vec<(glob::Pattern, FileType)> {
+    let (type_name, pattern) = match (args.value("force-type"), args.value("name-glob")) {
+        (None, None) => return Vec::new(),
+        (Some(t), Some(p)) => (t, p),
+        _ => fail("--force-type and --name-glob must be given together"),
+    };
+    let file_type = file_type_from_arg(type_name)
+        .unwrap_or_else(|| fail(&format!("unknown file type: '{}'", type_name)));
+    let pattern = glob::Pattern::new(pattern)
+        .unwrap_or_else(|e| fail(&format!("invalid pattern '{}': {}", pattern, e)));
+    vec![(pattern, file_type)]
+}
+
+/// Read split parts (`fw.bin.001`, `fw.bin.002`, ...) as the one image they make up
+/// A name is only taken for a part when it doesn't exist itself or the pattern matched
+/// other parts of the same base, so an existing file like `build.2024` stays as given
+fn collapse_parts(expanded: Vec<String>) -> Vec<String> {
+    let base_of = |path: &str| split_part_name(path).map(|(base, _)| base.to_string());
+    let bases: Vec<Option<String>> = expanded.iter().map(|input| base_of(input)).collect();
+    expanded
+        .iter()
+        .zip(&bases)
+        .map(|(input, base)| match base {
+            Some(base)
+                if !Path::new(input).exists()
+                    || bases.iter().filter(|b| b.as_ref() == Some(base)).count() > 1 =>
+            {
+                base.clone()
+            }
+            _ => input.clone(),
+        })
+        .collect()
+}
+
+/// Input files and output directory of an extracting mode
+/// With `--out <dir>` every positional is an input, otherwise it's `<input> [output_dir]`
+fn inputs_and_output(args: &Args, program: &str) -> (Vec<String>, Option<String>) {
+    let (patterns, mut output_dir) = match args.value("out") {
+        Some(out) => (&args.positional[..], Some(out.to_string())),
+        None => (
+            &args.positional[..args.positional.len().min(1)],
+            args.positional.get(1).cloned(),
+        ),
+    };
+    if patterns.is_empty() {
+        usage(program);
+    }
+
+    let mut inputs: Vec<String> = Vec::new();
+    for pattern in patterns {
+        let expanded = expand_input(pattern).unwrap_or_else(|e| fail(&e));
+        for input in collapse_parts(expanded) {
+            if !inputs.contains(&input) {
+                inputs.push(input);
+            }
+        }
+    }
+
+    if args.flag("auto-out") {
+        if output_dir.is_some() {
+            fail("--auto-out picks the output directory itself; don't give one too");
+        }
+        let input = match &inputs[..] {
+            [input] => input,
+            _ => fail("--auto-out takes a single input; use --out <dir> for a batch"),
+        };
+        let dir = auto_output_dir(input);
+        check_auto_output(&dir, input, args.flag("force")).unwrap_or_else(|e| fail(&e));
+        eprintln!("Note: extracting into {}", dir.display());
+        output_dir = Some(dir.to_string_lossy().into_owned());
+    }
+    (inputs, output_dir)
+}
+
+/// `--auto-out` directory for an input: `<stem>_extracted` next to it
+fn auto_output_dir(input: &str) -> PathBuf {
+    let path = Path::new(input);
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
         .unwrap_or("output");
+    path.with_file_name(format!("{}_extracted", stem))
+}
+
+/// Refuse an automatic output directory that is the input itself, isn't a directory, or
+/// already has something in it (unless `force`)
+fn check_auto_output(dir: &Path, input: &str, force: bool) -> Result<(), String> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    let same = match (fs::canonicalize(dir), fs::canonicalize(input)) {
+        (Ok(dir), Ok(input)) => dir == input,
+        _ => false,
+    };
+    if same {
+        return Err(format!(
+            "{} is the input file; refusing to write into it",
+            dir.display()
+        ));
+    }
+    if !dir.is_dir() {
+        return Err(format!("{} exists and is not a directory", dir.display()));
+    }
+    let empty = fs::read_dir(dir)
+        .map_err(|e| format!("{}: {}", dir.display(), e))?
+        .next()
+        .is_none();
+    if !empty && !force {
+        return Err(format!(
+            "{} already exists and is not empty; pass --force to write into it anyway",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Expand a glob pattern into the files it matches; plain paths are passed through as-is
+fn expand_input(pattern: &str) -> Result<Vec<String>, String> {
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![pattern.to_string()]);
+    }
+
+    let paths = glob::glob(pattern).map_err(|e| format!("invalid pattern '{}': {}", pattern, e))?;
+    let matches: Vec<String> = paths
+        .filter_map(Result::ok)
+        .filter(|p| p.is_file())
+        .map(|p| p.to_string_lossy().into_owned())
+        .collect();
+    if matches.is_empty() {
+        return Err(format!("no files match '{}'", pattern));
+    }
+    Ok(matches)
+}
+
+/// Output subdirectory name for each batch input: its file stem, with `-2`, `-3`, ...
+/// appended when an earlier input already took that name
+fn batch_stems(inputs: &[String]) -> Vec<String> {
+    let mut used = HashSet::new();
+    inputs
+        .iter()
+        .map(|input| {
+            let stem = Path::new(input)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("output");
+            let mut name = stem.to_string();
+            let mut n = 1;
+            while !used.insert(name.clone()) {
+                n += 1;
+                name = format!("{}-{}", stem, n);
+            }
+            name
+        })
+        .collect()
+}
+
+/// Run `process_one` over every input
+/// A batch of several inputs gets a header per input, a subdirectory per input under the
+/// output directory, and aggregate stats at the end; one bad input doesn't stop the rest
+fn run_batch(
+    inputs: &[String],
+    output_dir: Option<&str>,
+    stats_json: Option<StatsTarget>,
+    summary_csv: Option<&str>,
+    offset_map: Option<&str>,
+    process_one: impl Fn(&str, Option<&str>) -> io::Result<ProcessStats>,
+) -> io::Result<()> {
+    if let [input] = inputs {
+        let stats = process_one(input, output_dir)?;
+        report_type_totals(&stats.type_totals);
+        if let Some(target) = stats_json {
+            write_stats_json(target, stats.to_json())?;
+        }
+        if let Some(path) = summary_csv {
+            write_summary_csv(path, &stats)?;
+        }
+        if let Some(path) = offset_map {
+            write_offset_map(path, &stats.regions)?;
+        }
+        return report_write_failures(&stats);
+    }
+
+    let mut total = ProcessStats::default();
+    let mut failed_inputs = 0;
+    for (input, stem) in inputs.iter().zip(batch_stems(inputs)) {
+        println!("==> {} <==", input);
+        let input_dir =
+            output_dir.map(|dir| Path::new(dir).join(stem).to_string_lossy().into_owned());
+        match process_one(input, input_dir.as_deref()) {
+            Ok(stats) => {
+                report_type_totals(&stats.type_totals);
+                total.merge(stats);
+            }
+            Err(e) => {
+                failed_inputs += 1;
+                eprintln!("Error: {}: {}", input, e);
+            }
+        }
+        println!();
+    }
 
-    process_file(&buffer, Some(input_filename), output_dir, 0)?;
+    println!(
+        "=== Batch: {} inputs ({} failed), {} files written, {} skipped ===",
+        inputs.len(),
+        failed_inputs,
+        total.files_written,
+        total.files_skipped
+    );
+    print!("{}", render_type_table(&total.type_totals));
+    if let Some(target) = stats_json {
+        let mut json = total.to_json();
+        json["inputs"] = inputs.len().into();
+        json["failed_inputs"] = failed_inputs.into();
+        write_stats_json(target, json)?;
+    }
+    if let Some(path) = summary_csv {
+        write_summary_csv(path, &total)?;
+    }
+    if let Some(path) = offset_map {
+        write_offset_map(path, &total.regions)?;
+    }
+
+    if failed_inputs > 0 {
+        return Err(io::Error::other(format!(
+            "{} of {} inputs failed",
+            failed_inputs,
+            inputs.len()
+        )));
+    }
+    report_write_failures(&total)
+}
 
+/// Where `--stats-json` writes the run's statistics
+#[derive(Clone, Copy)]
+enum StatsTarget<'a> {
+    Stderr,
+    File(&'a str),
+}
+
+/// `--stats-json` goes to stderr, `--stats-json=<file>` to a file
+fn stats_target(args: &Args) -> Option<StatsTarget<'_>> {
+    match args.value("stats-json") {
+        Some(path) => Some(StatsTarget::File(path)),
+        None if args.flag("stats-json") => Some(StatsTarget::Stderr),
+        None => None,
+    }
+}
+
+fn write_stats_json(target: StatsTarget, json: serde_json::Value) -> io::Result<()> {
+    let text = serde_json::to_string_pretty(&json)?;
+    match target {
+        StatsTarget::Stderr => eprintln!("{}", text),
+        StatsTarget::File(path) => fs::write(path, text + "\n")?,
+    }
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::file_types::{detect_file_type, FileType};
-    use crate::parser::parse_file_entries;
+/// `--summary-csv`: the header/meta pairs of every container entry seen, across all inputs
+fn write_summary_csv(path: &str, stats: &ProcessStats) -> io::Result<()> {
+    let mut out = BufWriter::new(open_output(Path::new(path))?);
+    write_metadata_csv(&stats.metadata_summary, &mut out)?;
+    out.flush()?;
+    println!("→ Header/meta summary written to {}", path);
+    Ok(())
+}
+
+/// `--offset-map`: the absolute byte range of every entry and nested artifact of each input,
+/// as CSV for a `.csv` file and JSON otherwise
+fn write_offset_map(path: &str, regions: &[OffsetRegion]) -> io::Result<()> {
+    let mut out = BufWriter::new(open_output(Path::new(path))?);
+    if path.to_ascii_lowercase().ends_with(".csv") {
+        write_offset_csv(regions, &mut out)?;
+    } else {
+        serde_json::to_writer_pretty(&mut out, &offset_map_json(regions))?;
+        writeln!(out)?;
+    }
+    out.flush()?;
+    println!("→ Offset map written to {}", path);
+    Ok(())
+}
+
+/// Default mode: walk the input and extract into the output directory, if given
+fn run_extract(args: &Args, program: &str) -> io::Result<()> {
+    let config = load_config(args);
+    let format = match args.value("format") {
+        Some(value) => OutputFormat::from_name(value)
+            .unwrap_or_else(|| fail(&format!("unknown output format: '{}'", value))),
+        None if args.flag("json-lines") => OutputFormat::JsonLines,
+        None => config
+            .output_format()
+            .unwrap_or_else(|e| fail(&format!("config: {}", e)))
+            .unwrap_or(OutputFormat::Text),
+    };
+
+    let (inputs, output_dir) = inputs_and_output(args, program);
+    let output_dir = output_dir.as_deref();
+    let options = process_options(args, &config);
+
+    let batch_output = format == OutputFormat::Text
+        && !["out-archive", "dedup-store", "stdout"]
+            .iter()
+            .any(|flag| args.flag(flag));
+    if args.value("summary-csv").is_some() && !batch_output {
+        fail("--summary-csv only works with text output, not with --out-archive, --dedup-store or --stdout");
+    }
+    if args.value("offset-map").is_some() && !batch_output {
+        fail("--offset-map only works with text output, not with --out-archive, --dedup-store or --stdout");
+    }
+
+    if let Some(archive_path) = args.value("out-archive") {
+        if output_dir.is_some() {
+            fail("--out-archive replaces the output directory; give only one");
+        }
+        if format != OutputFormat::Text {
+            fail("--out-archive only works with text output");
+        }
+        return run_to_archive(&inputs, Path::new(archive_path), &options);
+    }
+
+    if let Some(store_dir) = args.value("dedup-store") {
+        if output_dir.is_some() {
+            fail("--dedup-store replaces the output directory; give only one");
+        }
+        if format != OutputFormat::Text {
+            fail("--dedup-store only works with text output");
+        }
+        return run_to_store(&inputs, Path::new(store_dir), &options);
+    }
+
+    if args.flag("stdout") {
+        let input_path = match (&inputs[..], output_dir) {
+            ([input], None) => input,
+            _ => fail("--stdout takes a single input file and no output directory"),
+        };
+        return run_stdout(args, input_path, &options);
+    }
+
+    match format {
+        OutputFormat::Text => {
+            run_batch(
+                &inputs,
+                output_dir,
+                stats_target(args),
+                args.value("summary-csv"),
+                args.value("offset-map"),
+                |input_path, output_dir| {
+                    let buffer = read_input(input_path)?;
+                    process_file(&buffer, Some(input_name(input_path)), output_dir, &options)
+                },
+            )?;
+        }
+        OutputFormat::JsonLines => {
+            if output_dir.is_some() {
+                fail("JSON lines output does not extract files; omit the output directory");
+            }
+            run_json_lines(&inputs, &options)?;
+        }
+        OutputFormat::Json | OutputFormat::Tree | OutputFormat::Table => {
+            let input_path = match &inputs[..] {
+                [input] => input,
+                _ => fail("JSON, tree and table output take a single input file"),
+            };
+            if output_dir.is_some() {
+                fail("JSON, tree and table output do not extract files; omit the output directory");
+            }
+            let buffer = read_input(input_path)?;
+            let tree = extract_to_memory_with(
+                &buffer,
+                Some(input_name(input_path)),
+                options.full_eocd_scan,
+            );
+            match format {
+                OutputFormat::Json => {
+                    let json = serde_json::to_string_pretty(&tree.to_json())?;
+                    println!("{}", json);
+                }
+                OutputFormat::Tree => print!("{}", render_tree(&TreeNode::from_extracted(&tree))),
+                _ => print_leaf_table(&tree),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the per-type totals closing one input's report
+fn report_type_totals(totals: &TypeTotals) {
+    report!();
+    report!("=== File Types ===");
+    report_inline!("{}", render_type_table(totals));
+}
+
+/// Print the one-line-per-leaf table, then the per-type totals
+fn print_leaf_table(tree: &ExtractedNode) {
+    print!("{}", render_leaf_table(tree));
+    println!();
+    println!("=== File Types ===");
+    print!("{}", render_type_table(&tree_type_totals(tree)));
+}
+
+/// Stream one JSON object per extracted file to stdout, with the report on stderr
+/// Nothing is collected, so memory stays bounded however large the image
+fn run_json_lines(inputs: &[String], options: &ProcessOptions) -> io::Result<()> {
+    set_report_to_stderr(true);
+    let mut out = io::stdout().lock();
+    for input in inputs {
+        let buffer = read_input(input)?;
+        let totals = process_file_with(&buffer, Some(input_name(input)), options, &mut |file| {
+            writeln!(out, "{}", file.to_json())
+        })?;
+        report_type_totals(&totals);
+    }
+    out.flush()
+}
+
+/// Extract every input into one `.zip` or `.tar`, named by nesting path
+fn run_to_archive(
+    inputs: &[String],
+    archive_path: &Path,
+    options: &ProcessOptions,
+) -> io::Result<()> {
+    let mut writer = ArchiveWriter::create(archive_path)?;
+    let mut names = HashSet::new();
+    let mut written = 0;
+
+    for input in inputs {
+        if inputs.len() > 1 {
+            println!("==> {} <==", input);
+        }
+        let buffer = read_input(input)?;
+        let totals = process_file_with(&buffer, Some(input_name(input)), options, &mut |file| {
+            let name = archive_name(&transform_path(&options.name_transforms, &file.path));
+            // Archives can't hold two entries with the same name; keep the first
+            if !names.insert(name.clone()) {
+                tracing::warn!("{} already in archive, skipped", name);
+                return Ok(());
+            }
+            writer.add(&name, file.data)?;
+            written += 1;
+            Ok(())
+        })?;
+        report_type_totals(&totals);
+    }
+
+    writer.finish()?;
+    println!("→ {} files written to {}", written, archive_path.display());
+    Ok(())
+}
+
+/// Extract every input into a content-addressed store: leaves go in as `<dir>/<sha256>`,
+/// and each input gets a manifest of its paths and their digests
+fn run_to_store(inputs: &[String], store_dir: &Path, options: &ProcessOptions) -> io::Result<()> {
+    let mut store = DedupStore::open(store_dir)?;
+
+    for input in inputs {
+        if inputs.len() > 1 {
+            println!("==> {} <==", input);
+        }
+        let buffer = read_input(input)?;
+        // A container's contents are stored on their own; its bytes would only duplicate
+        // them. It's held back until the next file shows whether it was opened: anything
+        // that produced no children (refused, failed, or empty) is stored as a blob
+        let mut pending: Option<(PathBuf, FileType, Option<String>, Vec<u8>)> = None;
+        let totals = process_file_with(&buffer, Some(input_name(input)), options, &mut |file| {
+            if let Some((path, file_type, device, data)) = pending.take() {
+                let children = path.with_extension("");
+                if !file.path.starts_with(&children) {
+                    store.add(&ExtractedFile {
+                        path,
+                        file_type,
+                        data: &data,
+                        device,
+                    })?;
+                }
+            }
+            if recurses_into(file.file_type) && !options.no_recurse {
+                pending = Some((
+                    file.path.clone(),
+                    file.file_type,
+                    file.device.clone(),
+                    file.data.to_vec(),
+                ));
+                return Ok(());
+            }
+            store.add(file)
+        })?;
+        if let Some((path, file_type, device, data)) = pending {
+            store.add(&ExtractedFile {
+                path,
+                file_type,
+                data: &data,
+                device,
+            })?;
+        }
+        report_type_totals(&totals);
+        let manifest = store.finish_input(input)?;
+        println!("→ Manifest written to {}", manifest.display());
+    }
+
+    println!(
+        "→ {} files stored as {} new blobs in {} ({} bytes deduplicated)",
+        store.files_added,
+        store.blobs_written,
+        store_dir.display(),
+        store.bytes_deduplicated
+    );
+    Ok(())
+}
+
+/// Stream the bytes of the extracted file named by `--entry` to stdout
+/// The traversal report goes to stderr so stdout carries only the data
+fn run_stdout(args: &Args, input_path: &str, options: &ProcessOptions) -> io::Result<()> {
+    let name = match args.value("entry") {
+        Some(name) => name,
+        None => fail("--stdout requires --entry <name>"),
+    };
+    set_report_to_stderr(true);
+
+    let buffer = read_input(input_path)?;
+    let mut stdout = io::stdout().lock();
+    let mut found = false;
+    // A bare file name or a trailing part of the nesting path, e.g. `pkg/config.json`
+    let totals = process_file_with(
+        &buffer,
+        Some(input_name(input_path)),
+        options,
+        &mut |file| {
+            if !found && file.path.ends_with(name) {
+                found = true;
+                stdout.write_all(file.data)?;
+            }
+            Ok(())
+        },
+    )?;
+    report_type_totals(&totals);
+    stdout.flush()?;
+
+    if !found {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no extracted file matches '{}'", name),
+        ));
+    }
+    Ok(())
+}
+
+/// Parse a container that starts at an explicit offset, bypassing type detection
+fn run_parse(args: &Args, program: &str) -> io::Result<()> {
+    let (inputs, output_dir) = inputs_and_output(args, program);
+    let offset = args
+        .number("start-offset")
+        .unwrap_or_else(|e| fail(&e))
+        .unwrap_or(0);
+    let options = process_options(args, &load_config(args));
+
+    run_batch(
+        &inputs,
+        output_dir.as_deref(),
+        stats_target(args),
+        args.value("summary-csv"),
+        None,
+        |input_path, output_dir| {
+            let buffer = read_input(input_path)?;
+            if offset >= buffer.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "start offset 0x{:x} is past the end of the file (0x{:x} bytes)",
+                        offset,
+                        buffer.len()
+                    ),
+                ));
+            }
+
+            // Show where parsing will actually pick up, so a bad offset is obvious
+            match find_tag(&buffer, offset) {
+                Some((pos, tag)) => println!(
+                    "First tag at 0x{:x} (+0x{:x}): {}",
+                    pos,
+                    pos - offset,
+                    String::from_utf8_lossy(tag)
+                ),
+                None => println!("Warning: no tag found at or after 0x{:x}", offset),
+            }
+
+            process_file_as(
+                &buffer[offset..],
+                Some(input_name(input_path)),
+                FileType::AutelContainer,
+                output_dir,
+                &options,
+            )
+        },
+    )
+}
+
+/// Fail the run (after it has finished) if any extracted file could not be written
+fn report_write_failures(stats: &ProcessStats) -> io::Result<()> {
+    if stats.write_failures > 0 {
+        return Err(io::Error::other(format!(
+            "{} of {} files failed to write",
+            stats.write_failures,
+            stats.write_failures + stats.files_written
+        )));
+    }
+    Ok(())
+}
+
+/// Compare the leaf entries of several images: which are identical in all, which changed
+fn run_components(inputs: &[String]) -> io::Result<()> {
+    let mut images = Vec::new();
+    for input in inputs {
+        let buffer = read_input(input)?;
+        images.push(leaf_digests(&extract_to_memory(
+            &buffer,
+            Some(input_name(input)),
+        )));
+    }
+    let rows = component_matrix(&images);
+
+    let mut headers = vec!["Component"];
+    headers.extend(inputs.iter().map(|input| input_name(input)));
+    headers.push("Status");
+    let table: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            let mut cells = vec![row.path.clone()];
+            cells.extend(row.variants().iter().map(char::to_string));
+            cells.push(row.status().name().to_string());
+            cells
+        })
+        .collect();
+    print!("{}", render_table(&headers, &table));
+
+    let count = |status| rows.iter().filter(|r| r.status() == status).count();
+    println!();
+    println!(
+        "{} components: {} shared, {} changed, {} not in every image",
+        rows.len(),
+        count(ComponentStatus::Shared),
+        count(ComponentStatus::Changed),
+        count(ComponentStatus::Partial)
+    );
+    println!("Letters mark byte-identical copies of a component; '-' means absent");
+    Ok(())
+}
+
+/// Print the distinct types found anywhere in each input's tree, with counts
+/// With several inputs each line is prefixed by the input and a tab, for grouping
+fn run_types(inputs: &[String]) -> io::Result<()> {
+    for input in inputs {
+        let buffer = read_input(input)?;
+        let tree = extract_to_memory(&buffer, Some(input_name(input)));
+        let fingerprint = render_type_fingerprint(&tree_type_totals(&tree));
+        if inputs.len() > 1 {
+            println!("{}\t{}", input, fingerprint);
+        } else {
+            println!("{}", fingerprint);
+        }
+    }
+    Ok(())
+}
+
+/// Check every entry in the tree, print pass/fail per entry and return the verdict
+/// With a checksum list, each listed component is also compared against its expected digest
+fn run_verify(input_path: &str, checksums: Option<&str>, full_eocd_scan: bool) -> io::Result<bool> {
+    let buffer = read_input(input_path)?;
+    let list = checksums.map(|path| {
+        let text = fs::read_to_string(path)
+            .unwrap_or_else(|e| fail(&format!("cannot read {}: {}", path, e)));
+        parse_checksum_file(&text).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)))
+    });
+
+    let report = verify_data_with(&buffer, Some(input_name(input_path)), full_eocd_scan);
+    for check in &report.checks {
+        if check.passed() {
+            println!("PASS {}", check.path);
+        } else {
+            println!("FAIL {}: {}", check.path, check.problems.join("; "));
+        }
+    }
+
+    let mut checksum_failures = 0;
+    if let Some(list) = list {
+        println!();
+        let tree = extract_to_memory_with(&buffer, Some(input_name(input_path)), full_eocd_scan);
+        for result in check_checksums(&tree, &list) {
+            let entry = &result.entry;
+            match &result.outcome {
+                ChecksumOutcome::Match { path } => {
+                    println!(
+                        "MATCH {} ({}) at {}",
+                        entry.name,
+                        entry.digest.algorithm(),
+                        path
+                    )
+                }
+                ChecksumOutcome::Mismatch { path, actual } => println!(
+                    "MISMATCH {}: expected {} {}, got {} ({})",
+                    entry.name,
+                    entry.digest.algorithm(),
+                    entry.digest.to_hex(),
+                    actual,
+                    path
+                ),
+                ChecksumOutcome::Missing => println!("MISSING {}", entry.name),
+            }
+            if !result.passed() {
+                checksum_failures += 1;
+            }
+        }
+    }
+
+    println!();
+    let passed = report.passed() && checksum_failures == 0;
+    if passed {
+        println!("Verdict: PASS ({} entries checked)", report.checks.len());
+    } else {
+        println!(
+            "Verdict: FAIL ({} of {} entries failed, {} listed checksums failed)",
+            report.failures(),
+            report.checks.len(),
+            checksum_failures
+        );
+    }
+
+    Ok(passed)
+}
+
+/// Write one container entry's content, encoded, to stdout or a file
+fn run_dump(args: &Args) -> io::Result<()> {
+    let input_path = match args.positional.first() {
+        Some(p) => p,
+        None => fail("dump requires an input file"),
+    };
+    let index = match args.number("entry").unwrap_or_else(|e| fail(&e)) {
+        Some(i) => i,
+        None => fail("dump requires --entry"),
+    };
+    let format = match args.value("format") {
+        Some(value) => DumpFormat::from_name(value)
+            .unwrap_or_else(|| fail(&format!("unknown dump format: '{}'", value))),
+        None => DumpFormat::Hex,
+    };
+
+    let buffer = read_input(input_path)?;
+    let entry = match iter_file_entries(&buffer).nth(index) {
+        Some(e) => e,
+        None => fail(&format!(
+            "entry {} out of range ({} entries)",
+            index,
+            iter_file_entries(&buffer).count()
+        )),
+    };
+
+    match args.value("out") {
+        Some(path) => write_dump(
+            entry.content,
+            format,
+            &mut BufWriter::new(open_output(Path::new(path))?),
+        ),
+        // Stdout is line buffered on its own, which would flush every line of hex
+        None => write_dump(
+            entry.content,
+            format,
+            &mut BufWriter::new(io::stdout().lock()),
+        ),
+    }
+}
+
+/// Run the analysis for one type over a whole standalone file, without extracting anything
+/// The type is detected (and the deciding rule shown) unless `--as` names it
+fn run_inspect(args: &Args) -> io::Result<()> {
+    let input_path = match args.positional.first() {
+        Some(p) => p,
+        None => fail("inspect requires an input file"),
+    };
+    let options = process_options(args, &load_config(args));
+    let buffer = read_input(input_path)?;
+    let name = input_name(input_path);
+
+    let mut payload = buffer.as_slice();
+    let file_type = match args.value("as") {
+        Some(type_name) => file_type_from_arg(type_name)
+            .unwrap_or_else(|| fail(&format!("unknown file type: '{}'", type_name))),
+        None => {
+            let detection = detect_and_describe_skipping(&buffer, Some(name), options.framing_skip);
+            match detection.matched_rule {
+                Some(rule) => println!(
+                    "Detected {} by {}{} ({:?} confidence)",
+                    file_type_name(&detection.file_type),
+                    rule,
+                    detection
+                        .match_offset
+                        .map(|o| format!(" at 0x{:x}", o))
+                        .unwrap_or_default(),
+                    detection.confidence
+                ),
+                None => println!("No detection rule matched"),
+            }
+            if detection.skipped > 0 {
+                println!("{}", framing_note(&detection, &buffer));
+                payload = &buffer[detection.skipped..];
+            }
+            detection.file_type
+        }
+    };
 
-    fn build_test_container(filename: &str, content: &[u8]) -> Vec<u8> {
-        let mut buffer = Vec::new();
+    let entropy = shannon_entropy(payload);
+    let note = if entropy > HIGH_ENTROPY {
+        " (likely compressed/encrypted)"
+    } else {
+        ""
+    };
+    println!("Entropy: {:.2} bits/byte{}", entropy, note);
+    println!();
 
-        // "<filetransfer>" tag
-        buffer.extend_from_slice(b"\"<filetransfer>\"");
+    let stats = process_file_as(payload, Some(name), file_type, None, &options)?;
+    report_type_totals(&stats.type_totals);
+    Ok(())
+}
 
-        // "<fileinfo>" tag
-        buffer.extend_from_slice(b"\"<fileinfo>\"");
+/// Slice a byte range out of the input, report its detected type and optionally save it
+fn run_carve(args: &Args) -> io::Result<()> {
+    let input_path = match args.positional.first() {
+        Some(p) => p,
+        None => fail("carve requires an input file"),
+    };
+    let offset = match args.number("offset").unwrap_or_else(|e| fail(&e)) {
+        Some(o) => o,
+        None => fail("carve requires --offset"),
+    };
+    let len = match args.number("len").unwrap_or_else(|e| fail(&e)) {
+        Some(l) => l,
+        None => fail("carve requires --len"),
+    };
 
-        // File info: 4 bytes name length + 4 bytes header + name
-        let name_bytes = filename.as_bytes();
-        buffer.extend_from_slice(&(name_bytes.len() as u32).to_be_bytes());
-        buffer.extend_from_slice(&[0xfd, 0xce, 0x69, 0x48]); // header data
-        buffer.extend_from_slice(name_bytes);
+    let buffer = fs::read(input_path)?;
+    let end = match offset.checked_add(len) {
+        Some(end) if end <= buffer.len() => end,
+        _ => fail(&format!(
+            "range 0x{:x}+0x{:x} exceeds file size 0x{:x} ({} bytes)",
+            offset,
+            len,
+            buffer.len(),
+            buffer.len()
+        )),
+    };
 
-        // "<filecontent>" tag
-        buffer.extend_from_slice(b"\"<filecontent>\"");
+    let carved = &buffer[offset..end];
+    let out_path = args.value("out");
+    let file_type = detect_file_type(carved, out_path);
 
-        // Content: 4 bytes length + 4 bytes meta + content
-        buffer.extend_from_slice(&(content.len() as u32).to_be_bytes());
-        buffer.extend_from_slice(&[0x33, 0xa8, 0x3b, 0x1f]); // content meta
-        buffer.extend_from_slice(content);
+    println!(
+        "Carved {} bytes at 0x{:x}..0x{:x}: [{}]",
+        carved.len(),
+        offset,
+        end,
+        file_type_name(&file_type)
+    );
+    print_hexdump_preview_indented(carved, 3, "");
 
-        buffer
+    if let Some(path) = out_path {
+        write_output(Path::new(path), carved)?;
+        println!("→ Written to {}", path);
     }
 
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::build_test_container;
+    use firmparse::parser::parse_file_entries;
+
     #[test]
     fn test_detect_and_parse_container() {
         let content = b"{\"test\": true}";
@@ -85,4 +1263,81 @@ mod tests {
             FileType::Json
         );
     }
+
+    #[test]
+    fn test_auto_output_dir() {
+        assert_eq!(
+            auto_output_dir("dl/EVO_FW_V1.5.8.bin"),
+            PathBuf::from("dl/EVO_FW_V1.5.8_extracted")
+        );
+        assert_eq!(auto_output_dir("fw"), PathBuf::from("fw_extracted"));
+
+        let dir = env::temp_dir().join(format!("firmparse-auto-out-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let input = dir.join("fw.bin");
+        let input = input.to_str().unwrap();
+        let out = auto_output_dir(input);
+        assert!(check_auto_output(&out, input, false).is_ok());
+
+        fs::create_dir_all(&out).unwrap();
+        assert!(check_auto_output(&out, input, false).is_ok());
+        fs::write(out.join("config.json"), b"{}").unwrap();
+        assert!(check_auto_output(&out, input, false)
+            .unwrap_err()
+            .contains("--force"));
+        assert!(check_auto_output(&out, input, true).is_ok());
+
+        fs::write(input, b"firmware").unwrap();
+        let as_itself = check_auto_output(Path::new(input), input, true).unwrap_err();
+        assert!(as_itself.contains("refusing"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_command_takes_option_values() {
+        let args: Vec<String> = ["fw.bin", "--name-transform", "lowercase", "out"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let parsed = parse_command_args(&args).unwrap();
+        assert_eq!(parsed.value("name-transform"), Some("lowercase"));
+        assert_eq!(parsed.positional, ["fw.bin", "out"]);
+    }
+
+    #[test]
+    fn test_batch_stems() {
+        let inputs: Vec<String> = ["a/fw.bin", "b/fw.bin", "fw-2.img", "c/fw.zip", "other.bin"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            batch_stems(&inputs),
+            ["fw", "fw-2", "fw-2-2", "fw-3", "other"]
+        );
+    }
+
+    #[test]
+    fn test_collapse_parts() {
+        let dir = env::temp_dir().join(format!("firmparse-parts-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        fs::write(path("build.2024"), b"not a part").unwrap();
+        fs::write(path("fw.bin.001"), b"a").unwrap();
+        fs::write(path("fw.bin.002"), b"b").unwrap();
+
+        // An existing file with a numeric suffix is read as itself
+        assert_eq!(
+            collapse_parts(vec![path("build.2024")]),
+            [path("build.2024")]
+        );
+        // Several parts of one base make up that base
+        assert_eq!(
+            collapse_parts(vec![path("fw.bin.001"), path("fw.bin.002")]),
+            [path("fw.bin"), path("fw.bin")]
+        );
+        // So does a part name that doesn't exist
+        assert_eq!(collapse_parts(vec![path("os.img.001")]), [path("os.img")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
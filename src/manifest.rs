@@ -0,0 +1,369 @@
+//! Firmware manifests: JSON entries that describe the other entries of their container
+
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::file_entry::FileEntry;
+use crate::file_types::FileType;
+use crate::hash::{sha256, to_hex};
+
+/// Keys a declared file's name may be stored under
+const NAME_KEYS: [&str; 4] = ["name", "file", "filename", "path"];
+const SIZE_KEYS: [&str; 3] = ["size", "length", "len"];
+const CHECKSUM_KEYS: [&str; 5] = ["crc32", "crc", "sha256", "checksum", "md5"];
+const DEVICE_KEYS: [&str; 3] = ["device", "target", "module"];
+
+/// One file listed in a manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeclaredFile {
+    pub name: String,
+    pub size: Option<u64>,
+    pub crc32: Option<u32>,
+    /// Lowercase hex digest
+    pub sha256: Option<String>,
+    /// The subsystem the file is for, as the manifest names it
+    pub device: Option<String>,
+}
+
+/// What a manifest says about its container
+#[derive(Debug, Clone, PartialEq)]
+pub struct FirmwareManifest {
+    pub version: Option<String>,
+    pub files: Vec<DeclaredFile>,
+}
+
+fn base_name(name: &str) -> &str {
+    Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(name)
+}
+
+fn first_key<'v>(object: &'v serde_json::Map<String, Value>, keys: &[&str]) -> Option<&'v Value> {
+    keys.iter().find_map(|k| object.get(*k))
+}
+
+/// A number, or a string holding one
+fn as_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// A hex string (optionally `0x`-prefixed) of the given number of digits
+fn hex_string(value: &Value, digits: usize) -> Option<String> {
+    let s = value.as_str()?.trim();
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    (s.len() == digits && s.bytes().all(|b| b.is_ascii_hexdigit())).then(|| s.to_lowercase())
+}
+
+fn declared_file(value: &Value) -> Option<DeclaredFile> {
+    let object = value.as_object()?;
+    let name = first_key(object, &NAME_KEYS)?.as_str()?.to_string();
+    let size = first_key(object, &SIZE_KEYS).and_then(as_u64);
+
+    // A generic `checksum` is classified by its length
+    let mut crc32 = None;
+    let mut sha = None;
+    for key in ["crc32", "crc", "checksum"] {
+        if let Some(value) = object.get(key) {
+            crc32 = crc32.or_else(|| match value {
+                Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+                _ => hex_string(value, 8).and_then(|h| u32::from_str_radix(&h, 16).ok()),
+            });
+        }
+    }
+    for key in ["sha256", "checksum"] {
+        if let Some(value) = object.get(key) {
+            sha = sha.or_else(|| hex_string(value, 64));
+        }
+    }
+
+    let device = first_key(object, &DEVICE_KEYS)
+        .and_then(Value::as_str)
+        .map(str::to_string);
+
+    Some(DeclaredFile {
+        name,
+        size,
+        crc32,
+        sha256: sha,
+        device,
+    })
+}
+
+/// Parse JSON that looks like a firmware manifest: an object with a `files` array of
+/// named entries, plus a `version` or per-file checksums to tell it apart from other JSON
+pub fn parse_manifest(data: &[u8]) -> Option<FirmwareManifest> {
+    let json: Value = serde_json::from_slice(data).ok()?;
+    let object = json.as_object()?;
+    let listed = object.get("files")?.as_array()?;
+    let files: Vec<DeclaredFile> = listed.iter().filter_map(declared_file).collect();
+    if files.is_empty() {
+        return None;
+    }
+
+    let version = object.get("version").and_then(|v| match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    });
+    let has_checksums = listed
+        .iter()
+        .filter_map(Value::as_object)
+        .any(|f| first_key(f, &CHECKSUM_KEYS).is_some());
+    if version.is_none() && !has_checksums {
+        return None;
+    }
+
+    Some(FirmwareManifest { version, files })
+}
+
+/// The first JSON entry that parses as a firmware manifest
+pub fn detect_manifest<'e, 'a>(entries: &'e [FileEntry<'a>]) -> Option<&'e FileEntry<'a>> {
+    entries
+        .iter()
+        .find(|e| e.detected_type() == FileType::Json && parse_manifest(e.content).is_some())
+}
+
+impl FirmwareManifest {
+    /// The declaration for an entry, matched on file name (manifests often carry paths)
+    pub fn declared(&self, filename: &str) -> Option<&DeclaredFile> {
+        let name = base_name(filename);
+        self.files.iter().find(|f| base_name(&f.name) == name)
+    }
+
+    /// Where the entry disagrees with its declaration, or None if it isn't declared
+    pub fn check_entry(&self, entry: &FileEntry) -> Option<Vec<String>> {
+        let declared = self.declared(entry.filename.as_deref()?)?;
+        let mut problems = Vec::new();
+
+        if let Some(size) = declared.size {
+            if size != entry.content.len() as u64 {
+                problems.push(format!(
+                    "manifest declares {} bytes, entry has {}",
+                    size,
+                    entry.content.len()
+                ));
+            }
+        }
+        if let Some(crc) = declared.crc32 {
+            let actual = crc32fast::hash(entry.content);
+            if crc != actual {
+                problems.push(format!(
+                    "manifest declares crc32 {:08x}, entry has {:08x}",
+                    crc, actual
+                ));
+            }
+        }
+        if let Some(digest) = &declared.sha256 {
+            let actual = to_hex(&sha256(entry.content));
+            if *digest != actual {
+                problems.push(format!(
+                    "manifest declares sha256 {}, entry has {}",
+                    &digest[..16],
+                    &actual[..16]
+                ));
+            }
+        }
+
+        Some(problems)
+    }
+
+    /// Declared files with no entry of that name
+    pub fn missing(&self, entries: &[FileEntry]) -> Vec<&str> {
+        self.files
+            .iter()
+            .filter(|f| {
+                !entries.iter().any(|e| {
+                    e.filename
+                        .as_deref()
+                        .is_some_and(|n| base_name(n) == base_name(&f.name))
+                })
+            })
+            .map(|f| f.name.as_str())
+            .collect()
+    }
+}
+
+/// How the files a manifest refers to line up with the entries actually present
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RefReport {
+    /// Indexes (into the entries given) of referenced entries, in the order first referenced
+    pub resolved: Vec<usize>,
+    /// References no entry answers to, as written in the manifest
+    pub missing: Vec<String>,
+    /// Indexes of entries the manifest never mentions, in container order
+    pub unreferenced: Vec<usize>,
+}
+
+impl RefReport {
+    /// Entry indexes in the order to extract them: what the manifest lists, in its order,
+    /// then everything else in container order
+    pub fn extraction_order(&self) -> Vec<usize> {
+        self.resolved
+            .iter()
+            .chain(&self.unreferenced)
+            .copied()
+            .collect()
+    }
+}
+
+/// Whether a string in a manifest reads as a file name or path: no spaces or URL scheme,
+/// and an extension with a letter in it (so `1.2.3` is a version, not a file)
+fn looks_like_file(s: &str) -> bool {
+    if s.is_empty() || s.contains(char::is_whitespace) || s.contains("://") {
+        return false;
+    }
+    match base_name(s).rsplit_once('.') {
+        Some((stem, ext)) => {
+            !stem.is_empty()
+                && (1..=8).contains(&ext.len())
+                && ext.bytes().all(|b| b.is_ascii_alphanumeric())
+                && ext.bytes().any(|b| b.is_ascii_alphabetic())
+        }
+        None => false,
+    }
+}
+
+/// Every string anywhere in the JSON, in array order (object keys come sorted)
+fn collect_strings<'v>(value: &'v Value, out: &mut Vec<&'v str>) {
+    match value {
+        Value::String(s) => out.push(s),
+        Value::Array(items) => items.iter().for_each(|v| collect_strings(v, out)),
+        Value::Object(object) => object.values().for_each(|v| collect_strings(v, out)),
+        _ => {}
+    }
+}
+
+/// Match the files a manifest refers to, by name or path anywhere in its JSON, against
+/// the entries present (on file name, as `declared` does)
+/// Unlike `parse_manifest` this takes any JSON: a string counts as a reference if it names
+/// an entry or looks like a file name
+pub fn resolve_manifest_refs(manifest: &Value, entries: &[FileEntry]) -> RefReport {
+    let mut strings = Vec::new();
+    collect_strings(manifest, &mut strings);
+
+    let mut report = RefReport::default();
+    let mut seen = Vec::new();
+    for s in strings {
+        let name = base_name(s);
+        if seen.contains(&name) {
+            continue;
+        }
+        let found = entries
+            .iter()
+            .position(|e| e.filename.as_deref().is_some_and(|n| base_name(n) == name));
+        match found {
+            Some(index) => report.resolved.push(index),
+            None if looks_like_file(s) => report.missing.push(s.to_string()),
+            None => continue,
+        }
+        seen.push(name);
+    }
+    report.unreferenced = (0..entries.len())
+        .filter(|i| !report.resolved.contains(i))
+        .collect();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry<'a>(name: &str, content: &'a [u8]) -> FileEntry<'a> {
+        FileEntry {
+            index: 0,
+            filename: Some(name.to_string()),
+            raw_name_bytes: None,
+            header_data: None,
+            content_meta: None,
+            content,
+            content_length: content.len(),
+            raw_content_data: content,
+            content_data_offset: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let json = br#"{"version": "1.2.3", "files": [
+            {"name": "fw/gimbal.upg", "size": 4, "crc32": "0x12345678"},
+            {"file": "esc.upg", "length": "10", "checksum": 305419896, "target": "ESC"},
+            {"unnamed": true}
+        ]}"#;
+        let manifest = parse_manifest(json).unwrap();
+        assert_eq!(manifest.version.as_deref(), Some("1.2.3"));
+        assert_eq!(manifest.files.len(), 2);
+        assert_eq!(manifest.files[0].crc32, Some(0x1234_5678));
+        assert_eq!(manifest.files[1].size, Some(10));
+        assert_eq!(manifest.files[1].crc32, Some(0x1234_5678));
+        assert_eq!(manifest.files[1].device.as_deref(), Some("ESC"));
+    }
+
+    #[test]
+    fn test_plain_json_is_not_a_manifest() {
+        assert!(parse_manifest(br#"{"files": [{"name": "a"}]}"#).is_none());
+        assert!(parse_manifest(br#"{"version": 2, "files": []}"#).is_none());
+        assert!(parse_manifest(br#"{"debug": true}"#).is_none());
+        assert!(parse_manifest(b"[1, 2]").is_none());
+    }
+
+    #[test]
+    fn test_check_entries_against_manifest() {
+        let crc = crc32fast::hash(b"good");
+        let json = format!(
+            r#"{{"version": 1, "files": [
+                {{"name": "a.bin", "size": 4, "crc32": "{:08x}"}},
+                {{"name": "b.bin", "size": 99}},
+                {{"name": "gone.bin", "size": 1}}
+            ]}}"#,
+            crc
+        );
+        let entries = [
+            entry("manifest.json", json.as_bytes()),
+            entry("a.bin", b"good"),
+            entry("b.bin", b"short"),
+            entry("extra.bin", b"?"),
+        ];
+
+        assert_eq!(detect_manifest(&entries).unwrap().index, 0);
+        let manifest = parse_manifest(json.as_bytes()).unwrap();
+        assert_eq!(manifest.check_entry(&entries[1]), Some(vec![]));
+        assert_eq!(
+            manifest.check_entry(&entries[2]),
+            Some(vec!["manifest declares 99 bytes, entry has 5".to_string()])
+        );
+        assert_eq!(manifest.check_entry(&entries[3]), None);
+        assert_eq!(manifest.missing(&entries), ["gone.bin"]);
+    }
+
+    #[test]
+    fn test_resolve_manifest_refs() {
+        let manifest: Value = serde_json::from_str(
+            r#"{"version": "1.2.3", "components": [
+                "fw/gimbal.upg",
+                {"path": "/app/esc.upg", "url": "https://example.com/esc.upg"},
+                "missing.bin",
+                "gimbal.upg",
+                "release notes"
+            ]}"#,
+        )
+        .unwrap();
+        let entries = [
+            entry("manifest.json", b"{}"),
+            entry("esc.upg", b"esc"),
+            entry("extra.bin", b"?"),
+            entry("gimbal.upg", b"gimbal"),
+        ];
+
+        let report = resolve_manifest_refs(&manifest, &entries);
+        assert_eq!(report.resolved, [3, 1]);
+        assert_eq!(report.missing, ["missing.bin"]);
+        assert_eq!(report.unreferenced, [0, 2]);
+        assert_eq!(report.extraction_order(), [3, 1, 0, 2]);
+    }
+}
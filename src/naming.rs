@@ -0,0 +1,110 @@
+use std::path::{Path, PathBuf};
+
+/// One step of a `--name-transform` pipeline, applied to output file names
+#[derive(Debug, Clone, PartialEq)]
+pub enum NameTransform {
+    Lowercase,
+    /// Spaces become underscores
+    ReplaceSpaces,
+    Prefix(String),
+}
+
+impl NameTransform {
+    /// Parse one step: `lowercase`, `replace-spaces` or `prefix:<str>`
+    pub fn parse(spec: &str) -> Result<NameTransform, String> {
+        match spec {
+            "lowercase" => Ok(NameTransform::Lowercase),
+            "replace-spaces" => Ok(NameTransform::ReplaceSpaces),
+            _ => match spec.strip_prefix("prefix:") {
+                Some(prefix) => Ok(NameTransform::Prefix(prefix.to_string())),
+                None => Err(format!(
+                    "unknown name transform '{}' (expected lowercase, replace-spaces or prefix:<str>)",
+                    spec
+                )),
+            },
+        }
+    }
+
+    pub fn apply(&self, name: &str) -> String {
+        match self {
+            NameTransform::Lowercase => name.to_lowercase(),
+            NameTransform::ReplaceSpaces => name.replace(' ', "_"),
+            NameTransform::Prefix(prefix) => format!("{}{}", prefix, name),
+        }
+    }
+}
+
+/// Parse a comma-separated list of steps, applied left to right
+pub fn parse_transforms(spec: &str) -> Result<Vec<NameTransform>, String> {
+    spec.split(',')
+        .map(|s| NameTransform::parse(s.trim()))
+        .collect()
+}
+
+/// Run a name through every step in order
+pub fn apply_transforms(transforms: &[NameTransform], name: &str) -> String {
+    transforms
+        .iter()
+        .fold(name.to_string(), |name, transform| transform.apply(&name))
+}
+
+/// Transform the file name of an output path, leaving its directories alone
+pub fn transform_path(transforms: &[NameTransform], path: &Path) -> PathBuf {
+    match path.file_name() {
+        Some(name) if !transforms.is_empty() => {
+            path.with_file_name(apply_transforms(transforms, &name.to_string_lossy()))
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+/// `--index-prefix` prefix for the `index`th of `count` entries: zero-padded to the width of
+/// the largest index, and at least two digits (`07_`, or `007_` in a container of 100+)
+pub fn index_prefix(index: usize, count: usize) -> String {
+    let width = count.saturating_sub(1).to_string().len().max(2);
+    format!("{:0width$}_", index, width = width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_transforms() {
+        assert_eq!(
+            parse_transforms("lowercase, prefix:fw_").unwrap(),
+            [
+                NameTransform::Lowercase,
+                NameTransform::Prefix("fw_".to_string())
+            ]
+        );
+        assert!(parse_transforms("uppercase").is_err());
+    }
+
+    #[test]
+    fn test_apply_transforms_in_order() {
+        let transforms = parse_transforms("replace-spaces,prefix:A-,lowercase").unwrap();
+        assert_eq!(
+            apply_transforms(&transforms, "Gimbal FW.bin"),
+            "a-gimbal_fw.bin"
+        );
+        assert_eq!(apply_transforms(&[], "Keep Me"), "Keep Me");
+    }
+
+    #[test]
+    fn test_index_prefix_width() {
+        assert_eq!(index_prefix(0, 3), "00_");
+        assert_eq!(index_prefix(7, 100), "07_");
+        assert_eq!(index_prefix(7, 101), "007_");
+        assert_eq!(index_prefix(1234, 2000), "1234_");
+    }
+
+    #[test]
+    fn test_transform_path_only_touches_file_name() {
+        let transforms = [NameTransform::ReplaceSpaces];
+        assert_eq!(
+            transform_path(&transforms, Path::new("My Dir/My File.txt")),
+            PathBuf::from("My Dir/My_File.txt")
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Absolute byte ranges of everything in a firmware image, for annotating it in a
+//! disassembler or drawing a byte map
+//!
+//! Ranges come from the in-memory tree: a node that borrows its bytes from the input has a
+//! place in it, while decompressed or decoded data (and anything nested inside) does not.
+
+use std::io::{self, Write};
+use std::ops::Range;
+
+use crate::display::csv_field;
+use crate::extract::ExtractedNode;
+use crate::file_types::{file_type_name, FileType};
+
+/// Why a region has no absolute range
+pub const NO_OFFSET_NOTE: &str = "no absolute offset (decompressed)";
+
+/// One node of the tree and where it sits in the input
+#[derive(Debug, Clone, PartialEq)]
+pub struct OffsetRegion {
+    /// Nesting path, starting with the input's name
+    pub path: String,
+    pub file_type: FileType,
+    /// Byte range in the input (end exclusive); None inside decompressed data
+    pub range: Option<Range<usize>>,
+    pub size: usize,
+}
+
+/// Where `data` sits in `buffer`, if it is a slice of it rather than a copy
+pub fn range_in(buffer: &[u8], data: &[u8]) -> Option<Range<usize>> {
+    let start = (data.as_ptr() as usize).checked_sub(buffer.as_ptr() as usize)?;
+    (start + data.len() <= buffer.len()).then(|| start..start + data.len())
+}
+
+/// Every node of an extracted tree, root first and then depth-first in physical order
+/// The root must borrow the whole input, as `extract_to_memory` returns it
+pub fn offset_map(root: &ExtractedNode) -> Vec<OffsetRegion> {
+    let mut regions = Vec::new();
+    collect_regions(root, &root.data, "", &mut regions);
+    regions
+}
+
+fn collect_regions(
+    node: &ExtractedNode,
+    input: &[u8],
+    prefix: &str,
+    regions: &mut Vec<OffsetRegion>,
+) {
+    let name = match &node.name {
+        Some(name) => name.clone(),
+        None => format!("<entry {}>", node.index),
+    };
+    let path = if prefix.is_empty() {
+        name
+    } else {
+        format!("{}/{}", prefix, name)
+    };
+    regions.push(OffsetRegion {
+        path: path.clone(),
+        file_type: node.file_type,
+        range: node.range_in(input),
+        size: node.data.len(),
+    });
+    for child in &node.children {
+        collect_regions(child, input, &path, regions);
+    }
+}
+
+/// The regions as a JSON array; regions without a range get null offsets and a note
+pub fn offset_map_json(regions: &[OffsetRegion]) -> serde_json::Value {
+    regions
+        .iter()
+        .map(|region| {
+            let mut value = serde_json::json!({
+                "path": region.path,
+                "type": file_type_name(&region.file_type),
+                "start": region.range.as_ref().map(|r| r.start),
+                "end": region.range.as_ref().map(|r| r.end),
+                "size": region.size,
+            });
+            if region.range.is_none() {
+                value["note"] = NO_OFFSET_NOTE.into();
+            }
+            value
+        })
+        .collect()
+}
+
+/// Write the regions as CSV, one row each; start and end are empty without a range
+pub fn write_offset_csv(regions: &[OffsetRegion], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "path,type,start,end,size,note")?;
+    for region in regions {
+        let (start, end, note) = match &region.range {
+            Some(range) => (range.start.to_string(), range.end.to_string(), ""),
+            None => (String::new(), String::new(), NO_OFFSET_NOTE),
+        };
+        writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            csv_field(&region.path),
+            csv_field(file_type_name(&region.file_type)),
+            start,
+            end,
+            region.size,
+            note
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extract::extract_to_memory;
+    use std::io::Cursor;
+    use zip::write::SimpleFileOptions;
+    use zip::CompressionMethod;
+
+    #[test]
+    fn test_offset_map_of_zip() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("stored.txt", stored).unwrap();
+        writer.write_all(b"kept in place").unwrap();
+        writer
+            .start_file("deflated.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&[b'x'; 200]).unwrap();
+        let zip = writer.finish().unwrap().into_inner();
+
+        let regions = offset_map(&extract_to_memory(&zip, Some("fw.zip")));
+        let at = zip.windows(13).position(|w| w == b"kept in place").unwrap();
+        assert_eq!(
+            regions,
+            [
+                OffsetRegion {
+                    path: "fw.zip".to_string(),
+                    file_type: FileType::Zip,
+                    range: Some(0..zip.len()),
+                    size: zip.len(),
+                },
+                OffsetRegion {
+                    path: "fw.zip/stored.txt".to_string(),
+                    file_type: FileType::Text,
+                    range: Some(at..at + 13),
+                    size: 13,
+                },
+                OffsetRegion {
+                    path: "fw.zip/deflated.txt".to_string(),
+                    file_type: FileType::Text,
+                    range: None,
+                    size: 200,
+                },
+            ]
+        );
+
+        let mut csv = Vec::new();
+        write_offset_csv(&regions[1..], &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            format!(
+                "path,type,start,end,size,note\n\
+                 fw.zip/stored.txt,Text,{},{},13,\n\
+                 fw.zip/deflated.txt,Text,,,200,{}\n",
+                at,
+                at + 13,
+                NO_OFFSET_NOTE
+            )
+        );
+
+        let json = offset_map_json(&regions);
+        assert_eq!(json[1]["start"], at);
+        assert!(json[2]["start"].is_null());
+        assert_eq!(json[2]["note"], NO_OFFSET_NOTE);
+    }
+}
@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use glob::Pattern;
+
+use crate::file_types::FileType;
+use crate::handlers::HandlerRegistry;
+use crate::naming::NameTransform;
+use crate::signatures::SignatureRegistry;
+
+/// Options controlling how a firmware tree is processed
+#[derive(Debug, Clone, Default)]
+pub struct ProcessOptions {
+    /// Scan the whole buffer for a ZIP's EOCD record instead of only the trailing 64 KiB window
+    pub full_eocd_scan: bool,
+    /// Turn every warning (truncation, missing tags, unreadable ZIPs, checksum
+    /// mismatches) into a hard error
+    pub strict: bool,
+    /// Worker threads used to decompress and hash ZIP members (0 or 1 means serial)
+    pub jobs: usize,
+    /// Compute each extracted ZIP member's SHA-256 and list it next to the member
+    pub hash_members: bool,
+    /// Write out (and recurse into) zero-length container entries instead of skipping them
+    pub include_empty: bool,
+    /// Entries parsed per container before giving up (None uses `DEFAULT_MAX_ENTRIES`)
+    pub max_entries: Option<usize>,
+    /// Leaf files written before extraction stops, however many more there are
+    pub max_files: Option<usize>,
+    /// Print the Shannon entropy of each container entry
+    pub entropy: bool,
+    /// Bruteforce common checksums against each container's meta and header fields
+    pub crack_meta: bool,
+    /// Print how often each distinct `header_data` value occurs in each container
+    pub header_histogram: bool,
+    /// Process files whose name matches a pattern as the given type instead of detecting it
+    /// Forcing `AutelContainer`, `Zip`, `Tar` or `Gzip` recurses into the file as that format
+    pub type_overrides: Vec<(Pattern, FileType)>,
+    /// Expected alignment of each container entry's `<filetransfer>` tag; misaligned
+    /// entries are reported
+    pub align: Option<usize>,
+    /// Open each ZIP on a worker thread first and give up on it if that takes longer than this
+    pub zip_open_timeout: Option<Duration>,
+    /// Password tried on encrypted ZIP members (traditional PKWARE or WinZip AES); without
+    /// one they are reported and skipped
+    pub zip_password: Option<String>,
+    /// Write files into per-device folders (`gimbal/`, `fcs/`, ... and `other/`) under the
+    /// output root instead of mirroring the container nesting
+    pub group_by_device: bool,
+    /// Write each container's entry list (with header/meta bytes) next to its extracted
+    /// files, so `pack_directory` can rebuild it
+    pub write_manifest: bool,
+    /// Write a `<name>.meta` file next to each container entry recording its index, raw
+    /// header/meta bytes and declared versus actual length
+    pub sidecar: bool,
+    /// Rewrites applied to each output file name before it is written
+    pub name_transforms: Vec<NameTransform>,
+    /// List printable ASCII runs of at least this many characters in device firmware images
+    pub strings_min_len: Option<usize>,
+    /// On a damaged container, resume scanning at each structural error instead of past it
+    pub recover: bool,
+    /// Leave an output file alone if it already exists with the size that would be written
+    pub skip_existing: bool,
+    /// Like `skip_existing`, but compare SHA-256 digests instead of sizes (implies `skip_existing`)
+    pub verify_existing: bool,
+    /// Read every file back after writing it and abort if it doesn't match the extracted data
+    pub verify_writes: bool,
+    /// Extra magic-byte signatures, checked after name overrides and before built-in detection
+    pub signatures: SignatureRegistry,
+    /// How extracted JSON files are shown in the report; on disk they keep their bytes
+    pub json_style: JsonStyle,
+    /// Write each container entry's raw content as-is and don't look inside it
+    pub no_recurse: bool,
+    /// Prefix each container entry's output name with its zero-padded position in the
+    /// container (`00_config.json`), so directory listings keep the physical order
+    pub index_prefix: bool,
+    /// Write bytes found after a ZIP's EOCD record (often an appended signature) to a
+    /// `<name>.trailer` file next to the ZIP
+    pub zip_trailer: bool,
+    /// When a file matches nothing, look this many bytes in for a known type behind a
+    /// framing byte, and process that payload too (0 turns this off)
+    pub framing_skip: usize,
+    /// Cross-check the files a container's manifest refers to against its entries, and
+    /// extract the entries in the order the manifest lists them
+    pub follow_json_refs: bool,
+    /// Per-type handlers used instead of the built-in processing
+    pub handlers: HandlerRegistry,
+}
+
+/// How extracted JSON is shown in the report; invalid JSON is never shown reformatted
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum JsonStyle {
+    /// The original text, untouched
+    Original,
+    /// Reformatted with two-space indentation and sorted keys
+    #[default]
+    Pretty,
+    /// All insignificant whitespace removed, with sorted keys
+    Minified,
+}
+
+impl JsonStyle {
+    pub fn from_name(name: &str) -> Option<JsonStyle> {
+        match name {
+            "original" => Some(JsonStyle::Original),
+            "pretty" => Some(JsonStyle::Pretty),
+            "minified" => Some(JsonStyle::Minified),
+            _ => None,
+        }
+    }
+}
+
+impl ProcessOptions {
+    /// The forced type for a file name, if any override pattern matches it
+    pub fn type_override(&self, filename: Option<&str>) -> Option<FileType> {
+        let name = filename?;
+        self.type_overrides
+            .iter()
+            .find(|(pattern, _)| pattern.matches(name))
+            .map(|&(_, file_type)| file_type)
+    }
+
+    /// The type a file is processed as without running built-in detection, if any
+    /// Name overrides win over registered signatures
+    pub fn forced_type(&self, data: &[u8], filename: Option<&str>) -> Option<FileType> {
+        self.type_override(filename)
+            .or_else(|| self.signatures.detect(data).map(|s| s.file_type))
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Component, Path};
+
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::tar::TarBuilder;
+
+/// A single archive that extracted files are written into instead of a directory tree
+pub enum ArchiveWriter<W: Write + io::Seek> {
+    Zip(Box<ZipWriter<W>>),
+    Tar(TarBuilder<W>),
+}
+
+impl ArchiveWriter<BufWriter<File>> {
+    /// Create an archive file, picking the format from its `.zip` or `.tar` extension
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        let is_zip = match extension.as_deref() {
+            Some("zip") => true,
+            Some("tar") => false,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "{}: output archive must end in .zip or .tar",
+                        path.display()
+                    ),
+                ))
+            }
+        };
+        let out = BufWriter::new(File::create(path)?);
+        Ok(if is_zip {
+            ArchiveWriter::Zip(Box::new(ZipWriter::new(out)))
+        } else {
+            ArchiveWriter::Tar(TarBuilder::new(out))
+        })
+    }
+}
+
+impl<W: Write + io::Seek> ArchiveWriter<W> {
+    /// Add a file under the given `/`-separated name
+    pub fn add(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        match self {
+            ArchiveWriter::Zip(zip) => {
+                let options =
+                    SimpleFileOptions::default().large_file(data.len() >= u32::MAX as usize);
+                zip.start_file(name, options).map_err(io::Error::other)?;
+                zip.write_all(data)
+            }
+            ArchiveWriter::Tar(tar) => tar.append(name, data),
+        }
+    }
+
+    /// Write the archive's closing structures
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            ArchiveWriter::Zip(zip) => zip.finish().map_err(io::Error::other)?.flush(),
+            ArchiveWriter::Tar(tar) => tar.finish().map(|_| ()),
+        }
+    }
+}
+
+/// Archive entry name for a nesting path, with `/` separators on every platform
+/// Root, `.` and `..` components are dropped, so no entry unpacks outside the archive's root
+pub fn archive_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+
+    #[test]
+    fn test_zip_archive_writer() {
+        let mut writer = ArchiveWriter::Zip(Box::new(ZipWriter::new(Cursor::new(Vec::new()))));
+        writer.add("fw/config.json", b"{}").unwrap();
+        writer.add("fw/pkg/a.txt", b"hello").unwrap();
+        let data = match writer {
+            ArchiveWriter::Zip(zip) => zip.finish().unwrap().into_inner(),
+            ArchiveWriter::Tar(_) => unreachable!(),
+        };
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+        let mut text = String::new();
+        archive
+            .by_name("fw/pkg/a.txt")
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "hello");
+        assert_eq!(archive.len(), 2);
+    }
+
+    #[test]
+    fn test_archive_name() {
+        assert_eq!(archive_name(Path::new("fw/pkg/a.txt")), "fw/pkg/a.txt");
+        assert_eq!(archive_name(Path::new("/../fw/./../a.txt")), "fw/a.txt");
+        assert!(ArchiveWriter::create(Path::new("/nonexistent/out.rar")).is_err());
+    }
+}
@@ -0,0 +1,233 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::file_entry::OwnedFileEntry;
+use crate::file_types::detect_file_type;
+
+/// Written next to a container's extracted entries with `--manifest`; read back by `pack_directory`
+pub const MANIFEST_FILE_NAME: &str = ".firmparse-manifest.json";
+
+/// Suffix of the `--sidecar` files written next to extracted entries; they aren't entries
+/// themselves, so packing without a manifest leaves them out
+pub const SIDECAR_SUFFIX: &str = ".meta";
+
+/// Serialize entries back into an Autel container
+/// Missing header/meta bytes are written as zeros and missing content as an empty entry;
+/// the declared length is always the length of the content written
+pub fn build_container(entries: &[OwnedFileEntry]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    for entry in entries {
+        let name = entry.filename.as_deref().unwrap_or("").as_bytes();
+        let content = entry.content.as_deref().unwrap_or(&[]);
+
+        buffer.extend_from_slice(b"\"<filetransfer>\"");
+        buffer.extend_from_slice(b"\"<fileinfo>\"");
+        buffer.extend_from_slice(&(name.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(&entry.header_data.unwrap_or_default());
+        buffer.extend_from_slice(name);
+
+        buffer.extend_from_slice(b"\"<filecontent>\"");
+        buffer.extend_from_slice(&(content.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(&entry.content_meta.unwrap_or_default());
+        buffer.extend_from_slice(content);
+    }
+    buffer
+}
+
+/// Rebuild a container from a directory of extracted entries
+/// With a manifest, its entries are packed in manifest order with their recorded
+/// header/meta bytes, reading each file's content from `dir/<filename>` (or the entry's `stored_as`); an entry whose
+/// file is gone is an error unless it was empty to begin with. Without one, every file
+/// under `dir` but `--sidecar` files is packed in path order, named by its path relative to `dir`
+pub fn pack_directory(dir: &Path) -> io::Result<Vec<u8>> {
+    let manifest_path = dir.join(MANIFEST_FILE_NAME);
+    let entries = if manifest_path.is_file() {
+        let manifest: Vec<OwnedFileEntry> = serde_json::from_slice(&fs::read(&manifest_path)?)?;
+        manifest
+            .into_iter()
+            .map(|entry| read_manifest_entry(dir, entry))
+            .collect::<io::Result<Vec<_>>>()?
+    } else {
+        let mut files = Vec::new();
+        collect_files(dir, dir, &mut files)?;
+        files.sort();
+        files
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let content = fs::read(dir.join(&name))?;
+                Ok(OwnedFileEntry {
+                    index,
+                    file_type: detect_file_type(&content, Some(&name)),
+                    header_data: None,
+                    content_meta: None,
+                    content_length: content.len(),
+                    size: content.len(),
+                    content_data_offset: 0,
+                    stored_as: None,
+                    filename: Some(name),
+                    content: Some(content),
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?
+    };
+    Ok(build_container(&entries))
+}
+
+/// Fill in a manifest entry's content from the extracted file
+fn read_manifest_entry(dir: &Path, mut entry: OwnedFileEntry) -> io::Result<OwnedFileEntry> {
+    let name = entry
+        .stored_as
+        .clone()
+        .or_else(|| entry.filename.clone())
+        .unwrap_or_default();
+    let content = match fs::read(dir.join(&name)) {
+        Ok(content) => content,
+        // Empty entries are skipped on extraction unless asked for
+        Err(e) if e.kind() == io::ErrorKind::NotFound && entry.size == 0 => Vec::new(),
+        Err(e) => {
+            return Err(io::Error::new(
+                e.kind(),
+                format!("entry {} ({}): {}", entry.index, name, e),
+            ))
+        }
+    };
+    entry.content = Some(content);
+    Ok(entry)
+}
+
+/// Relative paths (with `/` separators) of every file under `dir`, skipping the manifest
+/// and sidecars
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> io::Result<()> {
+    for dir_entry in fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n != MANIFEST_FILE_NAME && !n.ends_with(SIDECAR_SUFFIX))
+        {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            let parts: Vec<_> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect();
+            files.push(parts.join("/"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_types::FileType;
+    use crate::parser::parse_file_entries;
+
+    fn owned(name: &str, header: [u8; 4], meta: [u8; 4], content: &[u8]) -> OwnedFileEntry {
+        OwnedFileEntry {
+            index: 0,
+            filename: Some(name.to_string()),
+            file_type: FileType::Unknown,
+            header_data: Some(header),
+            content_meta: Some(meta),
+            content_length: content.len(),
+            size: content.len(),
+            content_data_offset: 0,
+            stored_as: None,
+            content: Some(content.to_vec()),
+        }
+    }
+
+    fn temp_dir(label: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("firmparse-{}-{}", label, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_build_container_roundtrip() {
+        let buffer = build_container(&[
+            owned("a.txt", [1, 2, 3, 4], [5, 6, 7, 8], b"hello"),
+            owned("b.bin", [0; 4], [9; 4], b"\"<filetransfer>\" inside"),
+        ]);
+
+        let entries = parse_file_entries(&buffer);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].filename.as_deref(), Some("a.txt"));
+        assert_eq!(entries[0].header_data, Some(&[1, 2, 3, 4]));
+        assert_eq!(entries[0].content_meta, Some(&[5, 6, 7, 8]));
+        assert_eq!(entries[0].content, b"hello");
+        assert_eq!(entries[1].content, b"\"<filetransfer>\" inside");
+    }
+
+    #[test]
+    fn test_pack_directory_with_manifest() {
+        let dir = temp_dir("pack-manifest");
+        let original = build_container(&[
+            owned("z.txt", [0xfd, 0xce, 0x69, 0x48], [1; 4], b"first"),
+            owned("a.txt", [0xfd, 0xce, 0x69, 0x48], [2; 4], b"second"),
+            owned("empty.bin", [0; 4], [3; 4], b""),
+        ]);
+        let manifest: Vec<OwnedFileEntry> = parse_file_entries(&original)
+            .iter()
+            .map(OwnedFileEntry::from)
+            .collect();
+        fs::write(
+            dir.join(MANIFEST_FILE_NAME),
+            serde_json::to_vec(&manifest).unwrap(),
+        )
+        .unwrap();
+        fs::write(dir.join("z.txt"), b"first").unwrap();
+        fs::write(dir.join("a.txt"), b"SECOND!").unwrap();
+
+        let packed = pack_directory(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let packed = packed.unwrap();
+
+        // Manifest order and header/meta bytes are kept, content comes from disk
+        let entries = parse_file_entries(&packed);
+        let names: Vec<_> = entries.iter().map(|e| e.filename.as_deref()).collect();
+        assert_eq!(names, [Some("z.txt"), Some("a.txt"), Some("empty.bin")]);
+        assert_eq!(entries[1].content, b"SECOND!");
+        assert_eq!(entries[1].content_meta, Some(&[2; 4]));
+        assert!(entries[2].content.is_empty());
+    }
+
+    #[test]
+    fn test_pack_directory_without_manifest() {
+        let dir = temp_dir("pack-plain");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub/inner.txt"), b"inner").unwrap();
+        fs::write(dir.join("config.json"), b"{}").unwrap();
+        fs::write(dir.join("config.json.meta"), b"index=0\n").unwrap();
+
+        let packed = pack_directory(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let packed = packed.unwrap();
+
+        let entries = parse_file_entries(&packed);
+        let names: Vec<_> = entries.iter().map(|e| e.filename.as_deref()).collect();
+        assert_eq!(names, [Some("config.json"), Some("sub/inner.txt")]);
+        assert_eq!(entries[0].header_data, Some(&[0; 4]));
+        assert_eq!(entries[1].content, b"inner");
+    }
+
+    #[test]
+    fn test_pack_directory_missing_file() {
+        let dir = temp_dir("pack-missing");
+        let manifest = [owned("gone.bin", [0; 4], [0; 4], b"data")];
+        fs::write(
+            dir.join(MANIFEST_FILE_NAME),
+            serde_json::to_vec(&manifest).unwrap(),
+        )
+        .unwrap();
+
+        let packed = pack_directory(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(packed.is_err());
+    }
+}
@@ -1,21 +1,97 @@
-use crate::file_entry::FileEntry;
+use crate::diagnostics::Diagnostic;
+use crate::file_entry::{Container, FileEntry, Gap, TagStyle};
+
+/// Entry limit per container, far above any legitimate firmware but bounding memory on
+/// hostile input
+pub const DEFAULT_MAX_ENTRIES: usize = 100_000;
+
+/// Size of the smallest possible container: one entry with an empty name and no content
+/// (`"<filetransfer>"`, `"<fileinfo>"`, 8 info bytes, `"<filecontent>"`, 8 content bytes)
+pub const MIN_CONTAINER_LEN: usize = 16 + 12 + 8 + 15 + 8;
+
+/// How far into a buffer the opening `<filetransfer>` tag may start, and how far past it the
+/// `<fileinfo>` tag may, for the buffer to be taken as a container
+pub const CONTAINER_TAG_WINDOW: usize = 100;
+
+const TRANSFER_TAG: &[u8] = b"\"<filetransfer>\"";
+const INFO_TAG: &[u8] = b"\"<fileinfo>\"";
+
+/// Tags also recognized without their quotes; any other bare `<...>` is left alone, since
+/// it is far more likely to be markup or binary noise than structure
+const BARE_TAGS: [&[u8]; 3] = [b"<filetransfer>", b"<fileinfo>", b"<filecontent>"];
+
+/// A tag without its quotes, if it has them: `"<fileinfo>"` and `<fileinfo>` both give
+/// `<fileinfo>`
+pub fn tag_name(tag: &[u8]) -> &[u8] {
+    tag.strip_prefix(b"\"")
+        .and_then(|t| t.strip_suffix(b"\""))
+        .unwrap_or(tag)
+}
+
+/// Whether a tag returned by `find_tag` is in its quoted or bare form
+pub fn tag_style(tag: &[u8]) -> TagStyle {
+    if tag.starts_with(b"\"") {
+        TagStyle::Quoted
+    } else {
+        TagStyle::Bare
+    }
+}
+
+/// Cheap check that a buffer plausibly is an Autel container, without parsing any entries:
+/// a `<filetransfer>` tag within the first `CONTAINER_TAG_WINDOW` bytes, followed by
+/// `<fileinfo>` as the next tag. A malformed first entry is passed over if a well-formed
+/// one still starts within the window. Only a bounded prefix of the buffer is looked at
+pub fn is_autel_container(buffer: &[u8]) -> bool {
+    find_container_start(buffer).is_some()
+}
+
+/// Offset of the `<filetransfer>` tag opening the container, if `is_autel_container` holds
+pub fn find_container_start(buffer: &[u8]) -> Option<usize> {
+    let head = &buffer[..buffer.len().min(CONTAINER_TAG_WINDOW + TRANSFER_TAG.len())];
+    let mut pos = 0;
+    while let Some(start) = find_transfer_tag(head, pos) {
+        if start > CONTAINER_TAG_WINDOW {
+            break;
+        }
+        let after = find_tag(head, start).map_or(start, |(_, tag)| start + tag.len());
+        let end = buffer
+            .len()
+            .min(after + CONTAINER_TAG_WINDOW + INFO_TAG.len());
+        if matches!(find_tag(&buffer[..end], after), Some((_, tag)) if tag_name(tag) == tag_name(INFO_TAG))
+        {
+            return Some(start);
+        }
+        pos = start + 1;
+    }
+    None
+}
 
 /// Find a quoted tag in the buffer starting from the given position
 /// Returns the position and the tag bytes (including quotes)
+/// The container tags in `BARE_TAGS` are also found without quotes, and returned as-is
+///
+/// The scan is a single forward pass that looks at each byte once, so it is O(n) however
+/// many stray `"<` the data holds. An opening `"<` that hasn't closed yet is superseded by
+/// the next one, which means the tag returned is the first `>"` and the `"<` nearest before
+/// it: noise ahead of a real tag can't swallow it. Returns None once the rest of the buffer
+/// has no `>"` after an opening `"<`.
 pub fn find_tag(buffer: &[u8], start: usize) -> Option<(usize, &'_ [u8])> {
-    let len = buffer.len();
-    let mut i = start;
-
-    while i < len.saturating_sub(3) {
-        if buffer[i] == b'"' && buffer[i + 1] == b'<' {
-            for j in i + 2..len.saturating_sub(1) {
-                if buffer[j] == b'>' && buffer.get(j + 1) == Some(&b'"') {
-                    return Some((i, &buffer[i..=j + 1]));
+    let mut open = None;
+
+    for i in start..buffer.len().saturating_sub(1) {
+        if buffer[i] == b'<' && (i == 0 || buffer[i - 1] != b'"') {
+            if let Some(bare) = BARE_TAGS.iter().find(|t| buffer[i..].starts_with(t)) {
+                return Some((i, &buffer[i..i + bare.len()]));
+            }
+        }
+        match (buffer[i], buffer[i + 1]) {
+            (b'"', b'<') => open = Some(i),
+            (b'>', b'"') => {
+                if let Some(tag_start) = open.filter(|&o| i >= o + 2) {
+                    return Some((tag_start, &buffer[tag_start..=i + 1]));
                 }
             }
-            i += 2;
-        } else {
-            i += 1;
+            _ => {}
         }
     }
 
@@ -32,75 +108,486 @@ pub fn extract_filename(info_data: &[u8]) -> (Option<String>, Option<&[u8; 4]>)
         u32::from_be_bytes([info_data[0], info_data[1], info_data[2], info_data[3]]) as usize;
     let header_bytes = info_data.get(4..8).and_then(|b| b.try_into().ok());
 
-    if info_data.len() < 8 + name_len {
-        return (None, header_bytes);
+    let name_bytes = match 8usize
+        .checked_add(name_len)
+        .and_then(|end| info_data.get(8..end))
+    {
+        Some(bytes) => bytes,
+        None => return (None, header_bytes),
+    };
+    let filename = decode_filename(name_bytes).map(|s| s.trim_matches('"').to_string());
+
+    (filename, header_bytes)
+}
+
+/// Get the raw (undecoded) filename bytes covered by the name length field
+pub fn raw_name_bytes(info_data: &[u8]) -> Option<&[u8]> {
+    let len_bytes: [u8; 4] = info_data.get(0..4)?.try_into().ok()?;
+    let name_len = u32::from_be_bytes(len_bytes) as usize;
+    info_data.get(8..8usize.checked_add(name_len)?)
+}
+
+/// What is surprising about a filename's raw bytes (as covered by its length field):
+/// quotes beyond one outer pair, control characters, or bytes that decode as neither UTF-8
+/// nor UTF-16. Empty for an ordinary name, quoted or not
+pub fn name_anomalies(raw: &[u8]) -> Vec<String> {
+    let Some(name) = decode_filename(raw) else {
+        return vec!["not valid UTF-8 or UTF-16".to_string()];
+    };
+    let mut problems = Vec::new();
+
+    let outer = if name.len() >= 2 && name.starts_with('"') && name.ends_with('"') {
+        2
+    } else {
+        0
+    };
+    let extra = name.matches('"').count() - outer;
+    if extra > 0 {
+        problems.push(format!(
+            "{} quote{} beyond the outer pair",
+            extra,
+            if extra == 1 { "" } else { "s" }
+        ));
+    }
+
+    let controls: Vec<String> = name
+        .chars()
+        .filter(|c| c.is_control())
+        .map(|c| format!("0x{:02x}", c as u32))
+        .collect();
+    if !controls.is_empty() {
+        problems.push(format!("control characters {}", controls.join(", ")));
+    }
+    problems
+}
+
+/// Which byte of each UTF-16 code unit is consistently zero, if any
+/// ASCII-range names encoded as UTF-16 show this interleaved null pattern
+fn utf16_null_pattern(bytes: &[u8]) -> Option<bool> {
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let pairs = bytes.chunks_exact(2);
+    let high_zero = pairs.clone().filter(|p| p[1] == 0 && p[0] != 0).count();
+    let low_zero = pairs.clone().filter(|p| p[0] == 0 && p[1] != 0).count();
+    let units = bytes.len() / 2;
+
+    // Allow a trailing NUL terminator unit
+    if high_zero > 0 && high_zero + 1 >= units && low_zero == 0 {
+        Some(true)
+    } else if low_zero > 0 && low_zero + 1 >= units && high_zero == 0 {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Byte order of a UTF-16 name that isn't all ASCII, such as `固件.bin`: true for LE
+/// Some NULs, all in the same half of each unit (a trailing NUL terminator aside)
+fn interleaved_nulls(bytes: &[u8]) -> Option<bool> {
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let mut units = bytes.chunks_exact(2).collect::<Vec<_>>();
+    while units.last().is_some_and(|u| u == &[0, 0]) {
+        units.pop();
+    }
+    let high_zero = units.iter().any(|u| u[1] == 0);
+    let low_zero = units.iter().any(|u| u[0] == 0);
+    match (high_zero, low_zero) {
+        (true, false) => Some(true),
+        (false, true) => Some(false),
+        _ => None,
     }
+}
 
-    let name_bytes = &info_data[8..8 + name_len];
-    let filename = std::str::from_utf8(name_bytes)
+/// Decode UTF-16 in the given byte order, dropping trailing NUL terminators
+fn decode_utf16(bytes: &[u8], little_endian: bool) -> Option<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|p| {
+            if little_endian {
+                u16::from_le_bytes([p[0], p[1]])
+            } else {
+                u16::from_be_bytes([p[0], p[1]])
+            }
+        })
+        .collect();
+    String::from_utf16(&units)
         .ok()
-        .map(|s| s.trim_matches('"').to_string());
+        .map(|s| s.trim_end_matches('\0').to_string())
+}
 
-    (filename, header_bytes)
+/// Decode filename bytes as UTF-8, or as UTF-16 only when a BOM or interleaved NULs say so
+pub fn decode_filename(bytes: &[u8]) -> Option<String> {
+    if let Some(rest) = bytes.strip_prefix(&[0xff, 0xfe]) {
+        return decode_utf16(rest, true);
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        return decode_utf16(rest, false);
+    }
+
+    if let Ok(s) = std::str::from_utf8(bytes) {
+        // UTF-16 ASCII names are also valid UTF-8; a NUL in every other byte gives them away
+        if let Some(little_endian) = utf16_null_pattern(bytes) {
+            if let Some(name) = decode_utf16(bytes, little_endian) {
+                return Some(name);
+            }
+        }
+        return Some(s.to_string());
+    }
+
+    decode_utf16(bytes, interleaved_nulls(bytes)?)
 }
 
 /// Parse all file entries from an Autel container buffer
+/// Entries are returned in the order they appear in the buffer
+/// Stops after `DEFAULT_MAX_ENTRIES` entries; use `parse_container` to also get the
+/// diagnostics saying so (and any other parse warnings)
 pub fn parse_file_entries(buffer: &[u8]) -> Vec<FileEntry<'_>> {
-    let mut entries = Vec::new();
-    let mut pos = 0;
+    let _span = tracing::debug_span!("parse_file_entries", size = buffer.len()).entered();
+    iter_file_entries(buffer).collect()
+}
 
-    while pos < buffer.len() {
-        let (start, tag_bytes) = match find_tag(buffer, pos) {
-            Some(res) => res,
-            None => break,
-        };
+/// Parse a container, also capturing any trailing bytes after the last entry
+pub fn parse_container(buffer: &[u8]) -> Container<'_> {
+    parse_container_with_max(buffer, DEFAULT_MAX_ENTRIES)
+}
 
-        let tag_str = match std::str::from_utf8(tag_bytes) {
-            Ok(s) => s.trim_matches('"'),
-            Err(_) => {
-                pos = start + 1;
-                continue;
-            }
-        };
+/// Like `parse_container`, but stop after `max_entries` entries
+pub fn parse_container_with_max(buffer: &[u8], max_entries: usize) -> Container<'_> {
+    collect_container(iter_file_entries(buffer).with_max_entries(max_entries))
+}
+
+/// Run a configured entry iterator to the end and gather everything it found
+pub fn collect_container(mut iter: FileEntries<'_>) -> Container<'_> {
+    let buffer = iter.buffer;
+    let _span = tracing::debug_span!("parse_container", size = buffer.len()).entered();
+    let entries: Vec<_> = iter.by_ref().collect();
+
+    // Bytes after a capped listing are unparsed entries, not a trailer
+    let trailer = entries
+        .last()
+        .filter(|_| !iter.cap_reached())
+        .map(|last| &buffer[last.end_offset().min(buffer.len())..])
+        .filter(|t| !t.is_empty());
+
+    tracing::debug!(
+        entries = entries.len(),
+        malformed = iter.malformed(),
+        gaps = iter.gaps.len(),
+        trailer = trailer.map_or(0, |t| t.len()),
+        "container parsed"
+    );
+    Container {
+        entries,
+        trailer,
+        malformed: iter.malformed(),
+        cap_reached: iter.cap_reached(),
+        diagnostics: iter.take_diagnostics(),
+        gaps: std::mem::take(&mut iter.gaps),
+        salvaged: iter.salvaged(),
+        tag_style: iter.tag_style(),
+    }
+}
+
+/// Lazily iterate over the file entries of an Autel container buffer
+pub fn iter_file_entries(buffer: &[u8]) -> FileEntries<'_> {
+    FileEntries {
+        buffer,
+        pos: 0,
+        index: 0,
+        malformed: 0,
+        max_entries: DEFAULT_MAX_ENTRIES,
+        cap_reached: false,
+        diagnostics: Vec::new(),
+        last_end: None,
+        gaps: Vec::new(),
+        recover: false,
+        damaged: false,
+        salvaged: 0,
+        quoted_tags: 0,
+        bare_tags: 0,
+    }
+}
+
+/// Iterator over file entries, yielding them in byte-offset order
+pub struct FileEntries<'a> {
+    buffer: &'a [u8],
+    pos: usize,
+    index: usize,
+    malformed: usize,
+    max_entries: usize,
+    cap_reached: bool,
+    diagnostics: Vec<Diagnostic>,
+    /// End of the last entry's content, until the next `<filetransfer>` tag is found
+    last_end: Option<usize>,
+    gaps: Vec<Gap>,
+    recover: bool,
+    /// A structural error has been seen
+    damaged: bool,
+    /// Entries yielded after the first structural error
+    salvaged: usize,
+    /// Tags accepted into an entry so far, by style
+    quoted_tags: usize,
+    bare_tags: usize,
+}
 
-        if tag_str != "<filetransfer>" {
-            pos = start + tag_bytes.len();
-            continue;
+impl FileEntries<'_> {
+    /// Stop after yielding `max_entries` entries
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// On a structural error, resume scanning at the point of failure instead of past it
+    /// A tag that broke an entry's sequence is rescanned, so a `<filetransfer>` there
+    /// still opens the next entry; a length running past the buffer is distrusted and
+    /// scanning resumes right after it
+    pub fn with_recovery(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+
+    /// Entries found after the first structural error (a malformed tag sequence or a
+    /// length past the end of the buffer)
+    pub fn salvaged(&self) -> usize {
+        self.salvaged
+    }
+
+    /// Whether iteration stopped at the entry cap with more entries left in the buffer
+    pub fn cap_reached(&self) -> bool {
+        self.cap_reached
+    }
+
+    /// Warnings raised by the entries parsed so far
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Take the warnings raised so far, leaving none behind
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Unparsed stretches found so far between one entry and the next
+    pub fn gaps(&self) -> &[Gap] {
+        &self.gaps
+    }
+
+    /// How the tags accepted into entries so far are written; None before the first
+    pub fn tag_style(&self) -> Option<TagStyle> {
+        match (self.quoted_tags, self.bare_tags) {
+            (0, 0) => None,
+            (_, 0) => Some(TagStyle::Quoted),
+            (0, _) => Some(TagStyle::Bare),
+            _ => Some(TagStyle::Mixed),
         }
+    }
 
-        let (info_start, info_tag_bytes) = match find_tag(buffer, start + tag_bytes.len()) {
-            Some(res) => res,
-            None => break,
-        };
-        let info_tag_str = std::str::from_utf8(info_tag_bytes)
-            .unwrap_or("")
-            .trim_matches('"');
-        if info_tag_str != "<fileinfo>" {
-            pos = info_start + info_tag_bytes.len();
-            continue;
+    /// Number of `<filetransfer>` tags seen so far that were not followed by the
+    /// expected `<fileinfo>`/`<filecontent>` tags and were skipped
+    pub fn malformed(&self) -> usize {
+        self.malformed
+    }
+}
+
+impl<'a> Iterator for FileEntries<'a> {
+    type Item = FileEntry<'a>;
+
+    fn next(&mut self) -> Option<FileEntry<'a>> {
+        let was_damaged = self.damaged;
+        let entry = self.next_entry()?;
+        if self.index >= self.max_entries {
+            self.diagnostics.push(Diagnostic::warning(
+                Some(entry.content_data_offset),
+                format!(
+                    "entry cap ({}) reached; remaining entries not parsed",
+                    self.max_entries
+                ),
+            ));
+            self.cap_reached = true;
+            self.pos = self.buffer.len();
+            return None;
+        }
+        self.index += 1;
+        tracing::debug!(
+            index = entry.index,
+            offset = entry.content_data_offset,
+            size = entry.content.len(),
+            declared = entry.content_length,
+            name = entry.filename.as_deref().unwrap_or("<unknown>"),
+            "container entry"
+        );
+        // An entry's own overlong length is its error, not one it was found after
+        let own_error = entry.content.len() < entry.content_length;
+        if was_damaged || (self.damaged && !own_error) {
+            self.salvaged += 1;
         }
+        Some(entry)
+    }
+}
 
-        let info_data_start = info_start + info_tag_bytes.len();
-        let next_tag_after_info = find_tag(buffer, info_data_start)
-            .map(|(i, _)| i)
-            .unwrap_or(buffer.len());
-        let info_data = &buffer[info_data_start..next_tag_after_info];
+/// Position within the `<filetransfer> → <fileinfo> → <filecontent>` tag grammar
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParseState {
+    /// Scanning for the `<filetransfer>` tag that opens an entry
+    SeekTransfer,
+    /// Inside an entry, expecting `<fileinfo>`
+    ExpectInfo,
+    /// File info parsed, expecting `<filecontent>`
+    ExpectContent,
+}
 
-        let (filename, header_data) = extract_filename(info_data);
+/// What a tag means in the current state
+#[derive(Debug, PartialEq)]
+enum Transition {
+    /// Move on to the next stage of the entry
+    Next(ParseState),
+    /// The tag completes an entry
+    Complete,
+    /// The tag is not relevant here; keep scanning past it
+    Skip,
+    /// The tag breaks the expected sequence; abandon the current entry
+    Malformed,
+}
 
-        let (content_start, content_tag_bytes) = match find_tag(buffer, next_tag_after_info) {
-            Some(res) => res,
-            None => break,
-        };
-        let content_tag_str = std::str::from_utf8(content_tag_bytes)
-            .unwrap_or("")
-            .trim_matches('"');
-        if content_tag_str != "<filecontent>" {
-            pos = content_start + content_tag_bytes.len();
-            continue;
+/// The tag grammar: optional tags can be accepted by mapping them to `Skip` in their state
+fn transition(state: ParseState, tag: &str) -> Transition {
+    match (state, tag) {
+        (ParseState::SeekTransfer, "<filetransfer>") => Transition::Next(ParseState::ExpectInfo),
+        (ParseState::SeekTransfer, _) => Transition::Skip,
+        (ParseState::ExpectInfo, "<fileinfo>") => Transition::Next(ParseState::ExpectContent),
+        (ParseState::ExpectContent, "<filecontent>") => Transition::Complete,
+        (ParseState::ExpectInfo, _) | (ParseState::ExpectContent, _) => Transition::Malformed,
+    }
+}
+
+/// Position of the next `<filetransfer>` tag at or after `start`
+fn find_transfer_tag(buffer: &[u8], start: usize) -> Option<usize> {
+    let mut pos = start;
+    while let Some((at, tag)) = find_tag(buffer, pos) {
+        if tag_name(tag) == tag_name(TRANSFER_TAG) {
+            return Some(at);
         }
+        pos = at + 1;
+    }
+    None
+}
 
-        let content_data_start = content_start + content_tag_bytes.len();
+/// Filename and header parsed from a `<fileinfo>` section
+type FileInfo<'a> = (Option<String>, Option<&'a [u8]>, Option<&'a [u8; 4]>);
+
+impl<'a> FileEntries<'a> {
+    /// Run the tag state machine from the current position until an entry completes
+    /// Every `<filetransfer>` missing its expected follow-up tags bumps `malformed`
+    fn next_entry(&mut self) -> Option<FileEntry<'a>> {
+        let buffer = self.buffer;
+        let mut state = ParseState::SeekTransfer;
+        let mut info: FileInfo<'a> = (None, None, None);
+
+        loop {
+            let (start, tag_bytes) = match find_tag(buffer, self.pos) {
+                Some(res) => res,
+                None => {
+                    if state != ParseState::SeekTransfer {
+                        self.malformed += 1;
+                    }
+                    self.pos = buffer.len();
+                    return None;
+                }
+            };
+            let after_tag = start + tag_bytes.len();
+
+            let tag_str = match std::str::from_utf8(tag_name(tag_bytes)) {
+                Ok(s) => s,
+                // Only resync byte-by-byte while looking for the start of an entry
+                Err(_) if state == ParseState::SeekTransfer => {
+                    self.pos = start + 1;
+                    continue;
+                }
+                Err(_) => "",
+            };
+
+            let transition = transition(state, tag_str);
+            if transition == Transition::Next(ParseState::ExpectInfo) {
+                if let Some(end) = self.last_end.take().filter(|&end| start > end) {
+                    self.gaps.push(Gap {
+                        offset: end,
+                        len: start - end,
+                    });
+                }
+            }
+
+            if matches!(transition, Transition::Next(_) | Transition::Complete) {
+                match tag_style(tag_bytes) {
+                    TagStyle::Bare => self.bare_tags += 1,
+                    _ => self.quoted_tags += 1,
+                }
+            }
+
+            match transition {
+                Transition::Skip => {
+                    self.pos = after_tag;
+                }
+                Transition::Malformed => {
+                    self.malformed += 1;
+                    self.damaged = true;
+                    state = ParseState::SeekTransfer;
+                    self.pos = if self.recover { start } else { after_tag };
+                }
+                Transition::Next(ParseState::ExpectContent) => {
+                    // The info section runs up to the next tag
+                    let next_tag = find_tag(buffer, after_tag)
+                        .map(|(i, _)| i)
+                        .unwrap_or(buffer.len());
+                    let info_data = &buffer[after_tag..next_tag];
+                    let (filename, header_data) = extract_filename(info_data);
+                    let raw_name = raw_name_bytes(info_data);
+                    if let Some(raw) = raw_name {
+                        let problems = name_anomalies(raw);
+                        if !problems.is_empty() {
+                            self.diagnostics.push(Diagnostic::warning(
+                                Some(after_tag + 8),
+                                format!(
+                                    "filename {:?}: {} (length field covers {} bytes, {} after trimming quotes)",
+                                    String::from_utf8_lossy(raw),
+                                    problems.join("; "),
+                                    raw.len(),
+                                    filename.as_ref().map_or(0, |n| n.len())
+                                ),
+                            ));
+                        }
+                    }
+                    info = (filename, raw_name, header_data);
+
+                    state = ParseState::ExpectContent;
+                    self.pos = next_tag;
+                }
+                Transition::Next(next) => {
+                    state = next;
+                    self.pos = after_tag;
+                }
+                Transition::Complete => {
+                    let (filename, raw_name, header_data) = info;
+                    return Some(self.read_content(after_tag, filename, raw_name, header_data));
+                }
+            }
+        }
+    }
+
+    /// Read a `<filecontent>` section starting at `content_data_start`, advancing past it
+    fn read_content(
+        &mut self,
+        content_data_start: usize,
+        filename: Option<String>,
+        raw_name: Option<&'a [u8]>,
+        header_data: Option<&'a [u8; 4]>,
+    ) -> FileEntry<'a> {
+        let buffer = self.buffer;
 
         // Read the declared content length first to properly skip over binary content
         // Format: 4 bytes (big-endian length) + 4 bytes (meta) + content
@@ -110,62 +597,96 @@ pub fn parse_file_entries(buffer: &[u8]) -> Vec<FileEntry<'_>> {
             &[u8],
             &[u8],
             usize,
-        ) = if buffer.len() >= content_data_start + 8 {
-            let len = u32::from_be_bytes([
-                buffer[content_data_start],
-                buffer[content_data_start + 1],
-                buffer[content_data_start + 2],
-                buffer[content_data_start + 3],
-            ]) as usize;
-            let meta = buffer
-                .get(content_data_start + 4..content_data_start + 8)
-                .and_then(|b| b.try_into().ok());
-
-            // Calculate where content should end based on declared length
-            let content_end = content_data_start + 8 + len;
-            let actual_content_end = content_end.min(buffer.len());
-
-            let content = &buffer[content_data_start + 8..actual_content_end];
-            let content_data = &buffer[content_data_start..actual_content_end];
-
-            if actual_content_end < content_end {
-                eprintln!(
-                    "Warning: Declared content length ({}) exceeds available data ({})",
-                    len,
-                    buffer.len().saturating_sub(content_data_start + 8)
-                );
+        ) = match content_data_start
+            .checked_add(8)
+            .and_then(|end| buffer.get(content_data_start..end))
+        {
+            Some(fields) => {
+                let len = u32::from_be_bytes([fields[0], fields[1], fields[2], fields[3]]) as usize;
+                let meta = fields[4..8].try_into().ok();
+
+                // Calculate where content should end based on declared length
+                let content_start = content_data_start + 8;
+                let content_end = content_start.saturating_add(len);
+                let actual_content_end = content_end.min(buffer.len());
+
+                let content = &buffer[content_start..actual_content_end];
+                let content_data = &buffer[content_data_start..actual_content_end];
+
+                if actual_content_end < content_end {
+                    self.damaged = true;
+                    self.diagnostics.push(Diagnostic::warning(
+                        Some(content_data_start),
+                        format!(
+                            "Declared content length ({}) exceeds available data ({})",
+                            len,
+                            buffer.len() - content_start
+                        ),
+                    ));
+                }
+
+                (len, meta, content, content_data, actual_content_end)
             }
+            None => {
+                // The length/meta header itself is cut off by the end of the buffer; keep the
+                // entry, flagged, and consume the stub so nothing after it is rescanned
+                let start = content_data_start.min(buffer.len());
+                self.diagnostics.push(Diagnostic::warning(
+                    Some(content_data_start),
+                    format!(
+                        "short content header ({} of 8 length/meta bytes)",
+                        buffer.len() - start
+                    ),
+                ));
+                (0, None, &[][..], &buffer[start..], buffer.len())
+            }
+        };
 
-            (len, meta, content, content_data, actual_content_end)
+        // A zero length with bytes before the next entry can't be trusted to locate it;
+        // resume at that entry's `<filetransfer>` tag and leave the stray bytes as a gap
+        // (bytes after the last entry are the container's trailer, not this case)
+        let mut next_entry_at = next_tag_after_content;
+        if content_length == 0 && content_meta.is_some() {
+            if let Some(next) = find_transfer_tag(buffer, next_tag_after_content)
+                .filter(|&next| next > next_tag_after_content)
+            {
+                self.diagnostics.push(Diagnostic::warning(
+                    Some(content_data_start),
+                    format!(
+                        "Declared content length is 0, but {} bytes follow before the next entry",
+                        next - next_tag_after_content
+                    ),
+                ));
+                next_entry_at = next;
+            }
+        }
+
+        // A truncated entry may be a corrupt length hiding the entries after it
+        self.pos = if self.recover && content_length > content.len() {
+            (content_data_start + 8).min(buffer.len())
         } else {
-            (
-                0,
-                None,
-                &[][..],
-                &buffer[content_data_start..content_data_start.min(buffer.len())],
-                content_data_start,
-            )
+            next_entry_at
         };
+        self.last_end = Some(self.pos.min(next_tag_after_content));
 
-        entries.push(FileEntry {
+        FileEntry {
+            index: self.index,
             filename,
+            raw_name_bytes: raw_name,
             header_data,
             content_length,
             content_meta,
             content,
             raw_content_data: content_data,
             content_data_offset: content_data_start,
-        });
-
-        pos = next_tag_after_content;
+        }
     }
-
-    entries
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::build_test_container;
 
     #[test]
     fn test_find_tag_basic() {
@@ -218,6 +739,44 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_find_tag_skips_unclosed_openers() {
+        // A stray "< before a real tag no longer pulls the noise into the tag
+        let buffer = b"\"<noise \"<filetransfer>\"";
+        assert_eq!(
+            find_tag(buffer, 0),
+            Some((8, b"\"<filetransfer>\"".as_slice()))
+        );
+        // The closing quote of one tag can open the next
+        let buffer = b"\"<\xff>\"<fileinfo>\"";
+        assert_eq!(find_tag(buffer, 0).map(|(pos, _)| pos), Some(0));
+        assert_eq!(find_tag(buffer, 1), Some((4, b"\"<fileinfo>\"".as_slice())));
+        assert_eq!(find_tag(b"\"<>\"", 0).map(|(pos, _)| pos), Some(0));
+        assert_eq!(find_tag(b"\"<", 5), None);
+    }
+
+    #[test]
+    fn test_find_tag_dense_noise_is_linear() {
+        // Every "< lacks a closing >", which used to cost a scan to the end per opener
+        let mut buffer = b"\"<".repeat(500_000);
+        buffer.extend_from_slice(b"\"<filetransfer>\"");
+        let (pos, tag) = find_tag(&buffer, 0).unwrap();
+        assert_eq!(pos, 1_000_000);
+        assert_eq!(tag, b"\"<filetransfer>\"");
+
+        assert_eq!(find_tag(&b"\"<".repeat(500_000), 0), None);
+    }
+
+    #[test]
+    fn test_find_tag_unclosed_angle_bracket_terminates() {
+        // A '>' that never gets its closing quote
+        let buffer = b"\"<tag> ".repeat(200_000);
+        assert_eq!(find_tag(&buffer, 0), None);
+
+        let entries = parse_file_entries(&buffer);
+        assert!(entries.is_empty());
+    }
+
     #[test]
     fn test_find_tag_nested_quotes() {
         let buffer = b"\"<outer>\"\"<inner>\"";
@@ -228,6 +787,36 @@ mod tests {
         assert_eq!(tag, b"\"<outer>\"");
     }
 
+    #[test]
+    fn test_is_autel_container() {
+        let entry = build_test_container("a.txt", b"data");
+        assert!(is_autel_container(&entry));
+        assert_eq!(find_container_start(&entry), Some(0));
+
+        let mut padded = vec![0u8; CONTAINER_TAG_WINDOW];
+        padded.extend_from_slice(&entry);
+        assert_eq!(find_container_start(&padded), Some(CONTAINER_TAG_WINDOW));
+        padded.insert(0, 0);
+        assert!(!is_autel_container(&padded));
+
+        // The tag sequence matters, not just the opening tag
+        assert!(!is_autel_container(
+            b"\"<filetransfer>\"\"<filecontent>\"\"<fileinfo>\""
+        ));
+        assert!(!is_autel_container(b"\"<filetransfer>\" no more tags"));
+        let mut skipped = b"\"<filetransfer>\"\"<bogus>\"".to_vec();
+        skipped.extend_from_slice(&entry);
+        assert_eq!(find_container_start(&skipped), Some(25));
+        assert!(!is_autel_container(b"\"<filetransfer"));
+        assert!(!is_autel_container(b""));
+
+        // <fileinfo> too far past the opening tag
+        let mut spread = TRANSFER_TAG.to_vec();
+        spread.extend_from_slice(&[0u8; CONTAINER_TAG_WINDOW + 1]);
+        spread.extend_from_slice(INFO_TAG);
+        assert!(!is_autel_container(&spread));
+    }
+
     #[test]
     fn test_extract_filename_basic() {
         // Format: 4 bytes name length (big-endian) + 4 bytes header + name
@@ -254,6 +843,49 @@ mod tests {
         assert_eq!(filename, Some("test.json".to_string()));
     }
 
+    #[test]
+    fn test_name_anomalies() {
+        assert!(name_anomalies(b"config.json").is_empty());
+        assert!(name_anomalies(b"\"config.json\"").is_empty());
+        assert!(name_anomalies(b"").is_empty());
+        assert_eq!(
+            name_anomalies(b"\"a\"b.json\""),
+            ["1 quote beyond the outer pair"]
+        );
+        assert_eq!(
+            name_anomalies(b"\"\"a.bin"),
+            ["2 quotes beyond the outer pair"]
+        );
+        assert_eq!(
+            name_anomalies(b"a\x07b\x00c"),
+            ["control characters 0x07, 0x00"]
+        );
+        assert_eq!(name_anomalies(b"\xff"), ["not valid UTF-8 or UTF-16"]);
+    }
+
+    #[test]
+    fn test_surprising_name_is_diagnosed() {
+        let buffer = build_test_container("\"cfg\"\".json\"", b"{}");
+        let mut iter = iter_file_entries(&buffer);
+        let entry = iter.next().unwrap();
+        assert_eq!(entry.raw_name_bytes, Some(b"\"cfg\"\".json\"".as_slice()));
+        assert_eq!(entry.filename.as_deref(), Some("cfg\"\".json"));
+        assert_eq!(
+            iter.diagnostics(),
+            [Diagnostic::warning(
+                Some(16 + 12 + 8),
+                "filename \"\\\"cfg\\\"\\\".json\\\"\": 2 quotes beyond the outer pair \
+                 (length field covers 12 bytes, 10 after trimming quotes)"
+                    .to_string()
+            )]
+        );
+
+        let buffer = build_test_container("ok.json", b"{}");
+        let mut iter = iter_file_entries(&buffer);
+        iter.next().unwrap();
+        assert!(iter.diagnostics().is_empty());
+    }
+
     #[test]
     fn test_extract_filename_too_short() {
         let data = vec![0x00, 0x00, 0x00, 0x05]; // only 4 bytes
@@ -287,30 +919,72 @@ mod tests {
         assert_eq!(header, Some(&[0xaa, 0xbb, 0xcc, 0xdd]));
     }
 
-    fn build_test_container(filename: &str, content: &[u8]) -> Vec<u8> {
-        let mut buffer = Vec::new();
+    fn utf16le(name: &str) -> Vec<u8> {
+        name.encode_utf16().flat_map(|u| u.to_le_bytes()).collect()
+    }
 
-        // "<filetransfer>" tag
-        buffer.extend_from_slice(b"\"<filetransfer>\"");
+    #[test]
+    fn test_extract_filename_utf16le() {
+        let name = utf16le("固件.bin");
+        let mut data = (name.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(&[0xfd, 0xce, 0x69, 0x48]);
+        data.extend_from_slice(&name);
 
-        // "<fileinfo>" tag
-        buffer.extend_from_slice(b"\"<fileinfo>\"");
+        let (filename, header) = extract_filename(&data);
+        assert_eq!(filename, Some("固件.bin".to_string()));
+        assert_eq!(header, Some(&[0xfd, 0xce, 0x69, 0x48]));
+        assert_eq!(raw_name_bytes(&data), Some(name.as_slice()));
+    }
 
-        // File info: 4 bytes name length + 4 bytes header + name
-        let name_bytes = filename.as_bytes();
-        buffer.extend_from_slice(&(name_bytes.len() as u32).to_be_bytes());
-        buffer.extend_from_slice(&[0xfd, 0xce, 0x69, 0x48]); // header data
-        buffer.extend_from_slice(name_bytes);
+    #[test]
+    fn test_decode_filename_utf16le_ascii() {
+        // ASCII-range UTF-16LE is also valid UTF-8 with embedded NULs
+        let mut name = utf16le("config.json");
+        name.extend_from_slice(&[0x00, 0x00]); // NUL terminator
+        assert_eq!(decode_filename(&name), Some("config.json".to_string()));
+    }
 
-        // "<filecontent>" tag
-        buffer.extend_from_slice(b"\"<filecontent>\"");
+    #[test]
+    fn test_decode_filename_utf16_bom() {
+        let mut le = vec![0xff, 0xfe];
+        le.extend_from_slice(&utf16le("a.txt"));
+        assert_eq!(decode_filename(&le), Some("a.txt".to_string()));
+
+        let mut be = vec![0xfe, 0xff];
+        be.extend("b.txt".encode_utf16().flat_map(|u| u.to_be_bytes()));
+        assert_eq!(decode_filename(&be), Some("b.txt".to_string()));
+    }
+
+    #[test]
+    fn test_decode_filename_utf16be_ascii() {
+        let name: Vec<u8> = "gps.bin"
+            .encode_utf16()
+            .flat_map(|u| u.to_be_bytes())
+            .collect();
+        assert_eq!(decode_filename(&name), Some("gps.bin".to_string()));
+    }
+
+    #[test]
+    fn test_decode_filename_prefers_utf8() {
+        assert_eq!(
+            decode_filename("名前.json".as_bytes()),
+            Some("名前.json".to_string())
+        );
+    }
 
-        // Content: 4 bytes length + 4 bytes meta + content
-        buffer.extend_from_slice(&(content.len() as u32).to_be_bytes());
-        buffer.extend_from_slice(&[0x33, 0xa8, 0x3b, 0x1f]); // content meta
-        buffer.extend_from_slice(content);
+    #[test]
+    fn test_decode_filename_undecodable() {
+        // Odd length and invalid UTF-8
+        assert_eq!(decode_filename(&[0xff, 0xff, 0xff]), None);
+        // Invalid UTF-8 without a BOM or interleaved NULs isn't guessed to be UTF-16
+        assert_eq!(decode_filename(&[0x80, 0x81, 0xfa, 0x56]), None);
+    }
 
-        buffer
+    #[test]
+    fn test_parse_keeps_raw_name_bytes() {
+        let buffer = build_test_container("test.txt", b"content");
+        let entries = parse_file_entries(&buffer);
+        assert_eq!(entries[0].raw_name_bytes, Some(&b"test.txt"[..]));
     }
 
     #[test]
@@ -345,6 +1019,162 @@ mod tests {
         assert_eq!(entries[2].filename, Some("file3.bin".to_string()));
     }
 
+    #[test]
+    fn test_parse_entries_have_sequential_indices() {
+        let mut buffer = build_test_container("b.txt", b"one");
+        buffer.extend_from_slice(&build_test_container("a.txt", b"two"));
+
+        let entries = parse_file_entries(&buffer);
+        assert_eq!(entries[0].index, 0);
+        assert_eq!(entries[1].index, 1);
+    }
+
+    #[test]
+    fn test_iter_matches_parse() {
+        let mut buffer = build_test_container("file1.txt", b"content one");
+        buffer.extend_from_slice(&build_test_container("file2.txt", b"content two"));
+
+        let parsed = parse_file_entries(&buffer);
+        let iterated: Vec<_> = iter_file_entries(&buffer).collect();
+        assert_eq!(parsed.len(), iterated.len());
+        for (p, i) in parsed.iter().zip(iterated.iter()) {
+            assert_eq!(p.filename, i.filename);
+            assert_eq!(p.content_data_offset, i.content_data_offset);
+        }
+    }
+
+    #[test]
+    fn test_iter_counts_malformed_entries() {
+        // <filetransfer> followed by the wrong tag, then a valid entry
+        let mut buffer = b"\"<filetransfer>\"\"<bogus>\"".to_vec();
+        buffer.extend_from_slice(&build_test_container("ok.txt", b"fine"));
+        // A trailing <filetransfer> with nothing after it
+        buffer.extend_from_slice(b"\"<filetransfer>\"");
+
+        let mut iter = iter_file_entries(&buffer);
+        let entries: Vec<_> = iter.by_ref().collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].filename, Some("ok.txt".to_string()));
+        assert_eq!(iter.malformed(), 2);
+    }
+
+    #[test]
+    fn test_transition_table() {
+        use ParseState::*;
+        assert_eq!(
+            transition(SeekTransfer, "<filetransfer>"),
+            Transition::Next(ExpectInfo)
+        );
+        assert_eq!(transition(SeekTransfer, "<other>"), Transition::Skip);
+        assert_eq!(
+            transition(ExpectInfo, "<fileinfo>"),
+            Transition::Next(ExpectContent)
+        );
+        assert_eq!(
+            transition(ExpectInfo, "<filecontent>"),
+            Transition::Malformed
+        );
+        assert_eq!(
+            transition(ExpectContent, "<filecontent>"),
+            Transition::Complete
+        );
+        assert_eq!(
+            transition(ExpectContent, "<fileinfo>"),
+            Transition::Malformed
+        );
+    }
+
+    #[test]
+    fn test_iter_is_fused_after_end() {
+        let mut buffer = build_test_container("ok.txt", b"fine");
+        buffer.extend_from_slice(b"\"<filetransfer>\"");
+
+        let mut iter = iter_file_entries(&buffer);
+        assert!(iter.next().is_some());
+        assert!(iter.next().is_none());
+        assert!(iter.next().is_none());
+        assert_eq!(iter.malformed(), 1);
+    }
+
+    #[test]
+    fn test_parse_container_trailer() {
+        let mut buffer = build_test_container("a.txt", b"content");
+        buffer.extend_from_slice(b"SIGNATURE-BYTES");
+
+        let container = parse_container(&buffer);
+        assert_eq!(container.entries.len(), 1);
+        assert_eq!(container.trailer, Some(&b"SIGNATURE-BYTES"[..]));
+        assert_eq!(container.malformed, 0);
+    }
+
+    #[test]
+    fn test_parse_container_gaps() {
+        let first = build_test_container("a.txt", b"content");
+        let mut buffer = first.clone();
+        buffer.extend_from_slice(&[0u8; 6]);
+        buffer.extend_from_slice(&build_test_container("b.txt", b"more"));
+        buffer.extend_from_slice(&build_test_container("c.txt", b"last"));
+
+        let container = parse_container(&buffer);
+        assert_eq!(container.entries.len(), 3);
+        assert_eq!(
+            container.gaps,
+            [Gap {
+                offset: first.len(),
+                len: 6
+            }]
+        );
+        assert_eq!(container.gap_bytes(), 6);
+        // Bytes before the first entry are a prefix, not a gap
+        let mut prefixed = b"HDR".to_vec();
+        prefixed.extend_from_slice(&first);
+        assert!(parse_container(&prefixed).gaps.is_empty());
+    }
+
+    #[test]
+    fn test_parse_container_entry_cap() {
+        let mut buffer = Vec::new();
+        for name in ["a", "b", "c"] {
+            buffer.extend_from_slice(&build_test_container(name, b"data"));
+        }
+
+        let capped = parse_container_with_max(&buffer, 2);
+        assert_eq!(capped.entries.len(), 2);
+        assert!(capped.cap_reached);
+        assert!(capped.trailer.is_none());
+
+        let exact = parse_container_with_max(&buffer, 3);
+        assert_eq!(exact.entries.len(), 3);
+        assert!(!exact.cap_reached);
+    }
+
+    #[test]
+    fn test_truncation_is_reported_as_diagnostic() {
+        let mut buffer = build_test_container("a.bin", &[0xAB; 100]);
+        buffer.truncate(buffer.len() - 40);
+
+        let container = parse_container(&buffer);
+        assert_eq!(container.entries.len(), 1);
+        assert_eq!(
+            container.diagnostics,
+            [Diagnostic::warning(
+                Some(container.entries[0].content_data_offset),
+                "Declared content length (100) exceeds available data (60)".to_string()
+            )]
+        );
+        assert_eq!(
+            container.diagnostics[0].to_string(),
+            "Warning: Declared content length (100) exceeds available data (60)"
+        );
+    }
+
+    #[test]
+    fn test_parse_container_no_trailer() {
+        let buffer = build_test_container("a.txt", b"content");
+        assert!(parse_container(&buffer).trailer.is_none());
+        assert!(parse_container(b"no entries here").trailer.is_none());
+    }
+
     #[test]
     fn test_parse_empty_content() {
         let buffer = build_test_container("empty.txt", b"");
@@ -354,6 +1184,170 @@ mod tests {
         assert_eq!(entries[0].content.len(), 0);
     }
 
+    #[test]
+    fn test_zero_length_with_trailing_bytes() {
+        let empty = build_test_container("empty.bin", b"");
+        let mut buffer = empty.clone();
+        // Stray bytes, including something tag-like, where the content should have been
+        buffer.extend_from_slice(b"STRAY\"<fileinfo>\"DATA");
+        buffer.extend_from_slice(&build_test_container("next.txt", b"next"));
+
+        let mut iter = iter_file_entries(&buffer);
+        let entries: Vec<_> = iter.by_ref().collect();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].content.is_empty());
+        assert_eq!(entries[1].filename.as_deref(), Some("next.txt"));
+        assert_eq!(entries[1].content, b"next");
+        assert_eq!(
+            iter.diagnostics(),
+            [Diagnostic::warning(
+                Some(empty.len() - 8),
+                "Declared content length is 0, but 21 bytes follow before the next entry"
+                    .to_string()
+            )]
+        );
+        assert_eq!(
+            iter.gaps(),
+            [Gap {
+                offset: empty.len(),
+                len: 21
+            }]
+        );
+        assert_eq!(iter.malformed(), 0);
+
+        // An empty entry followed only by a trailer is not flagged
+        let mut trailer = empty;
+        trailer.extend_from_slice(b"SIGNATURE");
+        let mut iter = iter_file_entries(&trailer);
+        assert_eq!(iter.by_ref().count(), 1);
+        assert!(iter.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_find_bare_tags() {
+        let buffer = b"xx<filetransfer><fileinfo>";
+        assert_eq!(find_tag(buffer, 0), Some((2, b"<filetransfer>".as_slice())));
+        assert_eq!(find_tag(buffer, 3), Some((16, b"<fileinfo>".as_slice())));
+        assert_eq!(tag_name(b"\"<fileinfo>\""), b"<fileinfo>");
+        assert_eq!(tag_style(b"<fileinfo>"), TagStyle::Bare);
+
+        // Only the container's own tags count when bare
+        assert_eq!(find_tag(b"<html><body>", 0), None);
+        // A quoted tag is still returned whole
+        assert_eq!(
+            find_tag(b"\"<fileinfo>\"", 0),
+            Some((0, b"\"<fileinfo>\"".as_slice()))
+        );
+    }
+
+    #[test]
+    fn test_parse_bare_tag_container() {
+        let quoted = build_test_container("config.json", b"{}");
+        // The header bytes aren't UTF-8, so drop the quotes around each tag byte-wise
+        let mut bare = quoted.clone();
+        for tag in BARE_TAGS {
+            let quoted_tag = [b"\"", tag, b"\""].concat();
+            let at = bare
+                .windows(quoted_tag.len())
+                .position(|w| w == quoted_tag)
+                .unwrap();
+            bare.splice(at..at + quoted_tag.len(), tag.iter().copied());
+        }
+        assert!(is_autel_container(&bare));
+
+        let container = parse_container(&bare);
+        assert_eq!(container.entries.len(), 1);
+        assert_eq!(
+            container.entries[0].filename.as_deref(),
+            Some("config.json")
+        );
+        assert_eq!(container.entries[0].content, b"{}");
+        assert_eq!(container.tag_style, Some(TagStyle::Bare));
+        assert_eq!(parse_container(&quoted).tag_style, Some(TagStyle::Quoted));
+
+        let mut mixed = quoted.clone();
+        mixed.extend_from_slice(&bare);
+        let container = parse_container(&mixed);
+        assert_eq!(container.entries.len(), 2);
+        assert_eq!(container.tag_style, Some(TagStyle::Mixed));
+        assert_eq!(parse_container(b"no tags").tag_style, None);
+    }
+
+    #[test]
+    fn test_parse_empty_and_tiny_input() {
+        for buffer in [&b""[..], b"\"", b"\"<", b"\"<filetransfer>\""] {
+            let container = parse_container(buffer);
+            assert!(container.entries.is_empty());
+            assert!(container.trailer.is_none());
+        }
+        assert_eq!(build_test_container("", b"").len(), MIN_CONTAINER_LEN);
+    }
+
+    #[test]
+    fn test_parse_short_content_header() {
+        let mut buffer = build_test_container("ok.txt", b"fine");
+        buffer.extend_from_slice(b"\"<filetransfer>\"\"<fileinfo>\"");
+        buffer.extend_from_slice(&5u32.to_be_bytes());
+        buffer.extend_from_slice(&[0u8; 4]);
+        buffer.extend_from_slice(b"short");
+        buffer.extend_from_slice(b"\"<filecontent>\"\x00\x00\x01");
+
+        let mut iter = iter_file_entries(&buffer);
+        let entries: Vec<_> = iter.by_ref().collect();
+        assert_eq!(entries.len(), 2);
+        let short = &entries[1];
+        assert_eq!(short.filename.as_deref(), Some("short"));
+        assert_eq!(short.content_length, 0);
+        assert!(short.content.is_empty());
+        assert!(short.content_meta.is_none());
+        assert_eq!(short.end_offset(), buffer.len());
+        assert!(iter.diagnostics()[0]
+            .message
+            .contains("short content header (3 of 8"));
+        assert!(iter.next().is_none());
+        assert!(parse_container(&buffer).trailer.is_none());
+    }
+
+    #[test]
+    fn test_recovery_rescans_breaking_tag() {
+        // A stray <filetransfer> with no <fileinfo> runs straight into a good entry
+        let mut buffer = b"\"<filetransfer>\"".to_vec();
+        buffer.extend_from_slice(&build_test_container("a.txt", b"aaa"));
+        buffer.extend_from_slice(&build_test_container("b.txt", b"bbb"));
+
+        let plain = collect_container(iter_file_entries(&buffer));
+        assert_eq!(plain.entries.len(), 1);
+        assert_eq!(plain.salvaged, 1);
+
+        let recovered = collect_container(iter_file_entries(&buffer).with_recovery(true));
+        let names: Vec<_> = recovered
+            .entries
+            .iter()
+            .map(|e| e.filename.as_deref().unwrap())
+            .collect();
+        assert_eq!(names, ["a.txt", "b.txt"]);
+        assert_eq!(recovered.malformed, 1);
+        assert_eq!(recovered.salvaged, 2);
+    }
+
+    #[test]
+    fn test_recovery_distrusts_overlong_length() {
+        let mut buffer = build_test_container("bad.bin", b"xx");
+        // Corrupt the declared length so it runs past the end of the buffer
+        let length_at = buffer.len() - 2 - 8;
+        buffer[length_at..length_at + 4].copy_from_slice(&0x00ff_ffffu32.to_be_bytes());
+        buffer.extend_from_slice(&build_test_container("good.txt", b"hello"));
+
+        let plain = parse_container(&buffer);
+        assert_eq!(plain.entries.len(), 1);
+
+        let recovered = collect_container(iter_file_entries(&buffer).with_recovery(true));
+        assert_eq!(recovered.entries.len(), 2);
+        assert_eq!(recovered.entries[1].filename.as_deref(), Some("good.txt"));
+        assert_eq!(recovered.entries[1].content, b"hello");
+        assert_eq!(recovered.salvaged, 1);
+    }
+
     #[test]
     fn test_parse_no_entries() {
         let buffer = b"random data without any tags";
@@ -382,6 +1376,40 @@ mod tests {
         assert!(entries[0].content.iter().all(|&b| b == 0xAB));
     }
 
+    #[test]
+    fn test_parse_max_declared_lengths() {
+        // Length fields of 0xffffffff must not overflow or index out of bounds
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(b"\"<filetransfer>\"\"<fileinfo>\"");
+        buffer.extend_from_slice(&[0xff; 4]); // name length
+        buffer.extend_from_slice(&[0xfd, 0xce, 0x69, 0x48]);
+        buffer.extend_from_slice(b"name");
+        buffer.extend_from_slice(b"\"<filecontent>\"");
+        buffer.extend_from_slice(&[0xff; 4]); // content length
+        buffer.extend_from_slice(&[0x33, 0xa8, 0x3b, 0x1f]);
+        buffer.extend_from_slice(b"tail");
+
+        let entries = parse_file_entries(&buffer);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].filename, None);
+        assert_eq!(entries[0].content_length, u32::MAX as usize);
+        assert_eq!(entries[0].content, b"tail");
+        assert_eq!(entries[0].end_offset(), buffer.len());
+    }
+
+    #[test]
+    fn test_parse_content_fields_cut_short() {
+        // The content tag is present but its length/meta fields are not
+        let mut buffer = build_test_container("a.txt", b"");
+        buffer.truncate(buffer.len() - 6);
+
+        let entries = parse_file_entries(&buffer);
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].content.is_empty());
+        assert_eq!(entries[0].content_meta, None);
+        assert_eq!(entries[0].end_offset(), buffer.len());
+    }
+
     #[test]
     fn test_parse_binary_content_with_tag_like_bytes() {
         // Content that looks like it might contain tags
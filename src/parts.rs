@@ -0,0 +1,150 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Digits a split part's numeric suffix needs (`fw.bin.001`), so version-like names such
+/// as `fw.v1.5` aren't taken for parts
+const MIN_PART_DIGITS: usize = 3;
+
+/// Split a part file name into the base it belongs to and its number:
+/// `fw.bin.002` → (`fw.bin`, 2)
+pub fn split_part_name(path: &str) -> Option<(&str, u32)> {
+    let (base, suffix) = path.rsplit_once('.')?;
+    if base.is_empty() || suffix.len() < MIN_PART_DIGITS {
+        return None;
+    }
+    if !suffix.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((base, suffix.parse().ok()?))
+}
+
+/// Check that part numbers run without gaps or repeats from 0 or 1
+pub fn check_part_sequence(numbers: &[u32]) -> Result<(), String> {
+    let mut sorted = numbers.to_vec();
+    sorted.sort_unstable();
+    let first = match sorted.first() {
+        Some(&n) if n <= 1 => n,
+        Some(&n) => {
+            return Err(format!(
+                "part {:03} is the first found; earlier parts are missing",
+                n
+            ))
+        }
+        None => return Err("no parts found".to_string()),
+    };
+    for (expected, &n) in (first..).zip(&sorted) {
+        if n < expected {
+            return Err(format!("part {:03} is given more than once", n));
+        }
+        if n > expected {
+            return Err(format!("part {:03} is missing", expected));
+        }
+    }
+    Ok(())
+}
+
+/// The part files of `base` (`<base>.001`, `<base>.002`, ...) next to it, in order
+/// Fails if there are none, or if the numbering has a gap
+pub fn find_parts(base: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir = match base.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let base_name = base.file_name().and_then(|n| n.to_str()).unwrap_or("");
+
+    let mut parts = Vec::new();
+    for dir_entry in fs::read_dir(dir)? {
+        let name = dir_entry?.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if let Some((part_base, number)) = split_part_name(name) {
+            if part_base == base_name {
+                parts.push((number, dir.join(name)));
+            }
+        }
+    }
+    if parts.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{}: no such file, and no parts of it", base.display()),
+        ));
+    }
+
+    let numbers: Vec<u32> = parts.iter().map(|&(n, _)| n).collect();
+    check_part_sequence(&numbers).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: {}", base.display(), e),
+        )
+    })?;
+    parts.sort();
+    Ok(parts.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Concatenate the parts, in the order given, into one buffer
+/// Returns the buffer and each part's size; every part but the last should be the same size
+/// as the first, since split tools cut at a fixed size
+pub fn read_parts(parts: &[PathBuf]) -> io::Result<(Vec<u8>, Vec<usize>)> {
+    let mut buffer = Vec::new();
+    let mut sizes = Vec::with_capacity(parts.len());
+    for part in parts {
+        let data = fs::read(part)?;
+        sizes.push(data.len());
+        buffer.extend_from_slice(&data);
+    }
+    Ok((buffer, sizes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_part_name() {
+        assert_eq!(split_part_name("fw.bin.002"), Some(("fw.bin", 2)));
+        assert_eq!(split_part_name("dir/EVO.bin.000"), Some(("dir/EVO.bin", 0)));
+        assert_eq!(split_part_name("fw.v1.5"), None);
+        assert_eq!(split_part_name("fw.bin"), None);
+        assert_eq!(split_part_name(".001"), None);
+    }
+
+    #[test]
+    fn test_check_part_sequence() {
+        assert!(check_part_sequence(&[2, 1, 3]).is_ok());
+        assert!(check_part_sequence(&[0, 1]).is_ok());
+        assert_eq!(
+            check_part_sequence(&[1, 3]).unwrap_err(),
+            "part 002 is missing"
+        );
+        assert_eq!(
+            check_part_sequence(&[1, 2, 2]).unwrap_err(),
+            "part 002 is given more than once"
+        );
+        assert!(check_part_sequence(&[2, 3]).is_err());
+        assert!(check_part_sequence(&[]).is_err());
+    }
+
+    #[test]
+    fn test_find_and_read_parts() {
+        let dir = std::env::temp_dir().join(format!("firmparse-parts-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // Written out of order, with an unrelated file alongside
+        fs::write(dir.join("fw.bin.002"), b"defg").unwrap();
+        fs::write(dir.join("fw.bin.001"), b"abc").unwrap();
+        fs::write(dir.join("other.bin.001"), b"xyz").unwrap();
+
+        let parts = find_parts(&dir.join("fw.bin")).unwrap();
+        let (buffer, sizes) = read_parts(&parts).unwrap();
+        assert_eq!(buffer, b"abcdefg");
+        assert_eq!(sizes, [3, 4]);
+
+        fs::write(dir.join("fw.bin.004"), b"h").unwrap();
+        let gap = find_parts(&dir.join("fw.bin")).unwrap_err();
+        assert!(gap.to_string().ends_with("part 003 is missing"));
+        assert!(find_parts(&dir.join("none.bin")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
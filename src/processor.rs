@@ -1,24 +1,757 @@
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, File};
-use std::io::{self, Cursor, Read, Write};
-use std::path::Path;
+use std::io::{self, Cursor, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
 use zip::read::ZipArchive;
+use zip::result::ZipResult;
+use zip::CompressionMethod;
 
-use crate::display::{print_hexdump_preview_indented, summarize_metadata};
-use crate::file_types::{detect_file_type, file_type_name, FileType};
-use crate::parser::parse_file_entries;
-use crate::zip_utils::slice_to_eocd;
+use crate::analysis::{
+    checksum_consensus, correlate_meta, header_histogram, infer_container_version,
+    is_likely_encrypted, merge_metadata_summary, metadata_summary, shannon_entropy,
+    MetadataSummary, HIGH_ENTROPY,
+};
+use crate::console::REPORT_TARGET;
+use crate::cpio;
+use crate::diagnostics::Severity;
+use crate::display::{
+    print_header_histogram, print_hexdump_preview_indented, print_metadata_summary,
+};
+use crate::file_entry::{FileEntry, OwnedFileEntry};
+use crate::file_types::{
+    detect_after_framing, detect_file_type, device_folder, file_type_name, framing_note,
+    upg_device_id, upg_device_label, FileType,
+};
+use crate::firmware::{parse_firmware_name, versions_agree, FirmwareInfo};
+use crate::gzip::{self, derive_decompressed_name, parse_gzip_header};
+use crate::handlers::Node;
+use crate::hash::{sha256, sha256_reader, to_hex};
+use crate::hexfile;
+use crate::image;
+use crate::integrity::{is_truncated, meta_matches_crc32, nested_length_mismatch};
+use crate::manifest::{detect_manifest, parse_manifest, resolve_manifest_refs};
+use crate::naming::{index_prefix, transform_path};
+use crate::offset_map::{range_in, OffsetRegion};
+use crate::options::{JsonStyle, ProcessOptions};
+use crate::out_archive::archive_name;
+use crate::pack::{MANIFEST_FILE_NAME, SIDECAR_SUFFIX};
+use crate::parser::{
+    collect_container, find_container_start, find_tag, iter_file_entries, parse_file_entries,
+    DEFAULT_MAX_ENTRIES,
+};
+use crate::report;
+use crate::squashfs;
+use crate::strings::{ascii_strings, lossy_text};
+use crate::tar;
+use crate::timestamp::{TimeRange, Timestamp};
+use crate::zip_utils::{
+    check_central_directory, dos_timestamp, find_eocd_with, open_zip_with_timeout,
+    scan_local_headers, EocdError,
+};
 
-/// Process a file based on its detected type
+/// Gaps between container entries listed individually before the rest are summarized
+const MAX_GAPS_LISTED: usize = 10;
+
+/// An artifact produced by the traversal, handed to the extraction callback
+#[derive(Debug)]
+pub struct ExtractedFile<'a> {
+    /// Nesting path relative to the output root (e.g. `fw/pkg/config.json`)
+    pub path: PathBuf,
+    pub file_type: FileType,
+    pub data: &'a [u8],
+    /// Subsystem the file belongs to, from a manifest or its type, if known
+    pub device: Option<String>,
+}
+
+impl ExtractedFile<'_> {
+    /// A flat JSON description of the file, for `--format jsonl`
+    /// The nesting path carries the structure, so the tree can be rebuilt downstream
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut json = serde_json::json!({
+            "path": archive_name(&self.path),
+            "type": file_type_name(&self.file_type),
+            "size": self.data.len(),
+            "sha256": to_hex(&sha256(self.data)),
+        });
+        if let Some(device) = &self.device {
+            json["device"] = device.as_str().into();
+        }
+        json
+    }
+}
+
+/// The entry missing the most bytes to truncation, and where its content should have ended
+struct WorstTruncation {
+    path: PathBuf,
+    missing: usize,
+    declared_end: usize,
+    buffer_len: usize,
+}
+
+/// Count and total size of every file seen, per detected type
+pub type TypeTotals = BTreeMap<FileType, (usize, u64)>;
+
+/// State threaded through the recursive traversal
+pub struct Context<'s> {
+    pub options: &'s ProcessOptions,
+    on_file: &'s mut dyn FnMut(&ExtractedFile) -> io::Result<()>,
+    type_totals: TypeTotals,
+    /// Device folder per output path, collected from manifests seen so far
+    /// Keyed by path rather than name so a manifest only speaks for its own container
+    device_hints: HashMap<PathBuf, String>,
+    /// SHA-256 of every container buffer recursed into so far
+    processed: HashSet<[u8; 32]>,
+    /// gzip and ZIP modification times seen so far, hinting at the build date
+    build_times: TimeRange,
+    /// ZIP members whose contents don't match their stored CRC32
+    crc_mismatches: usize,
+    /// Encrypted ZIP members that could not be decrypted
+    encrypted_members: usize,
+    /// Container entries cut short, and the declared bytes they're missing in total
+    truncated_entries: usize,
+    truncated_bytes: u64,
+    worst_truncation: Option<WorstTruncation>,
+    /// Container entries failing their meta CRC32 or their manifest declaration
+    checksum_failures: usize,
+    /// Size of the top-level input
+    input_bytes: u64,
+    /// Model and version from the input's file name, checked against manifest versions
+    firmware: Option<FirmwareInfo>,
+    /// Leaf files handed to the callback, counted against `max_files`
+    leaf_files: usize,
+    /// `max_files` was hit; nothing more is written or recursed into
+    file_limit_reached: bool,
+    /// Header/meta pairs of every container entry seen so far
+    metadata_summary: MetadataSummary,
+    /// The top-level input, which emitted slices are located in for the offset map
+    input: &'s [u8],
+    /// Where each emitted file sits in `input`, in emission order
+    regions: Vec<OffsetRegion>,
+    /// Content length the enclosing entry declares for the container about to be processed,
+    /// checked against its nested entries once they're parsed
+    declared_len: Option<usize>,
+}
+
+impl<'s> Context<'s> {
+    pub fn new(
+        options: &'s ProcessOptions,
+        on_file: &'s mut dyn FnMut(&ExtractedFile) -> io::Result<()>,
+    ) -> Self {
+        Context {
+            options,
+            on_file,
+            type_totals: TypeTotals::new(),
+            device_hints: HashMap::new(),
+            processed: HashSet::new(),
+            build_times: TimeRange::default(),
+            crc_mismatches: 0,
+            encrypted_members: 0,
+            truncated_entries: 0,
+            truncated_bytes: 0,
+            worst_truncation: None,
+            checksum_failures: 0,
+            input_bytes: 0,
+            firmware: None,
+            leaf_files: 0,
+            file_limit_reached: false,
+            metadata_summary: MetadataSummary::new(),
+            input: &[],
+            regions: Vec::new(),
+            declared_len: None,
+        }
+    }
+
+    /// Add a truncated container entry to the end-of-run summary
+    fn record_truncation(&mut self, entry: &FileEntry, path: PathBuf, buffer_len: usize) {
+        let missing = entry.missing_bytes();
+        self.truncated_entries += 1;
+        self.truncated_bytes += missing as u64;
+        if self
+            .worst_truncation
+            .as_ref()
+            .is_none_or(|worst| missing > worst.missing)
+        {
+            self.worst_truncation = Some(WorstTruncation {
+                path,
+                missing,
+                declared_end: entry.declared_end(),
+                buffer_len,
+            });
+        }
+    }
+
+    /// Add a file to the per-type totals
+    fn record(&mut self, file_type: FileType, size: usize) {
+        let totals = self.type_totals.entry(file_type).or_insert((0, 0));
+        totals.0 += 1;
+        totals.1 += size as u64;
+    }
+
+    /// Print the closing stats report and hand back the per-type totals for the caller to
+    /// show or keep
+    fn finish(self) -> TypeTotals {
+        if self.build_times.count > 0 {
+            report!("Build time range: {}", self.build_times);
+        }
+        if self.crc_mismatches > 0 {
+            report!("ZIP members failing their CRC32: {}", self.crc_mismatches);
+        }
+        if self.encrypted_members > 0 {
+            report!(
+                "Encrypted ZIP members not extracted: {}",
+                self.encrypted_members
+            );
+        }
+        if let Some(worst) = &self.worst_truncation {
+            report!(
+                "Truncated entries: {}, {} declared bytes missing in total",
+                self.truncated_entries,
+                self.truncated_bytes
+            );
+            report!(
+                "Worst: {} is missing {} bytes; its content would have ended at offset {:#x}, {} bytes past the end of its {}-byte container",
+                archive_name(&worst.path),
+                worst.missing,
+                worst.declared_end,
+                worst.declared_end - worst.buffer_len,
+                worst.buffer_len
+            );
+        }
+        self.type_totals
+    }
+
+    /// In strict mode, turn a warning into a hard error
+    pub fn fail_if_strict(&self, message: &str) -> io::Result<()> {
+        tracing::warn!(target: REPORT_TARGET, strict = self.options.strict, "{}", message);
+        if self.options.strict {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        Ok(())
+    }
+
+    /// Hand an artifact to the callback
+    /// Once `max_files` leaf files have been handed over, nothing more is
+    pub fn emit(&mut self, path: PathBuf, file_type: FileType, data: &[u8]) -> io::Result<()> {
+        if self.file_limit_reached {
+            return Ok(());
+        }
+        if !recurses_into(file_type) || self.options.no_recurse {
+            if let Some(max) = self.options.max_files.filter(|&max| self.leaf_files >= max) {
+                self.file_limit_reached = true;
+                tracing::warn!(target: REPORT_TARGET, max_files = max, "file limit reached");
+                report!("File limit ({}) reached, stopping.", max);
+                return Ok(());
+            }
+            self.leaf_files += 1;
+        }
+        // The manifest is written by us, not found in the input
+        if path.file_name() != Some(MANIFEST_FILE_NAME.as_ref()) {
+            self.regions.push(OffsetRegion {
+                path: archive_name(&path),
+                file_type,
+                range: range_in(self.input, data),
+                size: data.len(),
+            });
+        }
+        self.hand_over(path, file_type, data)
+    }
+
+    /// Hand a `--sidecar` record to the callback
+    /// Sidecars describe an entry rather than being one, so they don't count toward
+    /// `max_files`; none is written once the limit leaves no room for its entry
+    pub fn emit_sidecar(&mut self, path: PathBuf, data: &[u8]) -> io::Result<()> {
+        let full = self
+            .options
+            .max_files
+            .is_some_and(|max| self.leaf_files >= max);
+        if self.file_limit_reached || full {
+            return Ok(());
+        }
+        self.hand_over(path, FileType::Text, data)
+    }
+
+    fn hand_over(&mut self, path: PathBuf, file_type: FileType, data: &[u8]) -> io::Result<()> {
+        tracing::debug!(
+            path = %path.display(),
+            file_type = file_type_name(&file_type),
+            size = data.len(),
+            "file extracted"
+        );
+
+        // A manifest's word wins over what the type suggests
+        let device = self
+            .device_hints
+            .get(&path)
+            .cloned()
+            .or_else(|| device_folder(file_type).map(str::to_string));
+        (self.on_file)(&ExtractedFile {
+            path,
+            file_type,
+            data,
+            device,
+        })
+    }
+}
+
+/// Whether files of this type are opened and their contents processed in turn
+/// Everything else is a leaf of the extracted tree
+pub fn recurses_into(file_type: FileType) -> bool {
+    matches!(
+        file_type,
+        FileType::AutelContainer | FileType::Zip | FileType::Tar | FileType::Cpio | FileType::Gzip
+    )
+}
+
+/// Name an entry's content is written under; unnamed entries are numbered
+fn entry_file_name(entry: &FileEntry) -> String {
+    match &entry.filename {
+        Some(name) => name.clone(),
+        None => format!("entry_{}.bin", entry.index),
+    }
+}
+
+/// `--sidecar` record of the entry fields its extracted content doesn't carry
+/// Header and meta are hex, empty when the entry has none
+fn sidecar(entry: &FileEntry) -> String {
+    format!(
+        "index={}\nfilename={}\nheader={}\nmeta={}\ndeclared_length={}\nactual_length={}\n",
+        entry.index,
+        entry.filename.as_deref().unwrap_or(""),
+        entry.header_data.map(|h| to_hex(h)).unwrap_or_default(),
+        entry.content_meta.map(|m| to_hex(m)).unwrap_or_default(),
+        entry.content_length,
+        entry.content.len()
+    )
+}
+
+/// A JSON file as the report shows it, in the `--json-style` asked for
+fn render_json(style: JsonStyle, text: &str, json: &serde_json::Value) -> String {
+    let rendered = match style {
+        JsonStyle::Original => return text.to_string(),
+        JsonStyle::Pretty => serde_json::to_string_pretty(json),
+        JsonStyle::Minified => serde_json::to_string(json),
+    };
+    rendered.unwrap_or_else(|_| text.to_string())
+}
+
+/// Write a file to disk, creating any missing parent directories
+/// A named pipe already at `path` is written into instead of replaced, see `open_output`
+pub fn write_output(path: &Path, data: &[u8]) -> io::Result<()> {
+    if !is_fifo(path) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    let mut out_file = open_output(path)?;
+    out_file.write_all(data).map_err(|e| pipe_error(path, e))
+}
+
+/// Whether `path` is an existing named pipe (FIFO); always false off Unix
+pub fn is_fifo(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        fs::metadata(path).is_ok_and(|meta| meta.file_type().is_fifo())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// Open an output file for writing, truncating or creating a regular file
+/// Opening a FIFO blocks until another process opens it for reading, so say so first
+pub fn open_output(path: &Path) -> io::Result<File> {
+    if is_fifo(path) {
+        tracing::warn!(
+            path = %path.display(),
+            "{} is a named pipe; waiting for a reader to open it",
+            path.display()
+        );
+        return fs::OpenOptions::new().write(true).open(path);
+    }
+    File::create(path)
+}
+
+/// Explain a write that failed because the reading end of a pipe went away
+fn pipe_error(path: &Path, e: io::Error) -> io::Error {
+    if e.kind() == io::ErrorKind::BrokenPipe {
+        return io::Error::new(
+            e.kind(),
+            format!(
+                "reader of {} closed before all data was written",
+                path.display()
+            ),
+        );
+    }
+    e
+}
+
+/// Folder name for a device as a manifest spells it (`"Gimbal V2"` → `gimbal_v2`)
+fn device_dir_name(device: &str) -> String {
+    device
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Where a file goes when output is grouped by device: `<root>/<device>/<file name>`
+/// under the same top-level directory, with unattributed files in `other`
+/// A name already used in that folder keeps its nesting path, then gets a `~N` suffix
+fn device_path(file: &ExtractedFile, used: &mut HashSet<PathBuf>) -> PathBuf {
+    let mut components = file.path.components();
+    let root = match components.next() {
+        Some(root) => PathBuf::from(root.as_os_str()),
+        None => return file.path.clone(),
+    };
+    let nested = components.as_path();
+    let folder = root.join(file.device.as_deref().unwrap_or("other"));
+
+    let flat = folder.join(file.path.file_name().unwrap_or_default());
+    let mut path = if used.contains(&flat) {
+        folder.join(nested)
+    } else {
+        flat
+    };
+    let base = path.clone();
+    for n in 1.. {
+        if !used.contains(&path) {
+            break;
+        }
+        path = base.with_file_name(format!(
+            "{}~{}",
+            base.file_name().unwrap_or_default().to_string_lossy(),
+            n
+        ));
+    }
+    used.insert(path.clone());
+    path
+}
+
+/// Whether `path` already holds exactly `data`, so writing it again can be skipped
+/// Compares sizes only, unless `verify` asks for a SHA-256 comparison
+fn existing_matches(path: &Path, data: &[u8], verify: bool) -> bool {
+    match fs::metadata(path) {
+        Ok(meta) if meta.is_file() && meta.len() == data.len() as u64 => {
+            !verify
+                || File::open(path)
+                    .and_then(sha256_reader)
+                    .is_ok_and(|digest| digest == sha256(data))
+        }
+        _ => false,
+    }
+}
+
+/// Re-read a file just written and check it holds exactly `data`
+/// A mismatch is an `InvalidData` error; pipes can't be read back and are not checked
+pub fn verify_written(path: &Path, data: &[u8]) -> io::Result<()> {
+    if is_fifo(path) {
+        return Ok(());
+    }
+    let on_disk = File::open(path).and_then(sha256_reader)?;
+    let expected = sha256(data);
+    if on_disk != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} does not match what was written to it (sha256 {} on disk, {} expected)",
+                path.display(),
+                to_hex(&on_disk),
+                to_hex(&expected)
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Counters collected while extracting to disk
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ProcessStats {
+    pub files_written: usize,
+    /// Files left alone because an identical copy was already on disk
+    pub files_skipped: usize,
+    /// Written files read back and found identical, with `verify_writes`
+    pub files_verified: usize,
+    /// Files that could not be written; extraction carries on past them
+    pub write_failures: usize,
+    /// ZIP members whose contents don't match their stored CRC32
+    pub crc_mismatches: usize,
+    /// Encrypted ZIP members skipped for want of a (correct) password
+    pub encrypted_members: usize,
+    /// Container entries whose declared length runs past the end of the data
+    pub truncated_entries: usize,
+    /// Declared content bytes missing from truncated container entries
+    pub truncated_bytes: u64,
+    /// Container entries failing their meta CRC32 or their manifest declaration
+    pub checksum_failures: usize,
+    /// Size of the top-level inputs
+    pub input_bytes: u64,
+    /// Extraction stopped early at `max_files`
+    pub file_limit_reached: bool,
+    pub type_totals: TypeTotals,
+    /// Header/meta pairs of every container entry, by extension, for `--summary-csv`
+    pub metadata_summary: MetadataSummary,
+    /// The input and every file extracted from it, with where it sits, for `--offset-map`
+    pub regions: Vec<OffsetRegion>,
+}
+
+impl ProcessStats {
+    /// The stats as one JSON object, for `--stats-json`
+    /// Per-type counts are keyed by type name
+    pub fn to_json(&self) -> serde_json::Value {
+        let types: serde_json::Map<String, serde_json::Value> = self
+            .type_totals
+            .iter()
+            .map(|(file_type, &(count, bytes))| {
+                (
+                    file_type_name(file_type).to_string(),
+                    serde_json::json!({ "count": count, "bytes": bytes }),
+                )
+            })
+            .collect();
+        serde_json::json!({
+            "input_bytes": self.input_bytes,
+            "files": self.type_totals.values().map(|&(count, _)| count).sum::<usize>(),
+            "files_written": self.files_written,
+            "files_skipped": self.files_skipped,
+            "files_verified": self.files_verified,
+            "write_failures": self.write_failures,
+            "truncated_entries": self.truncated_entries,
+            "truncated_bytes": self.truncated_bytes,
+            "checksum_failures": self.checksum_failures,
+            "zip_crc_mismatches": self.crc_mismatches,
+            "zip_encrypted_members": self.encrypted_members,
+            "file_limit_reached": self.file_limit_reached,
+            "types": types,
+        })
+    }
+
+    /// Fold another run's stats into these, e.g. to total up a batch
+    pub fn merge(&mut self, other: ProcessStats) {
+        self.files_written += other.files_written;
+        self.files_skipped += other.files_skipped;
+        self.files_verified += other.files_verified;
+        self.write_failures += other.write_failures;
+        self.crc_mismatches += other.crc_mismatches;
+        self.encrypted_members += other.encrypted_members;
+        self.truncated_entries += other.truncated_entries;
+        self.truncated_bytes += other.truncated_bytes;
+        self.checksum_failures += other.checksum_failures;
+        self.input_bytes += other.input_bytes;
+        self.file_limit_reached |= other.file_limit_reached;
+        merge_metadata_summary(&mut self.metadata_summary, other.metadata_summary);
+        self.regions.extend(other.regions);
+        for (file_type, (count, bytes)) in other.type_totals {
+            let totals = self.type_totals.entry(file_type).or_insert((0, 0));
+            totals.0 += count;
+            totals.1 += bytes;
+        }
+    }
+}
+
+/// Process a file, extracting everything into `output_dir` if one is given
+/// A file that fails to write is reported and counted, but does not stop the run
 pub fn process_file(
     data: &[u8],
     filename: Option<&str>,
     output_dir: Option<&str>,
+    options: &ProcessOptions,
+) -> io::Result<ProcessStats> {
+    let _span = tracing::info_span!(
+        "process_file",
+        name = filename.unwrap_or("<unknown>"),
+        size = data.len()
+    )
+    .entered();
+    let file_type = options
+        .forced_type(data, filename)
+        .unwrap_or_else(|| detect_file_type(data, filename));
+    process_to_disk(data, filename, file_type, output_dir, options, |ctx| {
+        process_node(ctx, data, filename, Path::new(""), 0)
+    })
+}
+
+/// Run a traversal of `input` that writes every artifact under `output_dir`
+/// Only failing to create `output_dir` itself is fatal
+fn process_to_disk(
+    input: &[u8],
+    filename: Option<&str>,
+    file_type: FileType,
+    output_dir: Option<&str>,
+    options: &ProcessOptions,
+    process: impl FnOnce(&mut Context) -> io::Result<()>,
+) -> io::Result<ProcessStats> {
+    if let Some(out_dir) = output_dir {
+        fs::create_dir_all(out_dir)?;
+    }
+
+    let skip_existing = options.skip_existing || options.verify_existing;
+    let mut stats = ProcessStats::default();
+    let mut grouped_paths = HashSet::new();
+    let mut write_to_disk = |file: &ExtractedFile| {
+        if let Some(out_dir) = output_dir {
+            let rel_path = if options.group_by_device {
+                device_path(file, &mut grouped_paths)
+            } else {
+                file.path.clone()
+            };
+            // The manifest keeps its name so `pack_directory` can find it
+            let rel_path = if rel_path.file_name() == Some(MANIFEST_FILE_NAME.as_ref()) {
+                rel_path
+            } else {
+                transform_path(&options.name_transforms, &rel_path)
+            };
+            let path = Path::new(out_dir).join(rel_path);
+            if skip_existing && existing_matches(&path, file.data, options.verify_existing) {
+                stats.files_skipped += 1;
+                return Ok(());
+            }
+            match write_output(&path, file.data) {
+                Ok(()) => stats.files_written += 1,
+                Err(e) => {
+                    stats.write_failures += 1;
+                    tracing::warn!(
+                        path = %path.display(),
+                        "failed to write {}: {}",
+                        path.display(),
+                        e
+                    );
+                    return Ok(());
+                }
+            }
+            // Unlike a failed write, a file that reads back wrong aborts the run
+            if options.verify_writes {
+                verify_written(&path, file.data)?;
+                stats.files_verified += 1;
+            }
+        }
+        Ok(())
+    };
+    let mut ctx = Context::new(options, &mut write_to_disk);
+    ctx.input = input;
+    ctx.regions.push(OffsetRegion {
+        path: filename.unwrap_or("<input>").to_string(),
+        file_type,
+        range: Some(0..input.len()),
+        size: input.len(),
+    });
+    process(&mut ctx)?;
+    let crc_mismatches = ctx.crc_mismatches;
+    let encrypted_members = ctx.encrypted_members;
+    let truncated_entries = ctx.truncated_entries;
+    let truncated_bytes = ctx.truncated_bytes;
+    let checksum_failures = ctx.checksum_failures;
+    let input_bytes = ctx.input_bytes;
+    let file_limit_reached = ctx.file_limit_reached;
+    let metadata_summary = std::mem::take(&mut ctx.metadata_summary);
+    let regions = std::mem::take(&mut ctx.regions);
+    let type_totals = ctx.finish();
+    stats.crc_mismatches = crc_mismatches;
+    stats.encrypted_members = encrypted_members;
+    stats.truncated_entries = truncated_entries;
+    stats.truncated_bytes = truncated_bytes;
+    stats.checksum_failures = checksum_failures;
+    stats.input_bytes = input_bytes;
+    stats.file_limit_reached = file_limit_reached;
+    stats.type_totals = type_totals;
+    stats.metadata_summary = metadata_summary;
+    stats.regions = regions;
+    if stats.files_skipped > 0 {
+        report!(
+            "Skipped {} unchanged files already on disk",
+            stats.files_skipped
+        );
+    }
+    if options.verify_writes && output_dir.is_some() {
+        report!("Verified {} written files", stats.files_verified);
+    }
+    Ok(stats)
+}
+
+/// Process a file, invoking `on_file` for every extracted artifact instead of writing to disk
+/// Returns the count and total size of the files seen, per type
+pub fn process_file_with(
+    data: &[u8],
+    filename: Option<&str>,
+    options: &ProcessOptions,
+    on_file: &mut dyn FnMut(&ExtractedFile) -> io::Result<()>,
+) -> io::Result<TypeTotals> {
+    let mut ctx = Context::new(options, on_file);
+    process_node(&mut ctx, data, filename, Path::new(""), 0)?;
+    Ok(ctx.finish())
+}
+
+/// Like `process_file`, but treat the input as `file_type` instead of detecting it
+/// Nested files are still detected as usual
+pub fn process_file_as(
+    data: &[u8],
+    filename: Option<&str>,
+    file_type: FileType,
+    output_dir: Option<&str>,
+    options: &ProcessOptions,
+) -> io::Result<ProcessStats> {
+    process_to_disk(data, filename, file_type, output_dir, options, |ctx| {
+        process_node_as(ctx, data, filename, file_type, Path::new(""), 0)
+    })
+}
+
+/// Process a file based on its detected type
+/// `rel_dir` is the nesting path (relative to the output root) the file lives in
+pub fn process_node(
+    ctx: &mut Context,
+    data: &[u8],
+    filename: Option<&str>,
+    rel_dir: &Path,
+    depth: usize,
+) -> io::Result<()> {
+    let file_type = match ctx.options.forced_type(data, filename) {
+        Some(forced) => forced,
+        None => detect_file_type(data, filename),
+    };
+    process_node_as(ctx, data, filename, file_type, rel_dir, depth)
+}
+
+/// Process a file as an already-decided type
+pub fn process_node_as(
+    ctx: &mut Context,
+    data: &[u8],
+    filename: Option<&str>,
+    file_type: FileType,
+    rel_dir: &Path,
     depth: usize,
 ) -> io::Result<()> {
+    if ctx.file_limit_reached {
+        return Ok(());
+    }
+    let _span = tracing::debug_span!(
+        "node",
+        name = filename.unwrap_or("<unknown>"),
+        file_type = file_type_name(&file_type),
+        size = data.len(),
+        depth
+    )
+    .entered();
     let indent = "  ".repeat(depth);
-    let file_type = detect_file_type(data, filename);
+    ctx.record(file_type, data.len());
+
+    if depth == 0 {
+        ctx.input_bytes += data.len() as u64;
+        ctx.firmware = filename.and_then(parse_firmware_name);
+        if let Some(firmware) = &ctx.firmware {
+            report!("Firmware: {}", firmware);
+        }
+    }
 
-    println!(
+    report!(
         "{}[{}] {} ({} bytes)",
         indent,
         file_type_name(&file_type),
@@ -26,79 +759,114 @@ pub fn process_file(
         data.len()
     );
 
+    // A container seen before (a duplicated or self-referential component) would only
+    // repeat the same output, so save it as-is and don't recurse into it again
+    if recurses_into(file_type) && !ctx.processed.insert(sha256(data)) {
+        if let Some(fname) = filename {
+            ctx.emit(rel_dir.join(fname), file_type, data)?;
+        }
+        report!("{}  [already processed, skipping]", indent);
+        return Ok(());
+    }
+
+    // A registered handler replaces the built-in processing for its type
+    let node = Node {
+        data,
+        filename,
+        file_type,
+        rel_dir,
+        depth,
+    };
+    let options = ctx.options;
+    match options.handlers.get(file_type) {
+        Some(handler) => handler.handle(ctx, &node),
+        None => process_builtin(ctx, &node),
+    }
+}
+
+/// The built-in processing of a file by its type: containers are opened and recursed into,
+/// everything else is saved and summarized
+pub fn process_builtin(ctx: &mut Context, node: &Node) -> io::Result<()> {
+    let Node {
+        data,
+        filename,
+        file_type,
+        rel_dir,
+        depth,
+    } = *node;
+    let indent = node.indent();
+
     match file_type {
         FileType::AutelContainer => {
-            process_autel_container(data, filename, output_dir, depth)?;
+            process_autel_container(ctx, data, filename, rel_dir, depth)?;
         }
         FileType::Zip => {
-            process_zip(data, filename, output_dir, depth)?;
+            process_zip(ctx, data, filename, rel_dir, depth)?;
+        }
+        FileType::Tar => {
+            process_tar(ctx, data, filename, rel_dir, depth)?;
+        }
+        FileType::Cpio => {
+            process_cpio(ctx, data, filename, rel_dir, depth)?;
         }
         FileType::Gzip => {
-            // Write the file but note we can't recursively parse gzip without adding flate2
-            if let Some(out_dir) = output_dir {
-                if let Some(fname) = filename {
-                    let output_path = Path::new(out_dir).join(fname);
-                    if let Some(parent) = output_path.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
-                    let mut out_file = File::create(&output_path)?;
-                    out_file.write_all(data)?;
-                }
+            process_gzip(ctx, data, filename, rel_dir, depth)?;
+        }
+        FileType::IntelHex | FileType::SRecord => {
+            process_hex_records(ctx, data, filename, file_type, rel_dir, depth)?;
+        }
+        FileType::Xz => {
+            // Write the file but note we can't recursively parse xz yet
+            if let Some(fname) = filename {
+                ctx.emit(rel_dir.join(fname), file_type, data)?;
             }
-            println!(
-                "{}  → Gzip file saved (decompression not implemented)",
-                indent
+            report!(
+                "{}  → {} file saved (decompression not implemented)",
+                indent,
+                file_type_name(&file_type)
             );
         }
         FileType::Json => {
-            if let Some(out_dir) = output_dir {
-                if let Some(fname) = filename {
-                    let output_path = Path::new(out_dir).join(fname);
-                    if let Some(parent) = output_path.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
-                    let mut out_file = File::create(&output_path)?;
-                    out_file.write_all(data)?;
-                }
+            let parsed = std::str::from_utf8(data)
+                .ok()
+                .map(|s| (s, serde_json::from_str::<serde_json::Value>(s)));
+            // Written as found, so a repacked container is byte-identical
+            if let Some(fname) = filename {
+                ctx.emit(rel_dir.join(fname), file_type, data)?;
             }
-            if let Ok(s) = std::str::from_utf8(data) {
-                match serde_json::from_str::<serde_json::Value>(s) {
+            if let Some((s, result)) = parsed {
+                match result {
                     Ok(json) => {
-                        let pretty =
-                            serde_json::to_string_pretty(&json).unwrap_or_else(|_| s.to_string());
-                        for line in pretty.lines().take(20) {
-                            println!("{}  {}", indent, line);
+                        let shown = render_json(ctx.options.json_style, s, &json);
+                        for line in shown.lines().take(20) {
+                            report!("{}  {}", indent, line);
                         }
-                        if pretty.lines().count() > 20 {
-                            println!(
+                        if shown.lines().count() > 20 {
+                            report!(
                                 "{}  ... ({} more lines)",
                                 indent,
-                                pretty.lines().count() - 20
+                                shown.lines().count() - 20
                             );
                         }
                     }
-                    Err(_) => println!("{}  (invalid JSON)", indent),
+                    Err(_) => report!("{}  (invalid JSON)", indent),
                 }
             }
         }
         FileType::Text => {
-            if let Some(out_dir) = output_dir {
-                if let Some(fname) = filename {
-                    let output_path = Path::new(out_dir).join(fname);
-                    if let Some(parent) = output_path.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
-                    let mut out_file = File::create(&output_path)?;
-                    out_file.write_all(data)?;
-                }
+            if let Some(fname) = filename {
+                ctx.emit(rel_dir.join(fname), file_type, data)?;
             }
-            if let Ok(s) = std::str::from_utf8(data) {
-                for line in s.lines().take(5) {
-                    println!("{}  {}", indent, line);
-                }
-                if s.lines().count() > 5 {
-                    println!("{}  ... ({} more lines)", indent, s.lines().count() - 5);
-                }
+            // Best-effort: a log with a few stray binary bytes is still worth showing
+            let (s, invalid) = lossy_text(data);
+            if invalid > 0 {
+                report!("{}  (lossy, {} invalid bytes)", indent, invalid);
+            }
+            for line in s.lines().take(5) {
+                report!("{}  {}", indent, line);
+            }
+            if s.lines().count() > 5 {
+                report!("{}  ... ({} more lines)", indent, s.lines().count() - 5);
             }
         }
         FileType::UpgGimbal
@@ -108,250 +876,2266 @@ pub fn process_file(
         | FileType::UpgRcMcu
         | FileType::GpsBin => {
             // These are binary firmware files we can extract but not parse further
-            if let Some(out_dir) = output_dir {
-                if let Some(fname) = filename {
-                    let output_path = Path::new(out_dir).join(fname);
-                    if let Some(parent) = output_path.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
-                    let mut out_file = File::create(&output_path)?;
-                    out_file.write_all(data)?;
-                }
+            if let Some(fname) = filename {
+                ctx.emit(rel_dir.join(fname), file_type, data)?;
             }
-            println!(
+            report!(
                 "{}  → Binary firmware file (no further parsing available)",
                 indent
             );
+            if let Some(id) = upg_device_id(data) {
+                report!("{}  Device: {}", indent, upg_device_label(id));
+            }
             print_hexdump_preview_indented(data, 3, &indent);
+            if let Some(min_len) = ctx.options.strings_min_len {
+                let found = ascii_strings(data, min_len);
+                report!(
+                    "{}  Strings (at least {} chars): {} found",
+                    indent,
+                    min_len,
+                    found.len()
+                );
+                for s in found {
+                    report!("{}    0x{:08x}  {}", indent, s.offset, s.text);
+                }
+            }
         }
-        FileType::Unknown => {
-            if let Some(out_dir) = output_dir {
-                if let Some(fname) = filename {
-                    let output_path = Path::new(out_dir).join(fname);
-                    if let Some(parent) = output_path.parent() {
-                        fs::create_dir_all(parent)?;
+        FileType::SquashFs => {
+            // Listing the files inside needs a decompressor per compression type,
+            // so for now just summarize the superblock
+            if let Some(fname) = filename {
+                ctx.emit(rel_dir.join(fname), file_type, data)?;
+            }
+            match squashfs::parse_superblock(data) {
+                Some(sb) => {
+                    report!("{}  {}", indent, sb);
+                    if let Some(time) = sb.modified {
+                        ctx.build_times.add(time);
+                    }
+                    if let Some(used) = sb.bytes_used.filter(|&used| used > data.len() as u64) {
+                        let msg = format!(
+                            "SquashFS image claims {} bytes but only {} are present",
+                            used,
+                            data.len()
+                        );
+                        ctx.fail_if_strict(&msg)?;
+                        report!("{}  Warning: {}", indent, msg);
                     }
-                    let mut out_file = File::create(&output_path)?;
-                    out_file.write_all(data)?;
+                }
+                None => report!("{}  SquashFS (superblock unreadable)", indent),
+            }
+        }
+        FileType::Png | FileType::Jpeg => {
+            if let Some(fname) = filename {
+                ctx.emit(rel_dir.join(fname), file_type, data)?;
+            }
+            let (kind, dimensions) = if file_type == FileType::Png {
+                ("PNG", image::png_dimensions(data))
+            } else {
+                ("JPEG", image::jpeg_dimensions(data))
+            };
+            match dimensions {
+                Some((width, height)) => report!("{}  {} {}x{}", indent, kind, width, height),
+                None => report!("{}  {} (dimensions unreadable)", indent, kind),
+            }
+        }
+        FileType::Unknown => {
+            if let Some(fname) = filename {
+                ctx.emit(rel_dir.join(fname), file_type, data)?;
+            }
+            // The file itself is kept as found; the payload behind its framing is processed
+            // as a nested `<name>.unframed`
+            match detect_after_framing(data, ctx.options.framing_skip) {
+                Some(detection) => {
+                    report!("{}  → {}", indent, framing_note(&detection, data));
+                    let unframed = filename.map(|f| format!("{}.unframed", f));
+                    process_node_as(
+                        ctx,
+                        &data[detection.skipped..],
+                        unframed.as_deref(),
+                        detection.file_type,
+                        rel_dir,
+                        depth + 1,
+                    )?;
+                }
+                None => {
+                    report!("{}  → Unknown file format (no parser available)", indent);
+                    print_hexdump_preview_indented(data, 3, &indent);
                 }
             }
-            println!("{}  → Unknown file format (no parser available)", indent);
-            print_hexdump_preview_indented(data, 3, &indent);
         }
     }
 
     Ok(())
 }
 
-/// Process an Autel container format file
-pub fn process_autel_container(
-    data: &[u8],
-    container_name: Option<&str>,
-    output_dir: Option<&str>,
-    depth: usize,
-) -> io::Result<()> {
-    let indent = "  ".repeat(depth);
-    let entries = parse_file_entries(data);
+/// Cross-check the files a container's manifest refers to against its entries, reporting
+/// missing and unreferenced ones, and return the order to extract the entries in
+/// The manifest is the one `detect_manifest` finds, or else the first JSON entry naming
+/// another entry; without one, entries keep their container order
+fn follow_json_refs(ctx: &Context, entries: &[FileEntry], indent: &str) -> io::Result<Vec<usize>> {
+    let mut json_entries = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.detected_type() == FileType::Json)
+        .filter_map(|(i, e)| {
+            let json: serde_json::Value = serde_json::from_slice(e.content).ok()?;
+            Some((i, resolve_manifest_refs(&json, entries)))
+        });
+    let found = match detect_manifest(entries) {
+        Some(m) => json_entries.find(|(i, _)| *i == m.index),
+        None => json_entries.find(|(i, refs)| refs.resolved.iter().any(|r| r != i)),
+    };
+    let Some((manifest_index, mut refs)) = found else {
+        let message = "--follow-json-refs: no manifest found, extracting in container order";
+        ctx.fail_if_strict(message)?;
+        report!("{}  Warning: {}", indent, message);
+        return Ok((0..entries.len()).collect());
+    };
 
-    if entries.is_empty() {
-        println!("{}  → No file entries found in container", indent);
-        return Ok(());
+    // The manifest itself goes first and isn't counted as unreferenced
+    refs.resolved.retain(|&i| i != manifest_index);
+    refs.unreferenced.retain(|&i| i != manifest_index);
+    let manifest_name = entries[manifest_index]
+        .filename
+        .as_deref()
+        .unwrap_or("<unknown>");
+    report!(
+        "{}  → Manifest refs ({}): {} resolved, {} missing, {} unreferenced; extracting in manifest order",
+        indent,
+        manifest_name,
+        refs.resolved.len(),
+        refs.missing.len(),
+        refs.unreferenced.len()
+    );
+    for name in &refs.missing {
+        let message = format!(
+            "{} references {} but no entry has that name",
+            manifest_name, name
+        );
+        ctx.fail_if_strict(&message)?;
+        report!("{}  Warning: {}", indent, message);
+    }
+    for &i in &refs.unreferenced {
+        report!(
+            "{}  Note: {} is not referenced by {}",
+            indent,
+            entries[i].filename.as_deref().unwrap_or("<unknown>"),
+            manifest_name
+        );
     }
 
-    println!("{}  → Found {} file entries", indent, entries.len());
+    let mut order = vec![manifest_index];
+    order.extend(refs.extraction_order());
+    Ok(order)
+}
 
-    // Create output directory named after the container
-    let extract_dir = if let Some(out_dir) = output_dir {
-        let dir_name = container_name
-            .map(|n| {
-                Path::new(n)
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or(n)
-            })
-            .unwrap_or("extracted");
-        let extract_path = Path::new(out_dir).join(dir_name);
-        fs::create_dir_all(&extract_path)?;
-        Some(extract_path)
+/// The name a container entry is written under on disk, if index prefixes or name transforms
+/// make it differ from the entry's own name
+fn stored_name(
+    options: &ProcessOptions,
+    position: usize,
+    count: usize,
+    entry: &FileEntry,
+) -> Option<String> {
+    let name = entry.filename.as_deref()?;
+    let prefixed = if options.index_prefix {
+        format!("{}{}", index_prefix(position, count), name)
     } else {
-        None
+        name.to_string()
     };
+    let stored = transform_path(&options.name_transforms, Path::new(&prefixed));
+    let stored = stored.to_string_lossy();
+    (stored != name).then(|| stored.into_owned())
+}
 
-    for (i, entry) in entries.iter().enumerate() {
-        let filename = entry.filename.as_deref();
-        println!();
-        println!(
-            "{}  === Entry {}/{}: {} ===",
-            indent,
-            i + 1,
-            entries.len(),
-            filename.unwrap_or("<unknown>")
+/// Where a tar, cpio or ZIP member goes below `extract_dir`: its directory and its file name
+/// Root and `.` components are dropped, so a member can't be written outside `extract_dir`;
+/// `..` components are dropped too, with a warning (an error with `strict`)
+fn member_location(
+    ctx: &Context,
+    name: &str,
+    extract_dir: &Path,
+    indent: &str,
+) -> io::Result<(PathBuf, String)> {
+    let mut relative = PathBuf::new();
+    let mut escapes = false;
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::ParentDir => escapes = true,
+            Component::RootDir | Component::Prefix(_) | Component::CurDir => {}
+        }
+    }
+    if escapes {
+        let message = format!(
+            "{}: member path leaves the archive, extracting it as {}",
+            name,
+            relative.display()
         );
+        ctx.fail_if_strict(&message)?;
+        report!("{}  Warning: {}", indent, message);
+    }
 
-        if let Some(header_data) = entry.header_data {
-            println!("{}  Header: {:02x?}", indent, header_data);
+    let file_name = match relative.file_name().and_then(|s| s.to_str()) {
+        Some(file_name) => file_name.to_string(),
+        None => "unnamed".to_string(),
+    };
+    let sub_dir = match relative.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => extract_dir.join(parent),
+        _ => extract_dir.to_path_buf(),
+    };
+    Ok((sub_dir, file_name))
+}
+
+/// Process a tar archive, saving it and recursing into each member
+pub fn process_tar(
+    ctx: &mut Context,
+    data: &[u8],
+    tar_name: Option<&str>,
+    rel_dir: &Path,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+
+    // Save the raw archive
+    if let Some(fname) = tar_name {
+        ctx.emit(rel_dir.join(fname), FileType::Tar, data)?;
+    }
+
+    let entries = tar::list_entries(data);
+    report!("{}  → Contains {} files", indent, entries.len());
+
+    // Members are extracted into a directory named after the archive
+    let dir_name = tar_name
+        .map(|n| {
+            Path::new(n)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(n)
+        })
+        .unwrap_or("tar_contents");
+    let extract_dir = rel_dir.join(dir_name);
+
+    for entry in &entries {
+        if ctx.file_limit_reached {
+            break;
         }
-        if let Some(content_meta) = entry.content_meta {
-            println!("{}  Meta: {:02x?}", indent, content_meta);
+        let contents = tar::entry_data(data, entry);
+        if contents.len() < entry.size {
+            let message = format!(
+                "{}: declared size ({}) exceeds available data ({})",
+                entry.name,
+                entry.size,
+                contents.len()
+            );
+            ctx.fail_if_strict(&message)?;
+            report!("{}  Warning: {}", indent, message);
         }
-        println!("{}  Size: {} bytes", indent, entry.content.len());
 
-        let sub_output_dir = extract_dir
-            .as_ref()
-            .map(|p| p.to_string_lossy().to_string());
+        let (sub_dir, just_filename) = member_location(ctx, &entry.name, &extract_dir, &indent)?;
 
-        // Recursively process the extracted content
-        process_file(entry.content, filename, sub_output_dir.as_deref(), depth + 1)?;
+        report!();
+        process_node(ctx, contents, Some(&just_filename), &sub_dir, depth + 1)?;
     }
 
-    // Print summary
-    summarize_metadata(&entries, &indent);
-
     Ok(())
 }
 
-/// Process a ZIP archive file
-pub fn process_zip(
+/// Process a newc cpio archive (an initramfs, usually), saving it and recursing into its files
+/// Directories, symlinks and device nodes are listed but have nothing to extract
+pub fn process_cpio(
+    ctx: &mut Context,
     data: &[u8],
-    zip_name: Option<&str>,
-    output_dir: Option<&str>,
+    cpio_name: Option<&str>,
+    rel_dir: &Path,
     depth: usize,
 ) -> io::Result<()> {
     let indent = "  ".repeat(depth);
 
-    // Try to find valid ZIP by scanning for EOCD
-    let zip_slice = match slice_to_eocd(data) {
-        Some(slice) => slice,
-        None => {
-            println!(
-                "{}  → Could not find valid ZIP structure (no EOCD marker)",
-                indent
-            );
-            // Still save the raw file
-            if let Some(out_dir) = output_dir {
-                if let Some(fname) = zip_name {
-                    let output_path = Path::new(out_dir).join(fname);
-                    if let Some(parent) = output_path.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
-                    let mut out_file = File::create(&output_path)?;
-                    out_file.write_all(data)?;
-                }
-            }
-            return Ok(());
+    // Save the raw archive
+    if let Some(fname) = cpio_name {
+        ctx.emit(rel_dir.join(fname), FileType::Cpio, data)?;
+    }
+
+    let entries = cpio::list_entries(data);
+    let files = entries.iter().filter(|e| e.is_regular()).count();
+    report!(
+        "{}  → Contains {} entries ({} files)",
+        indent,
+        entries.len(),
+        files
+    );
+
+    // Members are extracted into a directory named after the archive
+    let dir_name = cpio_name
+        .map(|n| {
+            Path::new(n)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(n)
+        })
+        .unwrap_or("cpio_contents");
+    let extract_dir = rel_dir.join(dir_name);
+
+    for entry in &entries {
+        if ctx.file_limit_reached {
+            break;
+        }
+        if !entry.is_regular() {
+            report!(
+                "{}  {:06o} {} ({})",
+                indent,
+                entry.mode,
+                entry.name,
+                entry.kind()
+            );
+            continue;
         }
-    };
 
-    let reader = Cursor::new(zip_slice);
-    let mut archive = match ZipArchive::new(reader) {
-        Ok(a) => a,
+        let contents = cpio::entry_data(data, entry);
+        if contents.len() < entry.size {
+            let message = format!(
+                "{}: declared size ({}) exceeds available data ({})",
+                entry.name,
+                entry.size,
+                contents.len()
+            );
+            ctx.fail_if_strict(&message)?;
+            report!("{}  Warning: {}", indent, message);
+        }
+
+        // Names are relative to the archive root, sometimes with a leading `./`
+        let (sub_dir, just_filename) = member_location(ctx, &entry.name, &extract_dir, &indent)?;
+
+        report!();
+        report!("{}  Mode: {:06o}", indent, entry.mode);
+        process_node(ctx, contents, Some(&just_filename), &sub_dir, depth + 1)?;
+    }
+
+    Ok(())
+}
+
+/// Process an Intel HEX or S-record file, saving it and recursing into the rebuilt image
+pub fn process_hex_records(
+    ctx: &mut Context,
+    data: &[u8],
+    filename: Option<&str>,
+    file_type: FileType,
+    rel_dir: &Path,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+
+    // Save the original text
+    if let Some(fname) = filename {
+        ctx.emit(rel_dir.join(fname), file_type, data)?;
+    }
+
+    let decoded = if file_type == FileType::IntelHex {
+        hexfile::decode_intel_hex(data)
+    } else {
+        hexfile::decode_srecord(data)
+    };
+    let image = match decoded {
+        Ok(image) => image,
         Err(e) => {
-            println!("{}  → Failed to read ZIP archive: {}", indent, e);
+            ctx.fail_if_strict(&format!("failed to decode records: {}", e))?;
+            report!("{}  → Failed to decode records: {}", indent, e);
+            return Ok(());
+        }
+    };
+
+    if !image.checksum_errors.is_empty() {
+        let message = format!(
+            "{} records failed their checksum (lines {:?})",
+            image.checksum_errors.len(),
+            image.checksum_errors
+        );
+        ctx.fail_if_strict(&message)?;
+        report!("{}  Warning: {}", indent, message);
+    }
+
+    let stem = filename
+        .map(|n| {
+            Path::new(n)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(n)
+        })
+        .unwrap_or("image");
+    let name = format!("{}.bin", stem);
+    report!(
+        "{}  → Decoded to {} ({} bytes at 0x{:08x})",
+        indent,
+        name,
+        image.data.len(),
+        image.base_address
+    );
+    report!();
+    process_node(ctx, &image.data, Some(&name), rel_dir, depth + 1)
+}
+
+/// Process a gzip file, saving it and recursing into the decompressed data
+pub fn process_gzip(
+    ctx: &mut Context,
+    data: &[u8],
+    gzip_name: Option<&str>,
+    rel_dir: &Path,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+
+    // Save the raw compressed file
+    if let Some(fname) = gzip_name {
+        ctx.emit(rel_dir.join(fname), FileType::Gzip, data)?;
+    }
+
+    let header = match parse_gzip_header(data) {
+        Some(h) => h,
+        None => {
+            ctx.fail_if_strict("invalid gzip header")?;
+            report!("{}  → Invalid gzip header", indent);
             return Ok(());
         }
     };
 
-    println!("{}  → Contains {} files", indent, archive.len());
+    if header.mtime != 0 {
+        ctx.build_times
+            .add(Timestamp::from_unix(u64::from(header.mtime)));
+    }
 
-    // Create extraction directory named after the ZIP
-    let extract_dir = if let Some(out_dir) = output_dir {
-        let dir_name = zip_name
-            .map(|n| {
-                Path::new(n)
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or(n)
-            })
-            .unwrap_or("zip_contents");
-        let extract_path = Path::new(out_dir).join(dir_name);
-        fs::create_dir_all(&extract_path)?;
-        Some(extract_path)
-    } else {
-        None
+    let decompressed = match gzip::decompress_limited(data, gzip::MAX_DECOMPRESSED_SIZE) {
+        Ok((d, false)) => d,
+        Ok((d, true)) => {
+            let message = format!(
+                "gzip output exceeds {} bytes, truncated (possible decompression bomb)",
+                gzip::MAX_DECOMPRESSED_SIZE
+            );
+            ctx.fail_if_strict(&message)?;
+            report!("{}  Warning: {}", indent, message);
+            d
+        }
+        Err(e) => {
+            ctx.fail_if_strict(&format!("failed to decompress gzip: {}", e))?;
+            report!("{}  → Failed to decompress gzip: {}", indent, e);
+            return Ok(());
+        }
     };
 
-    // Also save the raw ZIP file
-    if let Some(out_dir) = output_dir {
-        if let Some(fname) = zip_name {
-            let output_path = Path::new(out_dir).join(fname);
-            if let Some(parent) = output_path.parent() {
-                fs::create_dir_all(parent)?;
+    let name = derive_decompressed_name(gzip_name.unwrap_or("gzip"), &header);
+    report!(
+        "{}  → Decompressed to {} ({} bytes)",
+        indent,
+        name,
+        decompressed.len()
+    );
+    report!();
+    process_node(ctx, &decompressed, Some(&name), rel_dir, depth + 1)
+}
+
+/// Warn when a nested container's entries span a different length than its entry declares
+fn check_nested_length(
+    ctx: &Context,
+    nested: &[FileEntry],
+    declared: usize,
+    filename: Option<&str>,
+    indent: &str,
+) -> io::Result<()> {
+    if let Some((spanned, declared)) = nested_length_mismatch(nested, declared) {
+        let message = format!(
+            "nested/declared length mismatch: nested entries span {} bytes, entry declares {}",
+            spanned, declared
+        );
+        ctx.fail_if_strict(&format!("{}: {}", filename.unwrap_or("<unknown>"), message))?;
+        report!("{}  Warning: {}", indent, message);
+    }
+    Ok(())
+}
+
+/// Process an Autel container format file
+pub fn process_autel_container(
+    ctx: &mut Context,
+    data: &[u8],
+    container_name: Option<&str>,
+    rel_dir: &Path,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    let max_entries = ctx.options.max_entries.unwrap_or(DEFAULT_MAX_ENTRIES);
+    let container = collect_container(
+        iter_file_entries(data)
+            .with_max_entries(max_entries)
+            .with_recovery(ctx.options.recover),
+    );
+    let entries = &container.entries;
+    if let Some(declared) = ctx.declared_len.take() {
+        check_nested_length(ctx, entries, declared, container_name, &indent)?;
+    }
+
+    for diagnostic in &container.diagnostics {
+        match diagnostic.severity {
+            Severity::Warning => {
+                tracing::warn!(offset = diagnostic.offset, "{}", diagnostic.message)
+            }
+            Severity::Error => {
+                tracing::error!(offset = diagnostic.offset, "{}", diagnostic.message)
             }
-            let mut out_file = File::create(&output_path)?;
-            out_file.write_all(data)?;
         }
     }
+    if container.cap_reached {
+        ctx.fail_if_strict(&format!("entry cap ({}) reached", max_entries))?;
+    }
 
-    for i in 0..archive.len() {
-        let mut file = match archive.by_index(i) {
-            Ok(f) => f,
-            Err(_) => continue,
+    if container.malformed > 0 {
+        let message = format!(
+            "{} malformed entries skipped (missing expected tag)",
+            container.malformed
+        );
+        ctx.fail_if_strict(&message)?;
+        report!("{}  → Warning: {}", indent, message);
+    }
+
+    if entries.is_empty() {
+        report!("{}  → No file entries found in container", indent);
+        return Ok(());
+    }
+
+    report!("{}  → Found {} file entries", indent, entries.len());
+    if let Some(style) = container.tag_style {
+        report!("{}  → Tag style: {}", indent, style.name());
+    }
+    let header = &data[..find_container_start(data).unwrap_or(0)];
+    report!(
+        "{}  → Schema: {}",
+        indent,
+        infer_container_version(entries, header, container.tag_style)
+    );
+    if ctx.options.recover && container.salvaged > 0 {
+        report!(
+            "{}  → Recovered {} entries after the first structural error",
+            indent,
+            container.salvaged
+        );
+    }
+
+    // Entries are extracted into a directory named after the container
+    let dir_name = container_name
+        .map(|n| {
+            Path::new(n)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(n)
+        })
+        .unwrap_or("extracted");
+    let extract_dir = rel_dir.join(dir_name);
+
+    // A manifest entry lets every sibling be checked against what it declares
+    let manifest = detect_manifest(entries).and_then(|m| {
+        let parsed = parse_manifest(m.content)?;
+        for declared in &parsed.files {
+            if let (Some(device), Some(name)) =
+                (&declared.device, Path::new(&declared.name).file_name())
+            {
+                ctx.device_hints
+                    .insert(extract_dir.join(name), device_dir_name(device));
+            }
+        }
+        report!(
+            "{}  → Manifest: {} (version {}, {} files declared)",
+            indent,
+            m.filename.as_deref().unwrap_or("<unknown>"),
+            parsed.version.as_deref().unwrap_or("?"),
+            parsed.files.len()
+        );
+        if let (Some(version), Some(firmware)) = (&parsed.version, &ctx.firmware) {
+            if versions_agree(version, &firmware.version) {
+                report!("{}  → Manifest version agrees with the file name", indent);
+            } else {
+                report!(
+                    "{}  → Note: manifest version {} conflicts with v{} from the file name",
+                    indent,
+                    version,
+                    firmware.version
+                );
+            }
+        }
+        Some(parsed)
+    });
+
+    // Only treat content_meta as a checksum if it matches for at least one entry
+    let meta_is_crc32 = entries.iter().any(meta_matches_crc32);
+
+    let order = if ctx.options.follow_json_refs {
+        follow_json_refs(ctx, entries, &indent)?
+    } else {
+        (0..entries.len()).collect()
+    };
+
+    if ctx.options.write_manifest {
+        let manifest: Vec<OwnedFileEntry> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| OwnedFileEntry {
+                stored_as: stored_name(ctx.options, i, entries.len(), entry),
+                ..OwnedFileEntry::from(entry)
+            })
+            .collect();
+        let json = serde_json::to_vec_pretty(&manifest)?;
+        ctx.emit(extract_dir.join(MANIFEST_FILE_NAME), FileType::Json, &json)?;
+    }
+
+    for i in order {
+        if ctx.file_limit_reached {
+            break;
+        }
+        let entry = &entries[i];
+        let filename = entry.filename.as_deref();
+        // The name written to disk; detection and manifest checks still use the entry's own
+        let output_name = match filename {
+            Some(name) if ctx.options.index_prefix => {
+                let prefixed = format!("{}{}", index_prefix(i, entries.len()), name);
+                if let Some(device) = ctx.device_hints.get(&extract_dir.join(name)).cloned() {
+                    ctx.device_hints.insert(extract_dir.join(&prefixed), device);
+                }
+                Some(prefixed)
+            }
+            _ => filename.map(str::to_string),
         };
+        report!();
+        report!(
+            "{}  === Entry {}/{}: {} ===",
+            indent,
+            i + 1,
+            entries.len(),
+            filename.unwrap_or("<unknown>")
+        );
+
+        if ctx.options.sidecar {
+            let name = match &output_name {
+                Some(name) => format!("{}{}", name, SIDECAR_SUFFIX),
+                None => format!("entry_{}{}", entry.index, SIDECAR_SUFFIX),
+            };
+            ctx.emit_sidecar(extract_dir.join(name), sidecar(entry).as_bytes())?;
+        }
+
+        if let Some(header_data) = entry.header_data {
+            report!("{}  Header: {:02x?}", indent, header_data);
+        }
+        if let Some(content_meta) = entry.content_meta {
+            report!("{}  Meta: {:02x?}", indent, content_meta);
+        }
+        report!("{}  Size: {} bytes", indent, entry.content.len());
+        if ctx.options.entropy {
+            let entropy = shannon_entropy(entry.content);
+            let note = if entropy > HIGH_ENTROPY {
+                " (likely compressed/encrypted)"
+            } else {
+                ""
+            };
+            report!("{}  Entropy: {:.2} bits/byte{}", indent, entropy, note);
+        }
+        if is_likely_encrypted(entry) {
+            report!("{}  [possibly encrypted]", indent);
+        }
+
+        if is_truncated(entry) {
+            // The parser has already warned about this
+            report!(
+                "{}  Truncated: {} of {} declared bytes missing",
+                indent,
+                entry.missing_bytes(),
+                entry.content_length
+            );
+            ctx.record_truncation(entry, extract_dir.join(entry_file_name(entry)), data.len());
+            ctx.fail_if_strict(&format!(
+                "{}: declared content length ({}) exceeds available data ({})",
+                filename.unwrap_or("<unknown>"),
+                entry.content_length,
+                entry.content.len()
+            ))?;
+        }
+        let crc_failed = meta_is_crc32 && !meta_matches_crc32(entry);
+        if crc_failed {
+            let message = format!(
+                "checksum mismatch (crc32 {:08x})",
+                crc32fast::hash(entry.content)
+            );
+            ctx.fail_if_strict(&format!("{}: {}", filename.unwrap_or("<unknown>"), message))?;
+            report!("{}  Warning: {}", indent, message);
+        }
+
+        let problems = manifest.as_ref().and_then(|m| m.check_entry(entry));
+        if crc_failed || problems.as_ref().is_some_and(|p| !p.is_empty()) {
+            ctx.checksum_failures += 1;
+        }
+        if let Some(problems) = problems {
+            if problems.is_empty() {
+                report!("{}  Manifest: matches declaration", indent);
+            }
+            for problem in problems {
+                ctx.fail_if_strict(&format!("{}: {}", filename.unwrap_or("<unknown>"), problem))?;
+                report!("{}  Warning: {}", indent, problem);
+            }
+        }
 
-        let file_name = file.name().to_string();
-        let file_size = file.size() as usize;
+        let nested_type = ctx
+            .options
+            .forced_type(entry.content, filename)
+            .unwrap_or_else(|| entry.detected_type());
 
-        // Skip directories
-        if file_name.ends_with('/') {
+        // Placeholder entries would only clutter the output with empty files, but they still
+        // count towards the per-type totals
+        if entry.content.is_empty() && !ctx.options.include_empty {
+            ctx.record(nested_type, 0);
+            report!("{}  [empty, skipped]", indent);
             continue;
         }
 
-        // Read file contents
-        let mut contents = Vec::with_capacity(file_size);
-        if file.read_to_end(&mut contents).is_err() {
-            println!("{}  - {} (read error)", indent, file_name);
+        // Split the container only: write the entry as found, whatever it holds
+        if ctx.options.no_recurse {
+            if nested_type == FileType::AutelContainer {
+                let nested = parse_file_entries(entry.content);
+                check_nested_length(ctx, &nested, entry.content_length, filename, &indent)?;
+            }
+            report!("{}  Type: {}", indent, file_type_name(&nested_type));
+            ctx.record(nested_type, entry.content.len());
+            let name = match &output_name {
+                Some(name) => name.clone(),
+                None => entry_file_name(entry),
+            };
+            ctx.emit(extract_dir.join(name), nested_type, entry.content)?;
             continue;
         }
 
-        // Determine sub output directory
-        let sub_output = extract_dir.as_ref().map(|p| {
-            let file_path = Path::new(&file_name);
-            if let Some(parent) = file_path.parent() {
-                if !parent.as_os_str().is_empty() {
-                    return p.join(parent).to_string_lossy().to_string();
+        // Recursively process the extracted content; a nested container checks its entries
+        // against the declared length when it parses them
+        ctx.declared_len = Some(entry.content_length);
+        let result = process_node_as(
+            ctx,
+            entry.content,
+            output_name.as_deref(),
+            nested_type,
+            &extract_dir,
+            depth + 1,
+        );
+        ctx.declared_len = None;
+        result?;
+    }
+
+    // With `follow_json_refs`, missing files were already reported from the references
+    if let Some(manifest) = manifest.as_ref().filter(|_| !ctx.options.follow_json_refs) {
+        for name in manifest.missing(entries) {
+            let message = format!("manifest declares {} but no entry has that name", name);
+            ctx.fail_if_strict(&message)?;
+            report!("{}  Warning: {}", indent, message);
+        }
+    }
+
+    if let Some(align) = ctx.options.align {
+        check_alignment(ctx, data, entries, align, &indent)?;
+    }
+
+    if !container.gaps.is_empty() {
+        report!();
+        report!(
+            "{}  Gaps: {} bytes unaccounted for between entries, in {} gaps",
+            indent,
+            container.gap_bytes(),
+            container.gaps.len()
+        );
+        for gap in container.gaps.iter().take(MAX_GAPS_LISTED) {
+            report!("{}    0x{:x}: {} bytes", indent, gap.offset, gap.len);
+        }
+        if container.gaps.len() > MAX_GAPS_LISTED {
+            report!(
+                "{}    ... ({} more)",
+                indent,
+                container.gaps.len() - MAX_GAPS_LISTED
+            );
+        }
+    }
+
+    if let Some(trailer) = container.trailer {
+        report!();
+        report!(
+            "{}  Trailer: {} bytes after last entry",
+            indent,
+            trailer.len()
+        );
+        print_hexdump_preview_indented(trailer, 3, &indent);
+    }
+
+    // Print summary
+    let summary = metadata_summary(entries);
+    print_metadata_summary(&summary, &indent);
+    merge_metadata_summary(&mut ctx.metadata_summary, summary);
+    if ctx.options.header_histogram {
+        print_header_histogram(&header_histogram(entries), &indent);
+    }
+    report!("{}Meta correlation: {}", indent, correlate_meta(entries));
+    if ctx.options.crack_meta {
+        report!(
+            "{}Meta checksum: {}",
+            indent,
+            checksum_consensus(entries, |e| e.content_meta)
+        );
+        report!(
+            "{}Header checksum: {}",
+            indent,
+            checksum_consensus(entries, |e| e.header_data)
+        );
+    }
+
+    Ok(())
+}
+
+/// Check that the tag following each entry starts on an `align`-byte boundary
+/// Offsets are relative to the start of the container
+fn check_alignment(
+    ctx: &Context,
+    data: &[u8],
+    entries: &[FileEntry],
+    align: usize,
+    indent: &str,
+) -> io::Result<()> {
+    let mut misaligned = 0;
+    for entry in entries {
+        let next = match find_tag(data, entry.end_offset()) {
+            Some((pos, _)) => pos,
+            None => continue,
+        };
+        if next % align != 0 {
+            misaligned += 1;
+            let message = format!(
+                "tag after {} starts at 0x{:x}, not {}-byte aligned (0x{:x} off)",
+                entry.filename.as_deref().unwrap_or("<unknown>"),
+                next,
+                align,
+                next % align
+            );
+            ctx.fail_if_strict(&message)?;
+            report!("{}  Warning: {}", indent, message);
+        }
+    }
+    if misaligned == 0 {
+        report!();
+        report!(
+            "{}  Alignment: every entry is followed by a {}-byte aligned tag",
+            indent,
+            align
+        );
+    }
+    Ok(())
+}
+
+/// Process a ZIP archive file
+pub fn process_zip(
+    ctx: &mut Context,
+    data: &[u8],
+    zip_name: Option<&str>,
+    rel_dir: &Path,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+
+    // Try to find valid ZIP by scanning for EOCD
+    let (zip_slice, trailer) = match find_eocd_with(data, ctx.options.full_eocd_scan) {
+        Ok(eocd) => data.split_at(eocd.total_size),
+        Err(e @ EocdError::CommentOverrunsBuffer { .. }) => {
+            // Only the comment is cut short; the central directory before it may be intact
+            let msg = format!("{}: {}", zip_name.unwrap_or("<unknown>"), e);
+            ctx.fail_if_strict(&msg)?;
+            report!("{}  Warning: {}", indent, msg);
+            (data, &[][..])
+        }
+        Err(e) => {
+            let local_headers = scan_local_headers(data);
+            ctx.fail_if_strict(&format!(
+                "{}: {} ({} local headers found)",
+                zip_name.unwrap_or("<unknown>"),
+                e,
+                local_headers.len()
+            ))?;
+            if local_headers.is_empty() {
+                report!("{}  → Could not find valid ZIP structure ({})", indent, e);
+            } else {
+                report!(
+                    "{}  → ZIP appears truncated ({}), {} local entries scanned",
+                    indent,
+                    e,
+                    local_headers.len()
+                );
+                for header in &local_headers {
+                    report!(
+                        "{}  - {} (local header at 0x{:x})",
+                        indent,
+                        header.name.as_deref().unwrap_or("<truncated header>"),
+                        header.offset
+                    );
                 }
             }
-            p.to_string_lossy().to_string()
-        });
+            // Still save the raw file
+            if let Some(fname) = zip_name {
+                ctx.emit(rel_dir.join(fname), FileType::Zip, data)?;
+            }
+            return Ok(());
+        }
+    };
 
-        // Get just the filename without directory
-        let just_filename = Path::new(&file_name)
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or(&file_name);
-
-        // Check if this file can be recursively processed
-        let file_type = detect_file_type(&contents, Some(&file_name));
-
-        if file_type == FileType::AutelContainer || file_type == FileType::Zip {
-            println!();
-            // Recursively process
-            process_file(
-                &contents,
-                Some(just_filename),
-                sub_output.as_deref(),
-                depth + 1,
+    // Self-extracting and signed ZIPs carry data past the EOCD, which the archive itself ignores
+    if !trailer.is_empty() {
+        tracing::debug!(
+            offset = zip_slice.len(),
+            size = trailer.len(),
+            "data after ZIP EOCD"
+        );
+        report!(
+            "{}  → {} bytes trailing after ZIP EOCD",
+            indent,
+            trailer.len()
+        );
+        print_hexdump_preview_indented(trailer, 3, &indent);
+        if let Some(fname) = zip_name.filter(|_| ctx.options.zip_trailer) {
+            ctx.emit(
+                rel_dir.join(format!("{}.trailer", fname)),
+                FileType::Unknown,
+                trailer,
             )?;
-        } else {
-            // Just extract, don't recurse for non-container types
-            println!(
-                "{}  - {} ({} bytes) [{}]",
-                indent,
-                file_name,
-                file_size,
-                file_type_name(&file_type)
-            );
+        }
+    }
 
-            if let Some(ref extract_path) = extract_dir {
-                let outpath = extract_path.join(&file_name);
-                if let Some(parent) = outpath.parent() {
-                    fs::create_dir_all(parent)?;
+    // Vet the central directory before the zip crate trusts it
+    let problem = match check_central_directory(zip_slice) {
+        Err(problem) => problem,
+        Ok(()) => match ctx.options.zip_open_timeout {
+            // The archive opened on the worker is the one used, so it's only parsed once
+            Some(timeout) => match open_zip_with_timeout(zip_slice, timeout) {
+                Some(opened) => {
+                    return process_zip_archive(ctx, opened, data, zip_name, rel_dir, depth)
                 }
-                let mut outfile = File::create(&outpath)?;
-                outfile.write_all(&contents)?;
+                None => format!("ZIP open timed out after {:?}", timeout),
+            },
+            None => {
+                let opened = ZipArchive::new(Cursor::new(zip_slice));
+                return process_zip_archive(ctx, opened, data, zip_name, rel_dir, depth);
+            }
+        },
+    };
+    ctx.fail_if_strict(&format!("{}: {}", zip_name.unwrap_or("<unknown>"), problem))?;
+    report!("{}  → Not opening ZIP archive: {}", indent, problem);
+    if let Some(fname) = zip_name {
+        ctx.emit(rel_dir.join(fname), FileType::Zip, data)?;
+    }
+    Ok(())
+}
+
+/// List and extract the members of an opened (or failed-to-open) ZIP
+/// `data` is the whole ZIP file as found, saved next to its extracted members
+fn process_zip_archive<R: Read + Seek + Clone + Send + Sync>(
+    ctx: &mut Context,
+    opened: ZipResult<ZipArchive<R>>,
+    data: &[u8],
+    zip_name: Option<&str>,
+    rel_dir: &Path,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    let mut archive = match opened {
+        Ok(a) => a,
+        Err(e) => {
+            ctx.fail_if_strict(&format!("failed to read ZIP archive: {}", e))?;
+            report!("{}  → Failed to read ZIP archive: {}", indent, e);
+            return Ok(());
+        }
+    };
+
+    report!("{}  → Contains {} files", indent, archive.len());
+    tracing::debug!(members = archive.len(), "ZIP opened");
+    for i in 0..archive.len() {
+        if let Some(time) = archive
+            .by_index_raw(i)
+            .ok()
+            .and_then(|member| member.last_modified())
+            .and_then(dos_timestamp)
+        {
+            ctx.build_times.add(time);
+        }
+    }
+
+    // Members are extracted into a directory named after the ZIP
+    let dir_name = zip_name
+        .map(|n| {
+            Path::new(n)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(n)
+        })
+        .unwrap_or("zip_contents");
+    let extract_dir = rel_dir.join(dir_name);
+
+    // Also save the raw ZIP file
+    if let Some(fname) = zip_name {
+        ctx.emit(rel_dir.join(fname), FileType::Zip, data)?;
+    }
+
+    let password = ctx.options.zip_password.as_deref().map(str::as_bytes);
+    let hash = ctx.options.hash_members;
+    if ctx.options.jobs > 1 {
+        // Decompress, classify and hash members in parallel, then handle them in order
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(ctx.options.jobs)
+            .build()
+            .map_err(io::Error::other)?;
+        let members: Vec<_> = pool.install(|| {
+            (0..archive.len())
+                .into_par_iter()
+                .map_init(
+                    || archive.clone(),
+                    |archive, i| read_zip_member(archive, i, password, hash),
+                )
+                .collect()
+        });
+        for member in members.into_iter().flatten() {
+            if ctx.file_limit_reached {
+                break;
+            }
+            handle_zip_member(ctx, member, data, &extract_dir, depth)?;
+        }
+    } else {
+        for i in 0..archive.len() {
+            if ctx.file_limit_reached {
+                break;
+            }
+            if let Some(member) = read_zip_member(&mut archive, i, password, hash) {
+                handle_zip_member(ctx, member, data, &extract_dir, depth)?;
             }
         }
     }
 
     Ok(())
 }
+
+/// A read ZIP member's contents, detected type and SHA-256 (with `hash_members`)
+type MemberContents = (Vec<u8>, FileType, Option<[u8; 32]>);
+
+/// A ZIP member read out of its archive, ready to be handled
+struct ZipMember {
+    name: String,
+    size: usize,
+    /// Whether the contents' CRC32 matches the one stored in the archive
+    crc_ok: bool,
+    /// The member is encrypted; if `contents` is an error, it could not be decrypted
+    encrypted: bool,
+    /// Offset of a stored, unencrypted member's bytes in the archive, so it can be handed
+    /// on as a slice of the input rather than a copy
+    stored_at: Option<u64>,
+    /// Contents, detected type and digest, or the read error
+    contents: Result<MemberContents, String>,
+}
+
+/// Read, classify and (with `hash`) hash a ZIP member, decrypting it with `password` if it
+/// is encrypted
+/// Returns None for directories and members whose header can't be read
+fn read_zip_member<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    index: usize,
+    password: Option<&[u8]>,
+    hash: bool,
+) -> Option<ZipMember> {
+    let (name, size, encrypted) = {
+        let raw = archive.by_index_raw(index).ok()?;
+        (raw.name().to_string(), raw.size() as usize, raw.encrypted())
+    };
+
+    // Skip directories
+    if name.ends_with('/') {
+        return None;
+    }
+
+    // Without a password the zip crate refuses an encrypted member outright; keep it so it
+    // is reported as encrypted rather than dropped or mistaken for a damaged one
+    let opened = match password.filter(|_| encrypted) {
+        Some(password) => archive.by_index_decrypt(index, password),
+        None => archive.by_index(index),
+    };
+    let mut file = match opened {
+        Ok(file) => file,
+        Err(e) if encrypted => {
+            return Some(ZipMember {
+                name,
+                size,
+                crc_ok: true,
+                encrypted,
+                stored_at: None,
+                contents: Err(e.to_string()),
+            })
+        }
+        Err(_) => return None,
+    };
+
+    let stored_crc = file.crc32();
+    let stored_at =
+        (file.compression() == CompressionMethod::Stored && !encrypted).then(|| file.data_start());
+    let mut contents = Vec::with_capacity(size);
+    let read = file.read_to_end(&mut contents);
+    let crc_ok = crc32fast::hash(&contents) == stored_crc;
+    // The zip crate rejects a bad CRC only once everything is read; keep those contents so
+    // the mismatch is reported and the member still extracted
+    let read = match read {
+        Err(_) if !crc_ok && contents.len() == size => Ok(size),
+        other => other,
+    };
+    let contents = match read {
+        Ok(_) => {
+            let file_type = detect_file_type(&contents, Some(&name));
+            let digest = hash.then(|| sha256(&contents));
+            Ok((contents, file_type, digest))
+        }
+        Err(e) => Err(e.to_string()),
+    };
+
+    Some(ZipMember {
+        name,
+        size,
+        crc_ok,
+        encrypted,
+        stored_at,
+        contents,
+    })
+}
+
+/// Log, extract or recurse into a single ZIP member
+fn handle_zip_member(
+    ctx: &mut Context,
+    member: ZipMember,
+    zip: &[u8],
+    extract_dir: &Path,
+    depth: usize,
+) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    let file_name = member.name;
+
+    let (contents, file_type, digest) = match member.contents {
+        Ok(c) => c,
+        Err(e) if member.encrypted => {
+            ctx.encrypted_members += 1;
+            ctx.fail_if_strict(&format!("{}: encrypted, cannot extract: {}", file_name, e))?;
+            report!(
+                "{}  - {} ({} bytes) [encrypted, cannot extract]",
+                indent,
+                file_name,
+                member.size
+            );
+            if ctx.options.zip_password.is_some() {
+                report!("{}    {}", indent, e);
+            }
+            return Ok(());
+        }
+        Err(e) => {
+            ctx.fail_if_strict(&format!("{}: read error: {}", file_name, e))?;
+            report!("{}  - {} (read error)", indent, file_name);
+            return Ok(());
+        }
+    };
+
+    tracing::debug!(
+        name = %file_name,
+        size = contents.len(),
+        file_type = file_type_name(&file_type),
+        crc_ok = member.crc_ok,
+        "ZIP member"
+    );
+    let crc_note = if member.crc_ok {
+        "[crc ok]"
+    } else {
+        ctx.crc_mismatches += 1;
+        ctx.fail_if_strict(&format!("{}: CRC32 does not match the archive", file_name))?;
+        "[CRC MISMATCH]"
+    };
+
+    let (sub_dir, just_filename) = member_location(ctx, &file_name, extract_dir, &indent)?;
+
+    // The member's type was detected when it was read, so a forced type replaces it here
+    let file_type = ctx
+        .options
+        .forced_type(&contents, Some(&just_filename))
+        .unwrap_or(file_type);
+
+    let digest = digest
+        .map(|d| format!(" sha256:{}", &to_hex(&d)[..16]))
+        .unwrap_or_default();
+    report!();
+    report!(
+        "{}  - {} ({} bytes) [{}]{} {}",
+        indent,
+        file_name,
+        member.size,
+        file_type_name(&file_type),
+        digest,
+        crc_note
+    );
+
+    // A stored member's bytes are the same as they sit in the archive, which keeps its place
+    // in the offset map
+    let in_place = member
+        .stored_at
+        .and_then(|start| usize::try_from(start).ok())
+        .and_then(|start| zip.get(start..start.checked_add(contents.len())?))
+        .filter(|bytes| *bytes == contents.as_slice());
+    process_node_as(
+        ctx,
+        in_place.unwrap_or(&contents),
+        Some(&just_filename),
+        file_type,
+        &sub_dir,
+        depth + 1,
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{build_test_container, build_test_containers, build_zip};
+
+    /// Output paths of everything `process_file_with` hands over, in order
+    fn extracted_paths(
+        data: &[u8],
+        filename: Option<&str>,
+        options: &ProcessOptions,
+    ) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        process_file_with(data, filename, options, &mut |file| {
+            paths.push(file.path.clone());
+            Ok(())
+        })
+        .unwrap();
+        paths
+    }
+
+    /// Output path, type and contents of everything `process_file_with` hands over, in order
+    fn extracted_files(
+        data: &[u8],
+        filename: Option<&str>,
+        options: &ProcessOptions,
+    ) -> Vec<(PathBuf, FileType, Vec<u8>)> {
+        let mut files = Vec::new();
+        process_file_with(data, filename, options, &mut |file| {
+            files.push((file.path.clone(), file.file_type, file.data.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+        files
+    }
+
+    #[test]
+    fn test_tar_members_stay_inside_the_output() {
+        let mut builder = tar::TarBuilder::new(Vec::new());
+        builder.append("../../escape.txt", b"up").unwrap();
+        builder.append("/etc/passwd", b"root").unwrap();
+        builder.append("./ok/file.txt", b"fine").unwrap();
+        let archive = builder.finish().unwrap();
+
+        let paths = extracted_paths(&archive, Some("pkg.tar"), &ProcessOptions::default());
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("pkg.tar"),
+                PathBuf::from("pkg/escape.txt"),
+                PathBuf::from("pkg/etc/passwd"),
+                PathBuf::from("pkg/ok/file.txt"),
+            ]
+        );
+
+        let strict = ProcessOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(process_file(&archive, Some("pkg.tar"), None, &strict).is_err());
+    }
+
+    #[test]
+    fn test_zip_members_stay_inside_the_output() {
+        let zip = build_zip(&[
+            ("../../escape.txt", b"up"),
+            ("/etc/passwd", b"root"),
+            ("./ok/file.txt", b"fine"),
+        ]);
+
+        let paths = extracted_paths(&zip, Some("pkg.zip"), &ProcessOptions::default());
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("pkg.zip"),
+                PathBuf::from("pkg/escape.txt"),
+                PathBuf::from("pkg/etc/passwd"),
+                PathBuf::from("pkg/ok/file.txt"),
+            ]
+        );
+
+        let strict = ProcessOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(process_file(&zip, Some("pkg.zip"), None, &strict).is_err());
+    }
+
+    #[test]
+    fn test_cpio_members_stay_inside_the_output() {
+        let archive = crate::cpio::tests::build_cpio(&[
+            ("a/../../b", 0o100644, b"up"),
+            ("/abs", 0o100644, b"root"),
+        ]);
+
+        let paths = extracted_paths(&archive, Some("rd.cpio"), &ProcessOptions::default());
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("rd.cpio"),
+                PathBuf::from("rd/a/b"),
+                PathBuf::from("rd/abs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_override() {
+        // Too much junk before the nested container for detection to find it
+        let mut nested = vec![0xffu8; 150];
+        nested.extend_from_slice(&build_test_container("inner.txt", b"hello"));
+        let buffer = build_test_containers(&[("blob.dat", &nested), ("notes.dat", b"{\"a\": 1}")]);
+
+        let collect = |options: &ProcessOptions| {
+            extracted_files(&buffer, Some("fw.bin"), options)
+                .into_iter()
+                .map(|(path, file_type, _)| (path, file_type))
+                .collect::<Vec<_>>()
+        };
+
+        let detected = collect(&ProcessOptions::default());
+        assert_eq!(
+            detected[0],
+            (PathBuf::from("fw/blob.dat"), FileType::Unknown)
+        );
+
+        let forced = ProcessOptions {
+            type_overrides: vec![(
+                glob::Pattern::new("blob.*").unwrap(),
+                FileType::AutelContainer,
+            )],
+            ..Default::default()
+        };
+        assert_eq!(
+            collect(&forced),
+            [
+                (PathBuf::from("fw/blob/inner.txt"), FileType::Text),
+                (PathBuf::from("fw/notes.dat"), FileType::Json),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repeated_container_processed_once() {
+        let nested = build_test_container("inner.txt", b"hello");
+        let buffer = build_test_containers(&[("a.bin", &nested), ("b.bin", &nested)]);
+
+        let seen = extracted_paths(&buffer, Some("fw.bin"), &ProcessOptions::default());
+        assert_eq!(
+            seen,
+            [PathBuf::from("fw/a/inner.txt"), PathBuf::from("fw/b.bin")]
+        );
+    }
+
+    #[test]
+    fn test_empty_and_tiny_inputs() {
+        for data in [&b""[..], b"x", b"PK", b"\x1f\x8b"] {
+            for file_type in [
+                FileType::AutelContainer,
+                FileType::Zip,
+                FileType::Tar,
+                FileType::Gzip,
+                FileType::Unknown,
+            ] {
+                process_file_as(
+                    data,
+                    Some("tiny.bin"),
+                    file_type,
+                    None,
+                    &ProcessOptions::default(),
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_extracted_file_json() {
+        let file = ExtractedFile {
+            path: PathBuf::from("fw").join("pkg").join("a.txt"),
+            file_type: FileType::Text,
+            data: b"abc",
+            device: None,
+        };
+        let json = file.to_json();
+        assert_eq!(json["path"], "fw/pkg/a.txt");
+        assert_eq!(json["type"], "Text");
+        assert_eq!(json["size"], 3);
+        assert_eq!(
+            json["sha256"],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(json.get("device").is_none());
+    }
+
+    #[test]
+    fn test_sidecar() {
+        let buffer = build_test_container("notes.txt", b"hello");
+        let options = ProcessOptions {
+            sidecar: true,
+            ..Default::default()
+        };
+
+        let files = extracted_files(&buffer, Some("fw.bin"), &options);
+        assert_eq!(files[0].0, PathBuf::from("fw/notes.txt.meta"));
+        assert_eq!(
+            String::from_utf8_lossy(&files[0].2),
+            "index=0\nfilename=notes.txt\nheader=fdce6948\nmeta=33a83b1f\ndeclared_length=5\nactual_length=5\n"
+        );
+        assert_eq!(files[1].0, PathBuf::from("fw/notes.txt"));
+
+        // Sidecars don't use up the file limit
+        let options = ProcessOptions {
+            max_files: Some(1),
+            ..options
+        };
+        let paths = extracted_paths(&buffer, Some("fw.bin"), &options);
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("fw/notes.txt.meta"),
+                PathBuf::from("fw/notes.txt")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_type_totals() {
+        let buffer = build_test_containers(&[
+            ("a.json", b"{\"a\": 1}"),
+            ("b.json", b"[1, 2]"),
+            ("notes.txt", b"hello"),
+            ("blank.bin", b""),
+        ]);
+
+        let stats =
+            process_file(&buffer, Some("fw.bin"), None, &ProcessOptions::default()).unwrap();
+        let totals: Vec<_> = stats.type_totals.into_iter().collect();
+        assert_eq!(
+            totals,
+            [
+                (FileType::AutelContainer, (1, buffer.len() as u64)),
+                (FileType::Json, (2, 14)),
+                (FileType::Text, (1, 5)),
+                (FileType::Unknown, (1, 0)),
+            ]
+        );
+        // Every entry is counted, the skipped empty one included
+        let files: usize = totals.iter().map(|(_, (count, _))| count).sum();
+        assert_eq!(files, 1 + 4);
+    }
+
+    #[test]
+    fn test_stats_merge() {
+        let mut total = ProcessStats::default();
+        for _ in 0..2 {
+            let mut stats = ProcessStats {
+                files_written: 3,
+                files_skipped: 4,
+                write_failures: 1,
+                ..Default::default()
+            };
+            stats.type_totals.insert(FileType::Json, (2, 100));
+            total.merge(stats);
+        }
+        assert_eq!(total.files_written, 6);
+        assert_eq!(total.files_skipped, 8);
+        assert_eq!(total.write_failures, 2);
+        assert_eq!(total.type_totals[&FileType::Json], (4, 200));
+    }
+
+    #[test]
+    fn test_empty_entries_skipped_by_default() {
+        let buffer = build_test_containers(&[("placeholder.bin", b""), ("notes.txt", b"hello")]);
+
+        let collect = |options: &ProcessOptions| extracted_paths(&buffer, Some("fw.bin"), options);
+
+        assert_eq!(
+            collect(&ProcessOptions::default()),
+            [Path::new("fw/notes.txt")]
+        );
+        let include_empty = ProcessOptions {
+            include_empty: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            collect(&include_empty),
+            [Path::new("fw/placeholder.bin"), Path::new("fw/notes.txt")]
+        );
+    }
+
+    #[test]
+    fn test_group_by_device() {
+        let out_dir = std::env::temp_dir().join(format!("firmparse-group-{}", std::process::id()));
+        let manifest = br#"{"version": "1", "files": [{"name": "cam.bin", "device": "Gimbal"}]}"#;
+        let buffer = build_test_containers(&[
+            ("manifest.json", manifest),
+            ("g.upg", b"\x34\x12\xef\xbe\x01rest"),
+            ("cam.bin", b"\xff\xfe\xfd\xfc"),
+            ("notes.txt", b"hello"),
+        ]);
+
+        let options = ProcessOptions {
+            group_by_device: true,
+            ..Default::default()
+        };
+        let stats = process_file(&buffer, Some("fw.bin"), out_dir.to_str(), &options);
+        let exists = |p: &str| out_dir.join(p).is_file();
+        let layout = [
+            exists("fw/gimbal/g.upg"),
+            exists("fw/gimbal/cam.bin"),
+            exists("fw/other/notes.txt"),
+            exists("fw/other/manifest.json"),
+        ];
+        fs::remove_dir_all(&out_dir).unwrap();
+
+        assert_eq!(stats.unwrap().files_written, 4);
+        assert_eq!(layout, [true; 4]);
+    }
+
+    #[test]
+    fn test_device_hints_stay_in_their_container() {
+        let manifest = br#"{"version": "1", "files": [{"name": "cam.bin", "device": "Gimbal"}]}"#;
+        let tagged = build_test_containers(&[
+            ("manifest.json", manifest),
+            ("cam.bin", b"\xff\xfe\xfd\xfc"),
+        ]);
+        let untagged = build_test_container("cam.bin", b"\xff\xfe\xfd\xfc");
+        let buffer = build_test_containers(&[("a.bin", &tagged), ("b.bin", &untagged)]);
+
+        let devices = |options: &ProcessOptions| {
+            let mut devices = Vec::new();
+            process_file_with(&buffer, Some("fw.bin"), options, &mut |file| {
+                if file.path.to_string_lossy().ends_with("cam.bin") {
+                    devices.push((archive_name(&file.path), file.device.clone()));
+                }
+                Ok(())
+            })
+            .unwrap();
+            devices
+        };
+        let gimbal = Some("gimbal".to_string());
+
+        assert_eq!(
+            devices(&ProcessOptions::default()),
+            [
+                ("fw/a/cam.bin".to_string(), gimbal.clone()),
+                ("fw/b/cam.bin".to_string(), None),
+            ]
+        );
+        let prefixed = ProcessOptions {
+            index_prefix: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            devices(&prefixed),
+            [
+                ("fw/00_a/01_cam.bin".to_string(), gimbal),
+                ("fw/01_b/00_cam.bin".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_device_path_keeps_names_unique() {
+        let mut used = HashSet::new();
+        let mut place = |path: &str| {
+            let file = ExtractedFile {
+                path: PathBuf::from(path),
+                file_type: FileType::Text,
+                data: b"",
+                device: None,
+            };
+            device_path(&file, &mut used)
+        };
+        assert_eq!(place("fw/pkg/a.txt"), Path::new("fw/other/a.txt"));
+        assert_eq!(place("fw/a.txt"), Path::new("fw/other/a.txt~1"));
+        assert_eq!(place("fw/sub/a.txt"), Path::new("fw/other/sub/a.txt"));
+    }
+
+    #[test]
+    fn test_skip_existing() {
+        let out_dir = std::env::temp_dir().join(format!("firmparse-skip-{}", std::process::id()));
+        let buffer = build_test_containers(&[("a.txt", b"hello"), ("b.txt", b"world")]);
+        let run = |options: &ProcessOptions| {
+            process_file(&buffer, Some("fw.bin"), out_dir.to_str(), options).unwrap()
+        };
+
+        assert_eq!(run(&ProcessOptions::default()).files_written, 2);
+
+        // Same size, different content: only a digest comparison notices
+        fs::write(out_dir.join("fw/b.txt"), b"WORLD").unwrap();
+        let by_size = run(&ProcessOptions {
+            skip_existing: true,
+            ..Default::default()
+        });
+        assert_eq!((by_size.files_written, by_size.files_skipped), (0, 2));
+        assert_eq!(fs::read(out_dir.join("fw/b.txt")).unwrap(), b"WORLD");
+
+        let by_digest = run(&ProcessOptions {
+            verify_existing: true,
+            ..Default::default()
+        });
+        let restored = fs::read(out_dir.join("fw/b.txt")).unwrap();
+        fs::remove_dir_all(&out_dir).unwrap();
+
+        assert_eq!((by_digest.files_written, by_digest.files_skipped), (1, 1));
+        assert_eq!(restored, b"world");
+    }
+
+    #[test]
+    fn test_renamed_extraction_repacks() {
+        let out_dir = std::env::temp_dir().join(format!("firmparse-rename-{}", std::process::id()));
+        let buffer =
+            build_test_containers(&[("Gimbal FW.bin", b"\x01\x02\x03"), ("Config.TXT", b"hello")]);
+        let options = ProcessOptions {
+            write_manifest: true,
+            index_prefix: true,
+            name_transforms: crate::naming::parse_transforms("replace-spaces,lowercase").unwrap(),
+            ..Default::default()
+        };
+        process_file(&buffer, Some("fw.bin"), out_dir.to_str(), &options).unwrap();
+        let renamed = out_dir.join("fw/00_gimbal_fw.bin").is_file();
+        let repacked = crate::pack::pack_directory(&out_dir.join("fw"));
+        fs::remove_dir_all(&out_dir).unwrap();
+
+        assert!(renamed);
+        assert_eq!(repacked.unwrap(), buffer);
+    }
+
+    #[test]
+    fn test_verify_writes() {
+        let out_dir = std::env::temp_dir().join(format!("firmparse-verify-{}", std::process::id()));
+        let buffer = build_test_container("a.txt", b"hello");
+        let options = ProcessOptions {
+            verify_writes: true,
+            ..Default::default()
+        };
+        let stats = process_file(&buffer, Some("fw.bin"), out_dir.to_str(), &options).unwrap();
+        assert_eq!((stats.files_written, stats.files_verified), (1, 1));
+
+        // A file that reads back different is an error, not a warning
+        let written = out_dir.join("fw/a.txt");
+        assert!(verify_written(&written, b"hello").is_ok());
+        let err = verify_written(&written, b"hellO").unwrap_err();
+        fs::remove_dir_all(&out_dir).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("does not match"));
+    }
+
+    #[test]
+    fn test_write_failures_do_not_abort() {
+        let out_dir = std::env::temp_dir().join(format!("firmparse-write-{}", std::process::id()));
+        fs::create_dir_all(&out_dir).unwrap();
+        // A plain file where the container's extraction directory should go
+        fs::write(out_dir.join("fw"), b"in the way").unwrap();
+
+        let buffer = build_test_containers(&[("a.txt", b"hello"), ("b.txt", b"world")]);
+        let stats = process_file(
+            &buffer,
+            Some("fw.bin"),
+            out_dir.to_str(),
+            &ProcessOptions::default(),
+        );
+        fs::remove_dir_all(&out_dir).unwrap();
+
+        let stats = stats.unwrap();
+        assert_eq!(stats.write_failures, 2);
+        assert_eq!(stats.files_written, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_output_into_fifo() {
+        let dir = std::env::temp_dir().join(format!("firmparse-fifo-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let fifo = dir.join("entry.bin");
+        let made = std::process::Command::new("mkfifo").arg(&fifo).status();
+        if !made.is_ok_and(|status| status.success()) {
+            fs::remove_dir_all(&dir).unwrap();
+            return;
+        }
+        assert!(is_fifo(&fifo));
+        assert!(!existing_matches(&fifo, b"", false));
+
+        let reader_path = fifo.clone();
+        let reader = std::thread::spawn(move || fs::read(reader_path).unwrap());
+        write_output(&fifo, b"streamed").unwrap();
+        let received = reader.join().unwrap();
+
+        // Still a pipe afterwards, not replaced by a regular file
+        assert!(is_fifo(&fifo));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(received, b"streamed");
+    }
+
+    #[test]
+    fn test_process_file_with_callback() {
+        let buffer =
+            build_test_containers(&[("config.json", b"{\"a\": 1}"), ("notes.txt", b"hello")]);
+
+        let mut seen = Vec::new();
+        process_file_with(
+            &buffer,
+            Some("fw.bin"),
+            &ProcessOptions::default(),
+            &mut |file| {
+                seen.push((file.path.clone(), file.file_type, file.data.to_vec()));
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].0, Path::new("fw/config.json"));
+        assert_eq!(seen[0].1, FileType::Json);
+        assert_eq!(seen[1].0, Path::new("fw/notes.txt"));
+        assert_eq!(seen[1].2, b"hello");
+    }
+
+    #[test]
+    fn test_json_style_only_changes_the_report() {
+        let text = "{ \"b\": [1,  2],\n \"a\": true }";
+        let json: serde_json::Value = serde_json::from_str(text).unwrap();
+        assert_eq!(render_json(JsonStyle::Original, text, &json), text);
+        assert_eq!(
+            render_json(JsonStyle::Minified, text, &json),
+            "{\"a\":true,\"b\":[1,2]}"
+        );
+        assert_eq!(
+            render_json(JsonStyle::Pretty, text, &json),
+            "{\n  \"a\": true,\n  \"b\": [\n    1,\n    2\n  ]\n}"
+        );
+
+        // Whatever the style, the extracted file keeps its bytes
+        let buffer = build_test_containers(&[
+            ("config.json", text.as_bytes()),
+            ("broken.json", b"{ \"b\": "),
+        ]);
+        for json_style in [JsonStyle::Original, JsonStyle::Pretty, JsonStyle::Minified] {
+            let options = ProcessOptions {
+                json_style,
+                ..Default::default()
+            };
+            let mut seen = Vec::new();
+            process_file_with(&buffer, Some("fw.bin"), &options, &mut |file| {
+                seen.push(String::from_utf8(file.data.to_vec()).unwrap());
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(seen, [text, "{ \"b\": "]);
+        }
+    }
+
+    #[test]
+    fn test_gzip_is_decompressed_and_named() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"plain text log").unwrap();
+        let gz = encoder.finish().unwrap();
+        let buffer = build_test_container("logs.txt.gz", &gz);
+
+        let mut paths = Vec::new();
+        process_file_with(
+            &buffer,
+            Some("fw.bin"),
+            &ProcessOptions::default(),
+            &mut |file| {
+                paths.push((file.path.clone(), file.file_type));
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(paths[0], (PathBuf::from("fw/logs.txt.gz"), FileType::Gzip));
+        assert_eq!(paths[1], (PathBuf::from("fw/logs.txt"), FileType::Text));
+    }
+
+    #[test]
+    fn test_tar_gz_inside_zip_is_unpacked() {
+        use flate2::{write::GzEncoder, Compression};
+
+        let mut builder = tar::TarBuilder::new(Vec::new());
+        builder.append("a.txt", b"hello").unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&builder.finish().unwrap()).unwrap();
+        let zip = build_zip(&[("logs.tar.gz", &encoder.finish().unwrap())]);
+
+        let paths = extracted_paths(&zip, Some("pkg.zip"), &ProcessOptions::default());
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("pkg.zip"),
+                PathBuf::from("pkg/logs.tar.gz"),
+                PathBuf::from("pkg/logs.tar"),
+                PathBuf::from("pkg/logs/a.txt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_framed_upg_payload_is_peeled() {
+        let upg = [0x0e, 0x34, 0x12, 0xef, 0xbe, 0x03, 0x00, 0x10, 0x20];
+        let buffer = build_test_container("gimbal.bin", &upg);
+        let run = |framing_skip| {
+            let options = ProcessOptions {
+                framing_skip,
+                ..Default::default()
+            };
+            extracted_files(&buffer, Some("fw.bin"), &options)
+                .into_iter()
+                .map(|(path, file_type, data)| (path, file_type, data.len()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            run(0),
+            [(PathBuf::from("fw/gimbal.bin"), FileType::Unknown, 9)]
+        );
+        // The framed file is still written whole, with its payload next to it
+        assert_eq!(
+            run(1),
+            [
+                (PathBuf::from("fw/gimbal.bin"), FileType::Unknown, 9),
+                (
+                    PathBuf::from("fw/gimbal.bin.unframed"),
+                    FileType::UpgGimbal,
+                    8
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_follow_json_refs_orders_extraction() {
+        let mut buffer = build_test_container("a.txt", b"first");
+        buffer.extend_from_slice(&build_test_container(
+            "list.json",
+            br#"{"components": ["b.txt", "a.txt", "gone.txt"]}"#,
+        ));
+        buffer.extend_from_slice(&build_test_container("b.txt", b"second"));
+        buffer.extend_from_slice(&build_test_container("extra.txt", b"third"));
+        let run = |options: &ProcessOptions| {
+            extracted_paths(&buffer, Some("fw.bin"), options)
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            run(&ProcessOptions::default()),
+            ["fw/a.txt", "fw/list.json", "fw/b.txt", "fw/extra.txt"]
+        );
+        let mut options = ProcessOptions {
+            follow_json_refs: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            run(&options),
+            ["fw/list.json", "fw/b.txt", "fw/a.txt", "fw/extra.txt"]
+        );
+
+        // A reference to a missing file is a warning, so strict mode fails on it
+        options.strict = true;
+        let result = process_file_with(&buffer, Some("fw.bin"), &options, &mut |_| Ok(()));
+        assert!(result.unwrap_err().to_string().contains("gone.txt"));
+
+        // So is a container with no manifest to follow
+        let plain = build_test_container("a.txt", b"aaa");
+        let result = process_file_with(&plain, Some("fw.bin"), &options, &mut |_| Ok(()));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("no manifest found"));
+    }
+
+    #[test]
+    fn test_strict_fails_on_zip_without_eocd() {
+        let mut zip = b"PK\x03\x04".to_vec();
+        zip.extend_from_slice(&[0u8; 40]);
+        let buffer = build_test_container("pkg.zip", &zip);
+
+        let lenient = ProcessOptions::default();
+        assert!(process_file_with(&buffer, None, &lenient, &mut |_| Ok(())).is_ok());
+
+        let strict = ProcessOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let err = process_file_with(&buffer, None, &strict, &mut |_| Ok(())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err
+            .to_string()
+            .starts_with("pkg.zip: no ZIP EOCD record found"));
+    }
+
+    #[test]
+    fn test_nested_length_mismatch() {
+        let mut padded = build_test_container("a.txt", b"hello");
+        padded.extend_from_slice(&[0; 8]);
+        let buffer = build_test_container("inner.bin", &padded);
+
+        let names = extracted_paths(&buffer, None, &ProcessOptions::default());
+        assert!(names.iter().any(|path| path.ends_with("inner/a.txt")));
+
+        let strict = ProcessOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let err = process_file_with(&buffer, None, &strict, &mut |_| Ok(())).unwrap_err();
+        assert!(err.to_string().contains("nested/declared length mismatch"));
+    }
+
+    #[test]
+    fn test_strict_fails_on_truncated_entry() {
+        let mut buffer = build_test_container("short.bin", b"abc");
+        // Declare far more content than is present
+        let len_pos = buffer.len() - 3 - 8;
+        buffer[len_pos..len_pos + 4].copy_from_slice(&100u32.to_be_bytes());
+
+        let lenient = ProcessOptions::default();
+        assert!(process_file_with(&buffer, None, &lenient, &mut |_| Ok(())).is_ok());
+
+        let strict = ProcessOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let err = process_file_with(&buffer, None, &strict, &mut |_| Ok(())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_no_recurse_writes_entries_verbatim() {
+        let inner = build_test_container("inner.txt", b"nested");
+        let buffer = build_test_containers(&[("pkg.bin", &inner), ("notes.txt", b"hello")]);
+
+        let options = ProcessOptions {
+            no_recurse: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            extracted_files(&buffer, Some("fw.bin"), &options),
+            [
+                (PathBuf::from("fw/pkg.bin"), FileType::AutelContainer, inner),
+                (
+                    PathBuf::from("fw/notes.txt"),
+                    FileType::Text,
+                    b"hello".to_vec()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index_prefix_keeps_container_order() {
+        let mut container = build_test_container("zeta.txt", b"first");
+        container.extend(build_test_container("alpha.txt", b"second"));
+        let options = ProcessOptions {
+            index_prefix: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            extracted_paths(&container, Some("fw.bin"), &options),
+            [
+                PathBuf::from("fw/00_zeta.txt"),
+                PathBuf::from("fw/01_alpha.txt")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_files_stops_extraction() {
+        let mut container = Vec::new();
+        for i in 0..5 {
+            container.extend(build_test_container(&format!("{}.txt", i), b"tiny"));
+        }
+        let options = ProcessOptions {
+            max_files: Some(2),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            extracted_paths(&container, Some("fw.bin"), &options),
+            [PathBuf::from("fw/0.txt"), PathBuf::from("fw/1.txt")]
+        );
+
+        let stats = process_file(&container, Some("fw.bin"), None, &options).unwrap();
+        assert!(stats.file_limit_reached);
+        let unlimited = process_file(&container, Some("fw.bin"), None, &Default::default());
+        assert!(!unlimited.unwrap().file_limit_reached);
+    }
+
+    #[test]
+    fn test_truncation_summary() {
+        let mut buffer = build_test_container("short.bin", b"abc");
+        let len_pos = buffer.len() - 3 - 8;
+        buffer[len_pos..len_pos + 4].copy_from_slice(&100u32.to_be_bytes());
+
+        let stats =
+            process_file(&buffer, Some("fw.bin"), None, &ProcessOptions::default()).unwrap();
+        assert_eq!(stats.truncated_bytes, 97);
+        let json = stats.to_json();
+        assert_eq!(json["truncated_entries"], 1);
+        assert_eq!(json["truncated_bytes"], 97);
+        assert_eq!(json["input_bytes"], buffer.len());
+        assert_eq!(json["types"]["Autel Container"]["count"], 1);
+
+        let options = ProcessOptions::default();
+        let mut on_file = |_: &ExtractedFile| Ok(());
+        let mut ctx = Context::new(&options, &mut on_file);
+        process_node(&mut ctx, &buffer, Some("fw.bin"), Path::new(""), 0).unwrap();
+        let worst = ctx.worst_truncation.as_ref().unwrap();
+        assert_eq!(worst.path, PathBuf::from("fw/short.bin"));
+        assert_eq!(worst.missing, 97);
+        assert_eq!(worst.declared_end, len_pos + 8 + 100);
+        assert_eq!(worst.declared_end - worst.buffer_len, 97);
+    }
+
+    #[test]
+    fn test_strict_fails_on_broken_zip() {
+        let buffer = build_test_container("pkg.zip", b"PK\x03\x04 no central directory");
+        let strict = ProcessOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(process_file_with(&buffer, None, &strict, &mut |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_strict_fails_on_misaligned_entry() {
+        let mut aligned = build_test_container("a.txt", b"hello");
+        aligned.resize(aligned.len().next_multiple_of(16), 0);
+        aligned.extend_from_slice(&build_test_container("b.txt", b"world"));
+        let mut misaligned = aligned.clone();
+        misaligned.insert(0, b' ');
+
+        let strict = ProcessOptions {
+            strict: true,
+            align: Some(16),
+            ..Default::default()
+        };
+        assert!(process_file_with(&aligned, None, &strict, &mut |_| Ok(())).is_ok());
+        assert!(process_file_with(&misaligned, None, &strict, &mut |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_strict_fails_on_malformed_entry() {
+        let mut buffer = b"\"<filetransfer>\"\"<bogus>\"".to_vec();
+        buffer.extend_from_slice(&build_test_container("ok.txt", b"fine"));
+        let strict = ProcessOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(process_file_with(&buffer, None, &strict, &mut |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_zip_trailer_sidecar() {
+        let mut zip = build_zip(&[("cfg.json", b"{}")]);
+        zip.extend_from_slice(b"SIGNATURE");
+
+        let files = |options: &ProcessOptions| extracted_files(&zip, Some("pkg.zip"), options);
+
+        let without = files(&ProcessOptions::default());
+        assert!(!without
+            .iter()
+            .any(|(path, _, _)| path.ends_with("pkg.zip.trailer")));
+
+        let with = files(&ProcessOptions {
+            zip_trailer: true,
+            ..Default::default()
+        });
+        assert!(with
+            .iter()
+            .any(|(path, _, data)| path == Path::new("pkg.zip.trailer") && data == b"SIGNATURE"));
+        assert!(with
+            .iter()
+            .any(|(path, _, _)| path.ends_with("pkg/cfg.json")));
+    }
+
+    #[test]
+    fn test_offset_regions() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        writer.start_file("cfg.json", stored).unwrap();
+        writer.write_all(b"{\"a\": 1}").unwrap();
+        let zip = writer.finish().unwrap().into_inner();
+        let buffer = build_test_containers(&[("pkg.zip", &zip), ("notes.txt", b"hello")]);
+
+        let stats =
+            process_file(&buffer, Some("fw.bin"), None, &ProcessOptions::default()).unwrap();
+        let range = |path: &str| {
+            stats
+                .regions
+                .iter()
+                .find(|region| region.path == path)
+                .unwrap_or_else(|| panic!("no region for {}", path))
+                .range
+                .clone()
+        };
+
+        assert_eq!(range("fw.bin"), Some(0..buffer.len()));
+        let notes = range("fw/notes.txt").unwrap();
+        assert_eq!(&buffer[notes], b"hello");
+        let member = range("fw/pkg/cfg.json").unwrap();
+        assert_eq!(&buffer[member], b"{\"a\": 1}");
+    }
+
+    #[test]
+    fn test_zip_member_crc_mismatch() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        for (name, data) in [("good.txt", b"intact"), ("bad.txt", b"damage")] {
+            writer.start_file(name, stored).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        let mut zip = writer.finish().unwrap().into_inner();
+        let at = zip.windows(6).position(|w| w == b"damage").unwrap();
+        zip[at] = b'D';
+
+        for jobs in [1, 2] {
+            let options = ProcessOptions {
+                jobs,
+                ..Default::default()
+            };
+            let stats = process_file(&zip, Some("pkg.zip"), None, &options).unwrap();
+            assert_eq!(stats.crc_mismatches, 1);
+
+            let extracted = extracted_paths(&zip, Some("pkg.zip"), &options);
+            assert!(extracted.contains(&PathBuf::from("pkg/bad.txt")));
+        }
+
+        let strict = ProcessOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(process_file(&zip, Some("pkg.zip"), None, &strict).is_err());
+    }
+
+    #[test]
+    fn test_zip_opened_under_timeout_is_extracted() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("a.txt", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"inside").unwrap();
+        let zip = writer.finish().unwrap().into_inner();
+
+        let options = ProcessOptions {
+            zip_open_timeout: Some(std::time::Duration::from_secs(10)),
+            jobs: 2,
+            ..Default::default()
+        };
+        let files = extracted_files(&zip, Some("pkg.zip"), &options);
+        assert!(files
+            .iter()
+            .any(|(path, _, data)| path == Path::new("pkg/a.txt") && data == b"inside"));
+    }
+
+    #[test]
+    fn test_encrypted_zip_member() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let plain = zip::write::SimpleFileOptions::default();
+        writer.start_file("open.txt", plain).unwrap();
+        writer.write_all(b"readable").unwrap();
+        let aes = plain.with_aes_encryption(zip::AesMode::Aes256, "hunter2");
+        writer.start_file("secret.txt", aes).unwrap();
+        writer.write_all(b"classified").unwrap();
+        let zip = writer.finish().unwrap().into_inner();
+
+        let extracted = |options: &ProcessOptions| {
+            let stats = process_file(&zip, Some("pkg.zip"), None, options).unwrap();
+            (stats, extracted_files(&zip, Some("pkg.zip"), options))
+        };
+        let secret = |(path, _, data): &(PathBuf, FileType, Vec<u8>)| {
+            path == Path::new("pkg/secret.txt") && data == b"classified"
+        };
+
+        for password in [None, Some("wrong")] {
+            for jobs in [1, 2] {
+                let (stats, files) = extracted(&ProcessOptions {
+                    jobs,
+                    zip_password: password.map(str::to_string),
+                    ..Default::default()
+                });
+                assert_eq!(stats.encrypted_members, 1);
+                assert_eq!(stats.crc_mismatches, 0);
+                assert!(!files.iter().any(secret));
+                assert!(files
+                    .iter()
+                    .any(|(path, _, _)| path.ends_with("pkg/open.txt")));
+            }
+        }
+
+        let (stats, files) = extracted(&ProcessOptions {
+            zip_password: Some("hunter2".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(stats.encrypted_members, 0);
+        assert!(files.iter().any(secret));
+
+        let strict = ProcessOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(process_file(&zip, Some("pkg.zip"), None, &strict).is_err());
+    }
+
+    #[test]
+    fn test_parallel_zip_matches_serial_order() {
+        let names: Vec<String> = (0..20).map(|i| format!("dir/file{:02}.txt", i)).collect();
+        let members: Vec<(&str, &[u8])> =
+            names.iter().map(|n| (n.as_str(), n.as_bytes())).collect();
+        let buffer = build_test_container("pkg.zip", &build_zip(&members));
+
+        let collect = |jobs: usize| {
+            let options = ProcessOptions {
+                jobs,
+                ..Default::default()
+            };
+            extracted_paths(&buffer, Some("fw.bin"), &options)
+        };
+
+        let serial = collect(1);
+        assert_eq!(serial.len(), 21); // raw ZIP + 20 members
+        assert_eq!(serial[1], Path::new("fw/pkg/dir/file00.txt"));
+        assert_eq!(collect(4), serial);
+    }
+
+    #[test]
+    fn test_callback_error_aborts() {
+        let buffer = build_test_container("notes.txt", b"hello");
+        let result = process_file_with(
+            &buffer,
+            Some("fw.bin"),
+            &ProcessOptions::default(),
+            &mut |_| Err(io::Error::other("upload failed")),
+        );
+        assert!(result.is_err());
+    }
+}
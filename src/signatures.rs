@@ -0,0 +1,86 @@
+use crate::file_types::FileType;
+
+/// A user-registered magic-byte signature
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    /// Label shown when the signature matches
+    pub name: String,
+    /// Where in the data the magic bytes must appear
+    pub offset: usize,
+    pub magic: Vec<u8>,
+    /// Type files matching the signature are processed as
+    pub file_type: FileType,
+}
+
+impl Signature {
+    pub fn matches(&self, data: &[u8]) -> bool {
+        !self.magic.is_empty()
+            && self
+                .offset
+                .checked_add(self.magic.len())
+                .and_then(|end| data.get(self.offset..end))
+                == Some(&self.magic[..])
+    }
+}
+
+/// Extra signatures checked before the built-in detection
+/// Lets new device families be recognized without code changes
+#[derive(Debug, Clone, Default)]
+pub struct SignatureRegistry {
+    signatures: Vec<Signature>,
+}
+
+impl SignatureRegistry {
+    pub fn register(&mut self, signature: Signature) {
+        self.signatures.push(signature);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// The first registered signature matching the data, in registration order
+    pub fn detect(&self, data: &[u8]) -> Option<&Signature> {
+        self.signatures.iter().find(|s| s.matches(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature(name: &str, offset: usize, magic: &[u8], file_type: FileType) -> Signature {
+        Signature {
+            name: name.to_string(),
+            offset,
+            magic: magic.to_vec(),
+            file_type,
+        }
+    }
+
+    #[test]
+    fn test_detect_in_registration_order() {
+        let mut registry = SignatureRegistry::default();
+        registry.register(signature("evo-fw", 4, b"EVO2", FileType::AutelContainer));
+        registry.register(signature("any-evo", 4, b"EVO", FileType::Zip));
+
+        assert_eq!(registry.detect(b"\0\0\0\0EVO2rest").unwrap().name, "evo-fw");
+        assert_eq!(
+            registry.detect(b"\0\0\0\0EVO1rest").unwrap().name,
+            "any-evo"
+        );
+        assert!(registry.detect(b"EVO2").is_none());
+        assert!(registry.detect(b"").is_none());
+    }
+
+    #[test]
+    fn test_empty_magic_never_matches() {
+        let mut registry = SignatureRegistry::default();
+        registry.register(signature("empty", 0, b"", FileType::Zip));
+        assert!(registry.detect(b"anything").is_none());
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt;
+
+use crate::display::format_size;
+use crate::timestamp::Timestamp;
+
+/// Magic of a little-endian SquashFS image ("hsqs" on disk)
+pub const SQUASHFS_MAGIC_LE: [u8; 4] = *b"hsqs";
+/// Magic of a big-endian SquashFS image, written by some 1.x-3.x era tools
+pub const SQUASHFS_MAGIC_BE: [u8; 4] = *b"sqsh";
+
+/// Size of a version 4 superblock
+const SUPERBLOCK_V4_LEN: usize = 96;
+
+/// The parts of a SquashFS superblock worth showing for an embedded image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Superblock {
+    pub big_endian: bool,
+    pub version_major: u16,
+    pub version_minor: u16,
+    /// Compressor id; versions before 4 always use zlib
+    pub compression: u16,
+    pub inode_count: u32,
+    /// Bytes the filesystem occupies; only version 4 has it at a fixed offset
+    pub bytes_used: Option<u64>,
+    pub block_size: Option<u32>,
+    pub modified: Option<Timestamp>,
+}
+
+impl Superblock {
+    pub fn compression_name(&self) -> &'static str {
+        match self.compression {
+            1 => "gzip",
+            2 => "lzma",
+            3 => "lzo",
+            4 => "xz",
+            5 => "lz4",
+            6 => "zstd",
+            _ => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for Superblock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SquashFS {}.{} ({}), {} compression, {} inodes",
+            self.version_major,
+            self.version_minor,
+            if self.big_endian {
+                "big-endian"
+            } else {
+                "little-endian"
+            },
+            self.compression_name(),
+            self.inode_count
+        )?;
+        if let Some(size) = self.block_size {
+            write!(f, ", {} blocks", format_size(size as u64))?;
+        }
+        if let Some(bytes) = self.bytes_used {
+            write!(f, ", image size {} bytes", bytes)?;
+        }
+        if let Some(time) = self.modified {
+            write!(f, ", built {}", time)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether data opens with a SquashFS magic followed by a known major version (1-4)
+pub fn is_squashfs(data: &[u8]) -> bool {
+    squashfs_version(data).is_some_and(|(major, _)| (1..=4).contains(&major))
+}
+
+/// Major version and byte order; the magic and version sit at the same offsets in every version
+fn squashfs_version(data: &[u8]) -> Option<(u16, bool)> {
+    let big_endian = match data.get(..4)? {
+        m if m == SQUASHFS_MAGIC_LE => false,
+        m if m == SQUASHFS_MAGIC_BE => true,
+        _ => return None,
+    };
+    Some((read_u16(data, 28, big_endian)?, big_endian))
+}
+
+/// Parse the superblock at the start of a SquashFS image
+pub fn parse_superblock(data: &[u8]) -> Option<Superblock> {
+    if !is_squashfs(data) {
+        return None;
+    }
+    let (version_major, big_endian) = squashfs_version(data)?;
+    let version_minor = read_u16(data, 30, big_endian)?;
+    let inode_count = read_u32(data, 4, big_endian)?;
+
+    if version_major < 4 {
+        return Some(Superblock {
+            big_endian,
+            version_major,
+            version_minor,
+            compression: 1,
+            inode_count,
+            bytes_used: None,
+            block_size: None,
+            modified: None,
+        });
+    }
+
+    if data.len() < SUPERBLOCK_V4_LEN {
+        return None;
+    }
+    let mtime = read_u32(data, 8, big_endian)?;
+    Some(Superblock {
+        big_endian,
+        version_major,
+        version_minor,
+        compression: read_u16(data, 20, big_endian)?,
+        inode_count,
+        bytes_used: Some(read_u64(data, 40, big_endian)?),
+        block_size: Some(read_u32(data, 12, big_endian)?),
+        modified: (mtime != 0).then(|| Timestamp::from_unix(mtime as u64)),
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn read_u32(data: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+fn read_u64(data: &[u8], offset: usize, big_endian: bool) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?.try_into().ok()?;
+    Some(if big_endian {
+        u64::from_be_bytes(bytes)
+    } else {
+        u64::from_le_bytes(bytes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A version 4 little-endian superblock with xz compression
+    fn superblock_v4() -> Vec<u8> {
+        let mut data = vec![0u8; SUPERBLOCK_V4_LEN];
+        data[..4].copy_from_slice(&SQUASHFS_MAGIC_LE);
+        data[4..8].copy_from_slice(&42u32.to_le_bytes());
+        data[8..12].copy_from_slice(&1_709_210_096u32.to_le_bytes());
+        data[12..16].copy_from_slice(&131_072u32.to_le_bytes());
+        data[20..22].copy_from_slice(&4u16.to_le_bytes());
+        data[28..30].copy_from_slice(&4u16.to_le_bytes());
+        data[30..32].copy_from_slice(&0u16.to_le_bytes());
+        data[40..48].copy_from_slice(&4096u64.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_parse_v4_superblock() {
+        let sb = parse_superblock(&superblock_v4()).unwrap();
+        assert!(!sb.big_endian);
+        assert_eq!((sb.version_major, sb.version_minor), (4, 0));
+        assert_eq!(sb.compression_name(), "xz");
+        assert_eq!(sb.inode_count, 42);
+        assert_eq!(sb.bytes_used, Some(4096));
+        assert_eq!(
+            sb.to_string(),
+            "SquashFS 4.0 (little-endian), xz compression, 42 inodes, 128.0 KiB blocks, \
+             image size 4096 bytes, built 2024-02-29 12:34:56"
+        );
+    }
+
+    #[test]
+    fn test_parse_old_big_endian_superblock() {
+        let mut data = vec![0u8; 64];
+        data[..4].copy_from_slice(&SQUASHFS_MAGIC_BE);
+        data[4..8].copy_from_slice(&7u32.to_be_bytes());
+        data[28..30].copy_from_slice(&3u16.to_be_bytes());
+        data[30..32].copy_from_slice(&1u16.to_be_bytes());
+
+        let sb = parse_superblock(&data).unwrap();
+        assert!(sb.big_endian);
+        assert_eq!((sb.version_major, sb.version_minor), (3, 1));
+        assert_eq!(sb.compression_name(), "gzip");
+        assert_eq!(sb.inode_count, 7);
+        assert_eq!(sb.bytes_used, None);
+    }
+
+    #[test]
+    fn test_rejects_bad_version_and_truncation() {
+        let mut data = superblock_v4();
+        data[28..30].copy_from_slice(&9u16.to_le_bytes());
+        assert!(!is_squashfs(&data));
+        assert_eq!(parse_superblock(&superblock_v4()[..64]), None);
+        assert!(!is_squashfs(b"hsqs"));
+    }
+}
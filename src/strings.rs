@@ -0,0 +1,84 @@
+use std::borrow::Cow;
+
+/// Minimum run length for `--strings` when none is given
+pub const DEFAULT_MIN_STRING_LEN: usize = 6;
+
+/// A run of printable ASCII found in binary data
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FoundString<'a> {
+    /// Offset of the first character within the scanned data
+    pub offset: usize,
+    pub text: &'a str,
+}
+
+fn is_printable(b: u8) -> bool {
+    b == b'\t' || (0x20..0x7f).contains(&b)
+}
+
+/// Every run of at least `min_len` printable ASCII characters (space through `~`, and tab),
+/// in offset order, like the `strings` utility
+pub fn ascii_strings(data: &[u8], min_len: usize) -> Vec<FoundString<'_>> {
+    let min_len = min_len.max(1);
+    let mut found = Vec::new();
+    let mut start = None;
+
+    for (i, &b) in data.iter().chain(std::iter::once(&0)).enumerate() {
+        match (is_printable(b) && i < data.len(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                if i - s >= min_len {
+                    // Printable ASCII is always valid UTF-8
+                    if let Ok(text) = std::str::from_utf8(&data[s..i]) {
+                        found.push(FoundString { offset: s, text });
+                    }
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    found
+}
+
+/// The data as text for a preview, with invalid UTF-8 replaced by U+FFFD, and how many
+/// bytes were invalid (0 when the data is valid UTF-8 and is borrowed as-is)
+pub fn lossy_text(data: &[u8]) -> (Cow<'_, str>, usize) {
+    let invalid = data.utf8_chunks().map(|chunk| chunk.invalid().len()).sum();
+    (String::from_utf8_lossy(data), invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ascii_strings() {
+        let data = b"\x00\x01Version 1.2.3\xffab\x00tail end";
+        let found = ascii_strings(data, 6);
+        assert_eq!(
+            found,
+            [
+                FoundString {
+                    offset: 2,
+                    text: "Version 1.2.3"
+                },
+                FoundString {
+                    offset: 19,
+                    text: "tail end"
+                },
+            ]
+        );
+        assert_eq!(ascii_strings(data, 2).len(), 3);
+        assert!(ascii_strings(b"", 6).is_empty());
+    }
+
+    #[test]
+    fn test_lossy_text() {
+        assert_eq!(lossy_text(b"plain log"), (Cow::Borrowed("plain log"), 0));
+
+        let (text, invalid) = lossy_text(b"boot ok\xff\xfe\nnext line\xc3");
+        assert_eq!(text, "boot ok\u{fffd}\u{fffd}\nnext line\u{fffd}");
+        assert_eq!(invalid, 3);
+    }
+}
@@ -0,0 +1,282 @@
+use std::io::{self, Write};
+
+/// Size of a tar header and of the blocks member data is padded to
+const BLOCK_SIZE: usize = 512;
+
+/// A regular file inside a tar archive
+#[derive(Debug, PartialEq)]
+pub struct TarEntry {
+    pub name: String,
+    pub size: usize,
+    /// Offset of the member's data within the archive
+    pub offset: usize,
+}
+
+/// Read a NUL-terminated string field
+fn read_str(field: &[u8]) -> String {
+    let len = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..len]).to_string()
+}
+
+/// Read an octal number field (space/NUL padded)
+fn read_octal(field: &[u8]) -> Option<usize> {
+    let digits: String = field
+        .iter()
+        .map(|&b| b as char)
+        .filter(|c| !matches!(c, ' ' | '\0'))
+        .collect();
+    if digits.is_empty() {
+        return Some(0);
+    }
+    usize::from_str_radix(&digits, 8).ok()
+}
+
+/// Whether the header's stored checksum matches its contents
+/// The checksum is the byte sum of the header with the checksum field taken as spaces
+fn checksum_valid(header: &[u8]) -> bool {
+    let stored = match read_octal(&header[148..156]) {
+        Some(s) => s,
+        None => return false,
+    };
+    let sum: usize = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (148..156).contains(&i) {
+                b' ' as usize
+            } else {
+                b as usize
+            }
+        })
+        .sum();
+    sum == stored
+}
+
+/// Returns true if `data` starts with a tar header
+/// Either the `ustar` magic at offset 257 or, for old-style archives, a valid header checksum
+pub fn is_tar(data: &[u8]) -> bool {
+    let header = match data.get(..BLOCK_SIZE) {
+        Some(h) => h,
+        None => return false,
+    };
+    if &header[257..262] == b"ustar" {
+        return true;
+    }
+    header[0] != 0 && checksum_valid(header)
+}
+
+/// List the regular files in a tar archive, in archive order
+/// Stops at the end-of-archive marker or the first header that doesn't check out
+pub fn list_entries(data: &[u8]) -> Vec<TarEntry> {
+    let mut entries = Vec::new();
+    let mut pos = 0;
+
+    while let Some(header) = data.get(pos..pos + BLOCK_SIZE) {
+        if header.iter().all(|&b| b == 0) || !checksum_valid(header) {
+            break;
+        }
+        let size = match read_octal(&header[124..136]) {
+            Some(s) => s,
+            None => break,
+        };
+
+        let mut name = read_str(&header[0..100]);
+        if &header[257..262] == b"ustar" {
+            let prefix = read_str(&header[345..500]);
+            if !prefix.is_empty() {
+                name = format!("{}/{}", prefix, name);
+            }
+        }
+
+        let offset = pos + BLOCK_SIZE;
+        // Directories, links and extended headers have no content worth listing
+        if matches!(header[156], b'0' | 0) {
+            entries.push(TarEntry { name, size, offset });
+        }
+
+        pos = match offset.checked_add(size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    entries
+}
+
+/// The data of a member, clamped to the archive if it was truncated
+pub fn entry_data<'a>(data: &'a [u8], entry: &TarEntry) -> &'a [u8] {
+    let start = entry.offset.min(data.len());
+    let end = entry.offset.saturating_add(entry.size).min(data.len());
+    &data[start..end]
+}
+
+/// Writes a ustar archive of regular files
+pub struct TarBuilder<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TarBuilder<W> {
+    pub fn new(out: W) -> Self {
+        TarBuilder { out }
+    }
+
+    /// Append a regular file; names over 100 bytes are split into the ustar prefix field
+    pub fn append(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let mut header = [0u8; BLOCK_SIZE];
+        let (prefix, name) = split_name(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("name too long for a tar header: {}", name),
+            )
+        })?;
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[108..115].copy_from_slice(b"0000000");
+        header[116..123].copy_from_slice(b"0000000");
+        let size = format!("{:011o}", data.len());
+        if size.len() > 11 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("file too large for a tar header: {}", name),
+            ));
+        }
+        header[124..135].copy_from_slice(size.as_bytes());
+        header[136..147].copy_from_slice(b"00000000000");
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        header[148..156].copy_from_slice(b"        ");
+        let sum: usize = header.iter().map(|&b| b as usize).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+
+        self.out.write_all(&header)?;
+        self.out.write_all(data)?;
+        let padding = data.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE - data.len();
+        self.out.write_all(&[0u8; BLOCK_SIZE][..padding])
+    }
+
+    /// Write the end-of-archive marker and hand back the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0u8; BLOCK_SIZE * 2])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Split a path into ustar (prefix, name) fields of at most 155 and 100 bytes
+fn split_name(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= 100 {
+        return Some(("", path));
+    }
+    path.match_indices('/')
+        .map(|(i, _)| (&path[..i], &path[i + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && name.len() <= 100 && !name.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(name: &str, size: usize, typeflag: u8, ustar: bool) -> Vec<u8> {
+        let mut header = vec![0u8; BLOCK_SIZE];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{:011o}", size).as_bytes());
+        header[156] = typeflag;
+        if ustar {
+            header[257..263].copy_from_slice(b"ustar\0");
+            header[263..265].copy_from_slice(b"00");
+        }
+        header[148..156].copy_from_slice(b"        ");
+        let sum: usize = header.iter().map(|&b| b as usize).sum();
+        header[148..155].copy_from_slice(format!("{:06o}\0", sum).as_bytes());
+        header
+    }
+
+    fn archive(members: &[(&str, &[u8])], ustar: bool) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (name, content) in members {
+            data.extend_from_slice(&header(name, content.len(), b'0', ustar));
+            data.extend_from_slice(content);
+            data.resize(data.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+        }
+        data.extend_from_slice(&[0u8; BLOCK_SIZE * 2]);
+        data
+    }
+
+    #[test]
+    fn test_is_tar() {
+        assert!(is_tar(&archive(&[("a.txt", b"hello")], true)));
+        // Pre-POSIX archives have no magic, only a checksum
+        assert!(is_tar(&archive(&[("a.txt", b"hello")], false)));
+        assert!(!is_tar(&[0u8; 1024]));
+        assert!(!is_tar(&[b'x'; 1024]));
+        assert!(!is_tar(b"short"));
+    }
+
+    #[test]
+    fn test_list_entries() {
+        let big = vec![0xAB; 700];
+        let data = archive(&[("a.txt", b"hello"), ("dir/big.bin", &big)], true);
+
+        let entries = list_entries(&data);
+        assert_eq!(
+            entries,
+            [
+                TarEntry {
+                    name: "a.txt".to_string(),
+                    size: 5,
+                    offset: 512,
+                },
+                TarEntry {
+                    name: "dir/big.bin".to_string(),
+                    size: 700,
+                    offset: 1536,
+                },
+            ]
+        );
+        assert_eq!(entry_data(&data, &entries[0]), b"hello");
+        assert_eq!(entry_data(&data, &entries[1]), &big[..]);
+    }
+
+    #[test]
+    fn test_list_entries_skips_directories() {
+        let mut data = header("dir/", 0, b'5', true);
+        data.extend_from_slice(&archive(&[("dir/a.txt", b"x")], true));
+
+        let entries = list_entries(&data);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "dir/a.txt");
+    }
+
+    #[test]
+    fn test_builder_roundtrip() {
+        let long_dir = "d".repeat(120);
+        let long_name = format!("{}/file.bin", long_dir);
+        let mut builder = TarBuilder::new(Vec::new());
+        builder.append("fw/config.json", b"{}").unwrap();
+        builder.append(&long_name, &[7u8; 600]).unwrap();
+        assert!(builder.append(&"x".repeat(300), b"").is_err());
+        let data = builder.finish().unwrap();
+
+        assert!(is_tar(&data));
+        let entries = list_entries(&data);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "fw/config.json");
+        assert_eq!(entry_data(&data, &entries[0]), b"{}");
+        assert_eq!(entries[1].name, long_name);
+        assert_eq!(entry_data(&data, &entries[1]), &[7u8; 600][..]);
+    }
+
+    #[test]
+    fn test_truncated_member_is_clamped() {
+        let mut data = archive(&[("a.bin", &[1u8; 400])], true);
+        data.truncate(BLOCK_SIZE + 100);
+
+        let entries = list_entries(&data);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entry_data(&data, &entries[0]).len(), 100);
+    }
+}
@@ -0,0 +1,59 @@
+//! Fixture builders shared by the unit tests
+//!
+//! The binary's tests include this file too, by path, since they can't see the library's
+//! test-only modules.
+
+use std::io::{Cursor, Write};
+
+use zip::write::{SimpleFileOptions, ZipWriter};
+
+/// One container entry with the given content meta and declared content length, which
+/// may disagree with the content actually appended
+pub(crate) fn build_entry(filename: &str, content: &[u8], meta: [u8; 4], declared: u32) -> Vec<u8> {
+    let mut buffer = Vec::new();
+
+    // "<filetransfer>" and "<fileinfo>" tags
+    buffer.extend_from_slice(b"\"<filetransfer>\"\"<fileinfo>\"");
+
+    // File info: 4 bytes name length + 4 bytes header + name
+    buffer.extend_from_slice(&(filename.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(&[0xfd, 0xce, 0x69, 0x48]);
+    buffer.extend_from_slice(filename.as_bytes());
+
+    // "<filecontent>" tag, then 4 bytes length + 4 bytes meta + content
+    buffer.extend_from_slice(b"\"<filecontent>\"");
+    buffer.extend_from_slice(&declared.to_be_bytes());
+    buffer.extend_from_slice(&meta);
+    buffer.extend_from_slice(content);
+    buffer
+}
+
+/// One container entry with a fixed content meta and its true content length
+pub(crate) fn build_test_container(filename: &str, content: &[u8]) -> Vec<u8> {
+    build_entry(
+        filename,
+        content,
+        [0x33, 0xa8, 0x3b, 0x1f],
+        content.len() as u32,
+    )
+}
+
+/// A container holding each `(filename, content)` pair, in order
+pub(crate) fn build_test_containers(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    entries
+        .iter()
+        .flat_map(|(name, content)| build_test_container(name, content))
+        .collect()
+}
+
+/// A ZIP of deflated members, in order
+pub(crate) fn build_zip(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in members {
+        writer
+            .start_file(*name, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(content).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+
+/// A calendar date and time as stored in an archive, with no time zone
+/// gzip MTIME is UTC while ZIP's DOS datetime is the packer's local time, so ranges that
+/// mix them are only accurate to within a day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Timestamp {
+    /// Convert seconds since the Unix epoch (UTC)
+    pub fn from_unix(secs: u64) -> Timestamp {
+        let days = (secs / 86_400) as i64;
+        let rem = secs % 86_400;
+
+        // Days to civil date, after Howard Hinnant's `civil_from_days`
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+        let year = (yoe + era * 400 + i64::from(month <= 2)) as u16;
+
+        Timestamp {
+            year,
+            month,
+            day,
+            hour: (rem / 3600) as u8,
+            minute: (rem % 3600 / 60) as u8,
+            second: (rem % 60) as u8,
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// Oldest and newest of a set of embedded timestamps
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TimeRange {
+    pub oldest: Option<Timestamp>,
+    pub newest: Option<Timestamp>,
+    pub count: usize,
+}
+
+impl TimeRange {
+    pub fn add(&mut self, time: Timestamp) {
+        self.oldest = Some(self.oldest.map_or(time, |t| t.min(time)));
+        self.newest = Some(self.newest.map_or(time, |t| t.max(time)));
+        self.count += 1;
+    }
+}
+
+impl fmt::Display for TimeRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.oldest, self.newest) {
+            (Some(oldest), Some(newest)) => {
+                let plural = if self.count == 1 { "" } else { "s" };
+                write!(
+                    f,
+                    "{} to {} ({} timestamp{})",
+                    oldest, newest, self.count, plural
+                )
+            }
+            _ => write!(f, "no timestamps"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_unix() {
+        assert_eq!(Timestamp::from_unix(0).to_string(), "1970-01-01 00:00:00");
+        assert_eq!(
+            Timestamp::from_unix(1_709_210_096).to_string(),
+            "2024-02-29 12:34:56"
+        );
+    }
+
+    #[test]
+    fn test_time_range() {
+        let mut range = TimeRange::default();
+        assert_eq!(range.to_string(), "no timestamps");
+        range.add(Timestamp::from_unix(2_000_000_000));
+        range.add(Timestamp::from_unix(1_000_000_000));
+        range.add(Timestamp::from_unix(1_500_000_000));
+        assert_eq!(
+            range.to_string(),
+            "2001-09-09 01:46:40 to 2033-05-18 03:33:20 (3 timestamps)"
+        );
+    }
+}
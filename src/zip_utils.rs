@@ -1,23 +1,442 @@
-/// Slice the data to include only up to the end of the ZIP's EOCD record
+use std::fmt;
+use std::io::{self, Cursor};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use zip::read::ZipArchive;
+use zip::result::{ZipError, ZipResult};
+use zip::{CompressionMethod, DateTime};
+
+use crate::timestamp::Timestamp;
+
+/// Size of the fixed part of the EOCD record
+const EOCD_LEN: usize = 22;
+
+/// Furthest the EOCD record can start from the end of a ZIP: the record plus a maximal comment
+pub const MAX_EOCD_SEARCH: usize = EOCD_LEN + 0xFFFF;
+
+/// Smallest possible central directory file header (no name, extra field or comment)
+const CENTRAL_HEADER_LEN: usize = 46;
+
+/// Find the start of a ZIP's EOCD record in its trailing `MAX_EOCD_SEARCH` bytes
+fn find_eocd_start(data: &[u8]) -> Option<usize> {
+    let last = data.len().checked_sub(EOCD_LEN)?;
+    let first = data.len().saturating_sub(MAX_EOCD_SEARCH);
+    (first..=last)
+        .rev()
+        .find(|&i| &data[i..i + 4] == b"PK\x05\x06")
+}
+
+/// Check the EOCD's central directory fields against the archive before trusting them
+/// A crafted entry count or directory size can make opening the archive slow or allocate
+/// heavily; well-formed archives always pass. ZIP64 archives are not checked
+pub fn check_central_directory(zip: &[u8]) -> Result<(), String> {
+    let eocd = match find_eocd_start(zip) {
+        Some(i) => i,
+        None => return Err("no EOCD record".to_string()),
+    };
+    let field = |at: usize, len: usize| {
+        zip[eocd + at..eocd + at + len]
+            .iter()
+            .rev()
+            .fold(0usize, |acc, &b| (acc << 8) | b as usize)
+    };
+    let entries = field(10, 2);
+    let cd_size = field(12, 4);
+    let cd_offset = field(16, 4);
+    if entries == 0xffff || cd_size == 0xffff_ffff || cd_offset == 0xffff_ffff {
+        return Ok(());
+    }
+
+    if entries.saturating_mul(CENTRAL_HEADER_LEN) > cd_size {
+        return Err(format!(
+            "central directory claims {} entries in {} bytes",
+            entries, cd_size
+        ));
+    }
+    if cd_size > eocd {
+        return Err(format!(
+            "central directory ({} bytes) is larger than the archive before it ({} bytes)",
+            cd_size, eocd
+        ));
+    }
+    Ok(())
+}
+
+/// Open the archive on a worker thread and report whether it failed to finish in time
+/// The worker gets its own copy of the data and is abandoned, not killed, on timeout
+pub fn zip_open_timed_out(zip: &[u8], timeout: Duration) -> bool {
+    open_zip_with_timeout(zip, timeout).is_none()
+}
+
+/// An archive over its own reference-counted copy of the data
+pub type SharedZipArchive = ZipArchive<Cursor<Arc<[u8]>>>;
+
+/// Open the archive on a worker thread, giving up on it if that takes longer than `timeout`
+/// The worker gets its own (shared, cheaply cloned) copy of the data and is abandoned, not
+/// killed, on timeout. None means it timed out; otherwise this is the opened archive
+pub fn open_zip_with_timeout(zip: &[u8], timeout: Duration) -> Option<ZipResult<SharedZipArchive>> {
+    let data: Arc<[u8]> = Arc::from(zip);
+    let (done, finished) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = done.send(ZipArchive::new(Cursor::new(data)));
+    });
+    match finished.recv_timeout(timeout) {
+        Ok(opened) => Some(opened),
+        Err(RecvTimeoutError::Timeout) => None,
+        Err(RecvTimeoutError::Disconnected) => Some(Err(ZipError::Io(io::Error::other(
+            "ZIP open worker panicked",
+        )))),
+    }
+}
+
+/// Where a ZIP's EOCD record was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EocdInfo {
+    /// Offset of the `PK\x05\x06` signature
+    pub offset: usize,
+    /// Length of the archive comment that follows the fixed record
+    pub comment_len: usize,
+    /// Bytes from the start of the data to the end of the comment: the archive's full size
+    pub total_size: usize,
+}
+
+/// Why no usable EOCD record was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EocdError {
+    /// The data is smaller than an EOCD record
+    TooShort { len: usize },
+    /// No EOCD signature in the searched range
+    NotFound,
+    /// A signature was found, but its comment runs past the end of the data
+    CommentOverrunsBuffer { offset: usize, comment_len: usize },
+}
+
+impl fmt::Display for EocdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EocdError::TooShort { len } => write!(
+                f,
+                "too short for a ZIP EOCD record ({} of {} bytes)",
+                len, EOCD_LEN
+            ),
+            EocdError::NotFound => write!(f, "no ZIP EOCD record found"),
+            EocdError::CommentOverrunsBuffer {
+                offset,
+                comment_len,
+            } => write!(
+                f,
+                "EOCD at 0x{:x} declares a {}-byte comment that runs past the end of the data",
+                offset, comment_len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EocdError {}
+
+/// Locate a ZIP's EOCD record in its trailing `MAX_EOCD_SEARCH` bytes, where a valid one must live
+pub fn find_eocd(data: &[u8]) -> Result<EocdInfo, EocdError> {
+    find_eocd_with(data, false)
+}
+
+/// Like `find_eocd`, but with `full_scan` searches the whole buffer backwards
+/// This finds ZIPs followed by more than 64 KiB of trailing data, at O(n) cost
+pub fn find_eocd_with(data: &[u8], full_scan: bool) -> Result<EocdInfo, EocdError> {
+    let last = data
+        .len()
+        .checked_sub(EOCD_LEN)
+        .ok_or(EocdError::TooShort { len: data.len() })?;
+    let first = if full_scan {
+        0
+    } else {
+        data.len().saturating_sub(MAX_EOCD_SEARCH)
+    };
+
+    let offset = (first..=last)
+        .rev()
+        .find(|&i| &data[i..i + 4] == b"PK\x05\x06")
+        .ok_or(EocdError::NotFound)?;
+    let comment_len = u16::from_le_bytes([data[offset + 20], data[offset + 21]]) as usize;
+    let total_size = offset + EOCD_LEN + comment_len;
+    if total_size > data.len() {
+        return Err(EocdError::CommentOverrunsBuffer {
+            offset,
+            comment_len,
+        });
+    }
+    Ok(EocdInfo {
+        offset,
+        comment_len,
+        total_size,
+    })
+}
+
+/// Find the end of a ZIP: the offset just past its EOCD record and comment
+/// Only the trailing `MAX_EOCD_SEARCH` bytes are scanned, where a valid EOCD must live
+/// A comment that runs past the buffer is clamped to `data.len()`
 /// Returns None if no valid EOCD marker is found
+pub fn find_eocd_end(data: &[u8]) -> Option<usize> {
+    find_eocd_end_with(data, false)
+}
+
+/// Like `find_eocd_end`, but with `full_scan` searches the whole buffer backwards
+/// This finds ZIPs followed by more than 64 KiB of trailing data, at O(n) cost
+pub fn find_eocd_end_with(data: &[u8], full_scan: bool) -> Option<usize> {
+    match find_eocd_with(data, full_scan) {
+        Ok(eocd) => Some(eocd.total_size),
+        Err(EocdError::CommentOverrunsBuffer { .. }) => Some(data.len()),
+        Err(_) => None,
+    }
+}
+
+/// The bytes after a ZIP's EOCD record and comment, such as an appended signature, which
+/// `slice_to_eocd` cuts off; empty if the ZIP runs to the end of the data
+/// Returns None if no valid EOCD marker is found
+pub fn trailing_after_eocd(data: &[u8]) -> Option<&[u8]> {
+    trailing_after_eocd_with(data, false)
+}
+
+/// Like `trailing_after_eocd`, but with `full_scan` searches the whole buffer backwards
+pub fn trailing_after_eocd_with(data: &[u8], full_scan: bool) -> Option<&[u8]> {
+    find_eocd_end_with(data, full_scan).map(|end| &data[end..])
+}
+
+/// Slice the data to include only up to the end of the ZIP's EOCD record
+/// Only the trailing `MAX_EOCD_SEARCH` bytes are scanned, where a valid EOCD must live
+/// Returns None if no valid EOCD marker is found; `find_eocd` says why
 pub fn slice_to_eocd(data: &[u8]) -> Option<&[u8]> {
-    for i in (0..=data.len().saturating_sub(22)).rev() {
-        if data.len() >= i + 4 && &data[i..i + 4] == b"PK\x05\x06" && data.len() >= i + 22 {
-            let comment_len = u16::from_le_bytes([data[i + 20], data[i + 21]]) as usize;
-            let end = i + 22 + comment_len;
-            if end <= data.len() {
-                return Some(&data[..end]);
-            } else {
-                return Some(data);
-            }
+    slice_to_eocd_with(data, false)
+}
+
+/// Like `slice_to_eocd`, but with `full_scan` searches the whole buffer backwards
+pub fn slice_to_eocd_with(data: &[u8], full_scan: bool) -> Option<&[u8]> {
+    find_eocd_end_with(data, full_scan).map(|end| &data[..end])
+}
+
+/// A ZIP member's central directory record, read without touching its contents
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZipMemberInfo {
+    pub name: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    pub compressed_size: u64,
+    pub method: CompressionMethod,
+    pub crc32: u32,
+    /// DOS modification time, if one was recorded
+    pub modified: Option<Timestamp>,
+}
+
+/// A member's DOS modification time, treating the 1980-01-01 00:00 default as unset
+pub fn dos_timestamp(time: DateTime) -> Option<Timestamp> {
+    if time == DateTime::default() || !time.is_valid() {
+        return None;
+    }
+    Some(Timestamp {
+        year: time.year(),
+        month: time.month(),
+        day: time.day(),
+        hour: time.hour(),
+        minute: time.minute(),
+        second: time.second(),
+    })
+}
+
+/// List a ZIP's members in central directory order without reading or extracting them
+/// Trailing bytes after the archive are ignored, as in `slice_to_eocd`
+pub fn list_zip_members(data: &[u8]) -> io::Result<Vec<ZipMemberInfo>> {
+    let zip = match find_eocd(data) {
+        Ok(eocd) => &data[..eocd.total_size],
+        Err(EocdError::CommentOverrunsBuffer { .. }) => data,
+        Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+    };
+    let mut archive = ZipArchive::new(Cursor::new(zip)).map_err(io::Error::other)?;
+
+    (0..archive.len())
+        .map(|i| {
+            let member = archive.by_index_raw(i).map_err(io::Error::other)?;
+            Ok(ZipMemberInfo {
+                name: member.name().to_string(),
+                size: member.size(),
+                compressed_size: member.compressed_size(),
+                method: member.compression(),
+                crc32: member.crc32(),
+                modified: member.last_modified().and_then(dos_timestamp),
+            })
+        })
+        .collect()
+}
+
+/// A ZIP local file header found by scanning for its signature
+#[derive(Debug)]
+pub struct LocalHeader {
+    pub offset: usize,
+    pub name: Option<String>,
+}
+
+/// Scan forward for `PK\x03\x04` local file headers, salvaging their filenames
+/// Useful when the central directory is missing, e.g. on a truncated archive
+pub fn scan_local_headers(data: &[u8]) -> Vec<LocalHeader> {
+    let mut headers = Vec::new();
+    let mut i = 0;
+
+    while i + 4 <= data.len() {
+        if &data[i..i + 4] != b"PK\x03\x04" {
+            i += 1;
+            continue;
+        }
+
+        // Fixed part of the local header is 30 bytes
+        if data.len() < i + 30 {
+            headers.push(LocalHeader {
+                offset: i,
+                name: None,
+            });
+            break;
         }
+
+        let flags = u16::from_le_bytes([data[i + 6], data[i + 7]]);
+        let compressed_size =
+            u32::from_le_bytes([data[i + 18], data[i + 19], data[i + 20], data[i + 21]]) as usize;
+        let name_len = u16::from_le_bytes([data[i + 26], data[i + 27]]) as usize;
+        let extra_len = u16::from_le_bytes([data[i + 28], data[i + 29]]) as usize;
+
+        let name = data
+            .get(i + 30..i + 30 + name_len)
+            .map(|b| String::from_utf8_lossy(b).to_string());
+        headers.push(LocalHeader { offset: i, name });
+
+        // Skip the member's data when its size is known up front (no data descriptor)
+        // A bogus size saturates past the end of the buffer, ending the scan
+        let data_start = i + 30 + name_len + extra_len;
+        i = if flags & 0x08 == 0 {
+            data_start.saturating_add(compressed_size)
+        } else {
+            data_start
+        };
     }
-    None
+
+    headers
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::build_zip;
+
+    fn local_header(name: &str, content: &[u8]) -> Vec<u8> {
+        let mut data = b"PK\x03\x04".to_vec();
+        data.extend_from_slice(&[0u8; 14]); // version, flags, method, time, date, crc
+        data.extend_from_slice(&(content.len() as u32).to_le_bytes()); // compressed size
+        data.extend_from_slice(&(content.len() as u32).to_le_bytes()); // uncompressed size
+        data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        data.extend_from_slice(&[0x00, 0x00]); // extra length
+        data.extend_from_slice(name.as_bytes());
+        data.extend_from_slice(content);
+        data
+    }
+
+    fn real_zip() -> Vec<u8> {
+        build_zip(&[("a.txt", b"data"), ("b.txt", b"data")])
+    }
+
+    #[test]
+    fn test_check_central_directory() {
+        let zip = real_zip();
+        assert_eq!(check_central_directory(&zip), Ok(()));
+        let eocd = find_eocd_start(&zip).unwrap();
+
+        let mut many_entries = zip.clone();
+        many_entries[eocd + 10..eocd + 12].copy_from_slice(&0xfff0u16.to_le_bytes());
+        assert!(check_central_directory(&many_entries)
+            .unwrap_err()
+            .contains("65520 entries"));
+
+        let mut huge_directory = zip.clone();
+        huge_directory[eocd + 12..eocd + 16].copy_from_slice(&0x7fff_0000u32.to_le_bytes());
+        assert!(check_central_directory(&huge_directory).is_err());
+    }
+
+    #[test]
+    fn test_list_zip_members() {
+        let mut zip = real_zip();
+        zip.extend_from_slice(b"trailing");
+
+        let members = list_zip_members(&zip).unwrap();
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].name, "a.txt");
+        assert_eq!(members[1].name, "b.txt");
+        assert_eq!(members[0].size, 4);
+        assert_eq!(members[0].crc32, crc32fast::hash(b"data"));
+        assert_eq!(members[0].method, CompressionMethod::Deflated);
+
+        assert!(list_zip_members(b"not a zip").is_err());
+    }
+
+    #[test]
+    fn test_dos_timestamp() {
+        let time = DateTime::from_date_and_time(2023, 7, 14, 9, 30, 12).unwrap();
+        assert_eq!(
+            dos_timestamp(time).map(|t| t.to_string()).as_deref(),
+            Some("2023-07-14 09:30:12")
+        );
+        assert_eq!(dos_timestamp(DateTime::default()), None);
+    }
+
+    #[test]
+    fn test_zip_open_within_timeout() {
+        assert!(!zip_open_timed_out(&real_zip(), Duration::from_secs(10)));
+
+        // The archive opened on the worker comes back ready to read
+        let mut archive = open_zip_with_timeout(&real_zip(), Duration::from_secs(10))
+            .unwrap()
+            .unwrap();
+        assert_eq!(archive.len(), 2);
+        assert_eq!(archive.by_index(0).unwrap().name(), "a.txt");
+        assert!(open_zip_with_timeout(b"not a zip", Duration::from_secs(10))
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_scan_local_headers() {
+        let mut data = local_header("a.txt", b"PK\x03\x04 inside content");
+        data.extend_from_slice(&local_header("dir/b.bin", b"more"));
+
+        let headers = scan_local_headers(&data);
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0].offset, 0);
+        assert_eq!(headers[0].name.as_deref(), Some("a.txt"));
+        assert_eq!(headers[1].name.as_deref(), Some("dir/b.bin"));
+    }
+
+    #[test]
+    fn test_scan_local_headers_truncated() {
+        let mut data = local_header("a.txt", b"content");
+        data.extend_from_slice(b"PK\x03\x04\x14\x00");
+
+        let headers = scan_local_headers(&data);
+        assert_eq!(headers.len(), 2);
+        assert!(headers[1].name.is_none());
+    }
+
+    #[test]
+    fn test_scan_local_headers_huge_size() {
+        let mut data = local_header("a.txt", b"content");
+        data[18..22].copy_from_slice(&[0xff; 4]); // compressed size
+        data.extend_from_slice(&local_header("b.txt", b"skipped"));
+
+        let headers = scan_local_headers(&data);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].name.as_deref(), Some("a.txt"));
+    }
+
+    #[test]
+    fn test_scan_local_headers_none() {
+        assert!(scan_local_headers(b"not a zip at all").is_empty());
+    }
 
     #[test]
     fn test_slice_to_eocd_basic() {
@@ -37,6 +456,19 @@ mod tests {
         assert_eq!(slice.len(), expected_len);
     }
 
+    #[test]
+    fn test_trailing_after_eocd() {
+        let mut data = b"PK\x05\x06".to_vec();
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&[0x00, 0x00]);
+        assert_eq!(trailing_after_eocd(&data), Some(&[][..]));
+
+        data.extend_from_slice(b"RSA-SIGNATURE");
+        assert_eq!(trailing_after_eocd(&data), Some(&b"RSA-SIGNATURE"[..]));
+        assert_eq!(slice_to_eocd(&data).map(|s| s.len()), Some(22));
+        assert_eq!(trailing_after_eocd(b"no zip here at all, none"), None);
+    }
+
     #[test]
     fn test_slice_to_eocd_with_comment() {
         let mut data = vec![b'P', b'K', 0x03, 0x04];
@@ -55,6 +487,40 @@ mod tests {
         assert_eq!(slice.len(), expected_len);
     }
 
+    #[test]
+    fn test_find_eocd_errors() {
+        let mut data = vec![0u8; 10];
+        data.extend_from_slice(b"PK\x05\x06");
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&[0x03, 0x00]);
+        data.extend_from_slice(b"abc");
+        assert_eq!(
+            find_eocd(&data),
+            Ok(EocdInfo {
+                offset: 10,
+                comment_len: 3,
+                total_size: 35,
+            })
+        );
+
+        // The comment claims more bytes than remain; slicing still keeps everything
+        data.truncate(33);
+        assert_eq!(
+            find_eocd(&data),
+            Err(EocdError::CommentOverrunsBuffer {
+                offset: 10,
+                comment_len: 3,
+            })
+        );
+        assert_eq!(slice_to_eocd(&data).map(<[u8]>::len), Some(33));
+
+        assert_eq!(find_eocd(&[0u8; 40]), Err(EocdError::NotFound));
+        assert_eq!(
+            find_eocd(b"PK\x05\x06"),
+            Err(EocdError::TooShort { len: 4 })
+        );
+    }
+
     #[test]
     fn test_slice_to_eocd_not_found() {
         let data = b"This is not a ZIP file at all";
@@ -62,6 +528,15 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_empty_input() {
+        assert!(slice_to_eocd(b"").is_none());
+        assert!(slice_to_eocd_with(b"", true).is_none());
+        assert!(scan_local_headers(b"").is_empty());
+        assert!(check_central_directory(b"").is_err());
+        assert!(list_zip_members(b"").is_err());
+    }
+
     #[test]
     fn test_slice_to_eocd_too_short() {
         let data = b"PK\x05\x06short"; // Less than 22 bytes after signature
@@ -69,6 +544,59 @@ mod tests {
         assert!(result.is_none());
     }
 
+    #[test]
+    fn test_slice_to_eocd_ignores_marker_outside_window() {
+        // A stray EOCD-looking marker far from the end of a large non-ZIP payload
+        let mut data = vec![];
+        data.extend_from_slice(b"PK\x05\x06");
+        data.extend_from_slice(&[0u8; 18]);
+        data.extend_from_slice(&[0x00, 0x00]);
+        data.extend_from_slice(&vec![0xAAu8; MAX_EOCD_SEARCH + 100]);
+
+        assert!(slice_to_eocd(&data).is_none());
+        // The full scan fallback still finds it
+        assert_eq!(slice_to_eocd_with(&data, true).map(|s| s.len()), Some(22));
+    }
+
+    #[test]
+    fn test_slice_to_eocd_max_comment_in_window() {
+        let mut data = vec![b'P', b'K', 0x03, 0x04];
+        data.extend_from_slice(&[0u8; 1000]);
+        data.extend_from_slice(b"PK\x05\x06");
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&[0xff, 0xff]); // Maximal comment length
+        data.extend_from_slice(&vec![b'c'; 0xffff]);
+
+        let slice = slice_to_eocd(&data).unwrap();
+        assert_eq!(slice.len(), data.len());
+    }
+
+    #[test]
+    fn test_find_eocd_end_with_trailing_data() {
+        let mut data = vec![b'P', b'K', 0x03, 0x04];
+        data.extend_from_slice(&[0u8; 10]);
+        data.extend_from_slice(b"PK\x05\x06");
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&[0x03, 0x00]); // Comment length = 3
+        data.extend_from_slice(b"abc");
+        let zip_end = data.len();
+        data.extend_from_slice(b"trailing bytes after the archive");
+
+        assert_eq!(find_eocd_end(&data), Some(zip_end));
+        assert_eq!(slice_to_eocd(&data).map(|s| s.len()), Some(zip_end));
+    }
+
+    #[test]
+    fn test_find_eocd_end_clamps_overlong_comment() {
+        let mut data = b"PK\x05\x06".to_vec();
+        data.extend_from_slice(&[0u8; 16]);
+        data.extend_from_slice(&[0x10, 0x00]); // Claims a 16-byte comment
+        data.extend_from_slice(b"short");
+
+        assert_eq!(find_eocd_end(&data), Some(data.len()));
+        assert_eq!(find_eocd_end(b"PK\x05\x06"), None);
+    }
+
     #[test]
     fn test_slice_to_eocd_multiple_markers() {
         // Should find the last EOCD marker
@@ -0,0 +1,19 @@
+//! Fixture builders shared by the integration tests and the benchmarks
+//!
+//! Built on the unit tests' builders, so there's one way to lay out an entry or a ZIP.
+
+#[allow(dead_code)]
+#[path = "../../src/test_support.rs"]
+mod support;
+
+pub(crate) use support::build_zip as zip_archive;
+
+/// One container entry: the three tags, a fixed header and the content's CRC32 as its meta
+pub fn container_entry(filename: &str, content: &[u8]) -> Vec<u8> {
+    support::build_entry(
+        filename,
+        content,
+        crc32fast::hash(content).to_be_bytes(),
+        content.len() as u32,
+    )
+}
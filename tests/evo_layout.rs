@@ -0,0 +1,93 @@
+//! End-to-end processing of a synthesized container shaped like an EVO firmware image
+//! (`EVO_FW_V1.5.8.bin`): a JSON config, a gimbal UPG, an embedded ZIP and a GPS blob
+
+mod common;
+
+use std::path::PathBuf;
+
+use firmparse::extract::extract_to_memory;
+use firmparse::file_types::FileType;
+use firmparse::integrity::verify_data;
+use firmparse::options::ProcessOptions;
+use firmparse::processor::process_file_with;
+
+use common::{container_entry, zip_archive};
+
+/// Build an EVO-style firmware image
+fn evo_firmware() -> Vec<u8> {
+    let config = br#"{"product": "EVO", "version": "1.5.8", "modules": ["gimbal", "gps"]}"#;
+
+    let mut gimbal = vec![0x34, 0x12, 0xef, 0xbe, 0x00, 0x00, 0x00, 0x00];
+    gimbal.extend((0..248u32).map(|i| (i * 7) as u8));
+
+    let package = zip_archive(&[
+        ("app/readme.txt", b"EVO application package\n"),
+        ("settings.json", br#"{"baud": 115200}"#),
+    ]);
+
+    let mut gps = b"@TD1050x".to_vec();
+    gps.extend_from_slice(&[0x00, 0x80, 0xff, 0x10, 0x20, 0x30, 0x40, 0x50]);
+
+    let mut firmware = container_entry("config.json", config);
+    firmware.extend_from_slice(&container_entry("gimbal.upg", &gimbal));
+    firmware.extend_from_slice(&container_entry("package.zip", &package));
+    firmware.extend_from_slice(&container_entry("gps.bin", &gps));
+    firmware
+}
+
+#[test]
+fn test_evo_layout_classifies_every_leaf() {
+    let firmware = evo_firmware();
+
+    let mut extracted: Vec<(PathBuf, FileType)> = Vec::new();
+    process_file_with(
+        &firmware,
+        Some("EVO_FW_V1.5.8.bin"),
+        &ProcessOptions::default(),
+        &mut |file| {
+            extracted.push((file.path.clone(), file.file_type));
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    let expected = [
+        ("EVO_FW_V1.5.8/config.json", FileType::Json),
+        ("EVO_FW_V1.5.8/gimbal.upg", FileType::UpgGimbal),
+        ("EVO_FW_V1.5.8/package.zip", FileType::Zip),
+        ("EVO_FW_V1.5.8/package/app/readme.txt", FileType::Text),
+        ("EVO_FW_V1.5.8/package/settings.json", FileType::Json),
+        ("EVO_FW_V1.5.8/gps.bin", FileType::GpsBin),
+    ];
+    let expected: Vec<_> = expected
+        .iter()
+        .map(|(path, file_type)| (PathBuf::from(path), *file_type))
+        .collect();
+    assert_eq!(extracted, expected);
+}
+
+#[test]
+fn test_evo_layout_tree_shape() {
+    let firmware = evo_firmware();
+    let tree = extract_to_memory(&firmware, Some("EVO_FW_V1.5.8.bin"));
+    assert_eq!(tree.file_type, FileType::AutelContainer);
+
+    let types: Vec<_> = tree.children.iter().map(|c| c.file_type).collect();
+    assert_eq!(
+        types,
+        [
+            FileType::Json,
+            FileType::UpgGimbal,
+            FileType::Zip,
+            FileType::GpsBin
+        ]
+    );
+    assert_eq!(tree.children[2].children.len(), 2);
+}
+
+#[test]
+fn test_evo_layout_verifies() {
+    let report = verify_data(&evo_firmware(), Some("EVO_FW_V1.5.8.bin"));
+    assert!(report.passed(), "{:?}", report.checks);
+    assert_eq!(report.checks.len(), 5);
+}